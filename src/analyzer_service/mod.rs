@@ -2,5 +2,5 @@
 include!("provider.rs");
 
 pub mod proto {
-    pub(crate) const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("provider_service_descriptor.bin");
+    pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("provider_service_descriptor.bin");
 }
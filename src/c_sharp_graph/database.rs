@@ -0,0 +1,458 @@
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int},
+    path::Path,
+    sync::{Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use rusqlite::{ffi, params, Connection, ErrorCode, OpenFlags, OptionalExtension};
+use stack_graphs::storage::{SQLiteReader, SQLiteWriter, StorageError};
+use tracing::{debug, warn};
+
+const MAX_ATTEMPTS: u32 = 10;
+const FIRST_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Version of how this tool lays out what it indexes, such as the source type symbols and the
+/// file tags. Bump it with any change to them, databases stamped with another version are
+/// indexed again rather than read.
+pub const INDEX_FORMAT_VERSION: u32 = 8;
+
+/// What a database was indexed with, see [`stamp_index_format`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexStamp {
+    pub tool_version: String,
+    pub format_version: u32,
+    /// The filters that picked the files, see `loader::IndexOptions::filters`.
+    pub filters: String,
+}
+
+/// Opens the database for writing, see [`with_busy_retry`].
+pub fn open_writer(path: &Path) -> Result<SQLiteWriter, StorageError> {
+    with_busy_retry(|| SQLiteWriter::open(path))
+}
+
+/// How much memory SQLite may use to read a database, see [`open_reader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadPragmas {
+    /// Bytes of pages cached by the connection, on the heap of the process.
+    pub cache_size: u64,
+    /// Bytes of the file mapped into memory. The pages are the operating system's file cache,
+    /// they add to the resident size of the process as they are read but can be reclaimed.
+    pub mmap_size: u64,
+}
+
+impl ReadPragmas {
+    /// SQLite's own default cache.
+    const MIN_CACHE_SIZE: u64 = 2 * 1024 * 1024;
+    const MAX_CACHE_SIZE: u64 = 256 * 1024 * 1024;
+    const MAX_MMAP_SIZE: u64 = 1024 * 1024 * 1024;
+
+    /// The configured sizes, or ones scaled to the database at `path` for those that are not:
+    /// an eighth of it cached, between SQLite's default of 2MiB and 256MiB, and all of it
+    /// mapped up to 1GiB.
+    pub fn for_database(path: &Path, cache_size: Option<u64>, mmap_size: Option<u64>) -> Self {
+        let db_size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        ReadPragmas {
+            cache_size: cache_size
+                .unwrap_or((db_size / 8).clamp(Self::MIN_CACHE_SIZE, Self::MAX_CACHE_SIZE)),
+            mmap_size: mmap_size.unwrap_or(db_size.min(Self::MAX_MMAP_SIZE)),
+        }
+    }
+
+    fn sql(&self) -> String {
+        // A negative cache size is in KiB rather than pages.
+        format!(
+            "PRAGMA cache_size = -{}; PRAGMA mmap_size = {};",
+            self.cache_size.div_ceil(1024),
+            self.mmap_size
+        )
+    }
+}
+
+thread_local! {
+    // The pragmas `apply_pragmas` runs on the connections opened by this thread.
+    static OPENING_WITH: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// How many threads are opening a connection with pragmas, `apply_pragmas` is registered while
+/// any is.
+static OPENING: Mutex<usize> = Mutex::new(0);
+
+/// Opens the database for reading with `pragmas` set on its connection.
+pub fn open_reader(path: &Path, pragmas: &ReadPragmas) -> Result<SQLiteReader, StorageError> {
+    debug!("opening {:?} with {:?}", path, pragmas);
+    with_pragmas(pragmas, || SQLiteReader::open(path))
+}
+
+/// Runs `open` with `pragmas` set on every connection it opens. stack-graphs keeps its
+/// connection to itself, they are set by an extension SQLite runs on each new connection.
+fn with_pragmas<T>(pragmas: &ReadPragmas, open: impl FnOnce() -> T) -> T {
+    let _registered = PragmasExtension::register(pragmas);
+    open()
+}
+
+/// Keeps `apply_pragmas` registered for as long as it lives. The extensions are those of the
+/// whole process, SQLite runs them on every connection any thread opens, so it is unregistered
+/// again once no thread is opening a connection with pragmas.
+struct PragmasExtension;
+
+impl PragmasExtension {
+    fn register(pragmas: &ReadPragmas) -> Self {
+        let mut opening = OPENING.lock().unwrap_or_else(PoisonError::into_inner);
+        if *opening == 0 {
+            // SAFETY: the entry point lives as long as the process and SQLite may call it from
+            // any thread opening a connection, `apply_pragmas` only reads that thread's pragmas.
+            let rc = unsafe { ffi::sqlite3_auto_extension(Some(entry_point())) };
+            if rc != ffi::SQLITE_OK {
+                warn!("unable to register the pragmas extension: {}", rc);
+            }
+        }
+        *opening += 1;
+        OPENING_WITH.with(|sql| sql.replace(Some(pragmas.sql())));
+        PragmasExtension
+    }
+}
+
+impl Drop for PragmasExtension {
+    fn drop(&mut self) {
+        OPENING_WITH.with(|sql| sql.replace(None));
+        let mut opening = OPENING.lock().unwrap_or_else(PoisonError::into_inner);
+        *opening -= 1;
+        if *opening == 0 {
+            // SAFETY: it is the entry point `register` registered, cancelling one that is not
+            // registered does nothing.
+            unsafe { ffi::sqlite3_cancel_auto_extension(Some(entry_point())) };
+        }
+    }
+}
+
+/// `apply_pragmas` as SQLite takes an extension entry point, without its arguments.
+fn entry_point() -> unsafe extern "C" fn() {
+    // SAFETY: only the type of the pointer changes, SQLite casts it back to the signature of
+    // an extension entry point, that of `apply_pragmas`, before calling it.
+    unsafe {
+        std::mem::transmute::<
+            unsafe extern "C" fn(
+                *mut ffi::sqlite3,
+                *mut *mut c_char,
+                *const ffi::sqlite3_api_routines,
+            ) -> c_int,
+            unsafe extern "C" fn(),
+        >(apply_pragmas)
+    }
+}
+
+/// # Safety
+///
+/// Called by SQLite with a connection being opened, as an extension entry point.
+unsafe extern "C" fn apply_pragmas(
+    db: *mut ffi::sqlite3,
+    _err: *mut *mut c_char,
+    _api: *const ffi::sqlite3_api_routines,
+) -> c_int {
+    let Some(sql) = OPENING_WITH.with(|sql| sql.borrow().clone()) else {
+        return ffi::SQLITE_OK;
+    };
+    // SAFETY: the handle is the connection being opened, it stays open after the borrowed
+    // connection is dropped.
+    match unsafe { Connection::from_handle(db) }.and_then(|conn| conn.execute_batch(&sql)) {
+        Ok(()) => {}
+        // Only slower without them, the connection is still opened.
+        Err(e) => warn!("unable to set pragmas `{}`: {}", sql, e),
+    }
+    ffi::SQLITE_OK
+}
+
+/// Runs `op` again, backing off between attempts, while SQLite reports the database as busy or
+/// locked. Dependencies are loaded by concurrent tasks that each write to the same database
+/// through their own connection. Those connections wait up to rusqlite's default busy timeout
+/// for the lock, which a writer storing a large dependency can hold for longer, and stack-graphs
+/// does not expose them to raise it. `op` has to be safe to repeat, which holds for opening the
+/// database and for writes done in a single transaction as a failed one is rolled back.
+pub fn with_busy_retry<T>(
+    mut op: impl FnMut() -> Result<T, StorageError>,
+) -> Result<T, StorageError> {
+    let mut backoff = FIRST_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(err) if is_busy(&err) && attempt < MAX_ATTEMPTS => {
+                debug!("database busy on attempt {}, retrying: {}", attempt, err);
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Records the version of the tool and of the index format in the database, along with the
+/// filters that picked the files, replacing what was recorded before. The table lives next to
+/// the stack-graphs tables, which do not know it.
+pub fn stamp_index_format(path: &Path, filters: &str) -> Result<(), rusqlite::Error> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(
+        "DROP TABLE IF EXISTS index_metadata;
+        CREATE TABLE index_metadata (
+            tool_version TEXT NOT NULL,
+            format_version INTEGER NOT NULL,
+            filters TEXT NOT NULL
+        );",
+    )?;
+    conn.execute(
+        "INSERT INTO index_metadata (tool_version, format_version, filters) VALUES (?1, ?2, ?3)",
+        params![env!("CARGO_PKG_VERSION"), INDEX_FORMAT_VERSION, filters],
+    )?;
+    Ok(())
+}
+
+/// The stamp of the database, `None` for one indexed before databases were stamped.
+pub fn index_stamp(path: &Path) -> Result<Option<IndexStamp>, rusqlite::Error> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let stamped: bool = conn.query_row(
+        "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'index_metadata'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !stamped {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT tool_version, format_version, filters FROM index_metadata",
+        [],
+        |row| {
+            Ok(IndexStamp {
+                tool_version: row.get(0)?,
+                format_version: row.get(1)?,
+                filters: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Moves what the write ahead log holds into the database and empties it, so the database
+/// file is complete on its own once the provider exits.
+pub fn checkpoint(path: &Path) -> Result<(), rusqlite::Error> {
+    let conn = Connection::open(path)?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+}
+
+/// Removes the database along with the write ahead log SQLite keeps next to it.
+pub fn remove_database(path: &Path) -> std::io::Result<()> {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        match std::fs::remove_file(&file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn is_busy(err: &StorageError) -> bool {
+    matches!(
+        err,
+        StorageError::Rusqlite(rusqlite::Error::SqliteFailure(e, _))
+            if matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use stack_graphs::{
+        graph::StackGraph,
+        partial::{PartialPath, PartialPaths},
+        storage::SQLiteReader,
+    };
+    use tree_sitter_stack_graphs::NoCancellation;
+
+    use super::*;
+    use crate::c_sharp_graph::{
+        loader::{add_dir_to_graph, init_stack_graph, IndexOptions, SourceType},
+        query::{NamespaceIndex, Querier, Query, QueryOptions},
+    };
+    use crate::test_support::{language_config, TestDir};
+
+    fn busy() -> StorageError {
+        StorageError::Rusqlite(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        ))
+    }
+
+    #[test]
+    fn busy_errors_are_retried_a_bounded_number_of_times() {
+        let mut attempts = 0;
+        let res = with_busy_retry(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(busy())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(res.unwrap(), 3);
+
+        let mut attempts = 0;
+        let res: Result<(), StorageError> = with_busy_retry(|| {
+            attempts += 1;
+            Err(busy())
+        });
+        assert!(is_busy(&res.unwrap_err()));
+        assert_eq!(attempts, MAX_ATTEMPTS);
+
+        let mut attempts = 0;
+        let res: Result<(), StorageError> = with_busy_retry(|| {
+            attempts += 1;
+            Err(StorageError::MissingDatabase("test.db".to_string()))
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn concurrent_writers_do_not_fail_on_locks() {
+        let dir = TestDir::new("busy-writers");
+        for i in 0..64 {
+            fs::write(
+                dir.join(format!("File{}.cs", i)),
+                format!(
+                    "namespace Demo.Busy {{ public class C{} {{ void M() {{ }} }} }}",
+                    i
+                ),
+            )
+            .unwrap();
+        }
+        let lc = language_config();
+        let mut graph = StackGraph::new();
+        let _ = SourceType::load_symbols_into_graph(&mut graph);
+        let loaded = add_dir_to_graph(
+            &dir,
+            &lc.dependnecy_type_node_info,
+            &lc.language_config,
+            graph,
+            None,
+            &NoCancellation,
+        )
+        .unwrap();
+        let files: Vec<_> = loaded.file_to_tag.iter().collect();
+        let db_path = dir.join("test.db");
+        drop(open_writer(&db_path).unwrap());
+
+        let writers = 8;
+        thread::scope(|s| {
+            let handles: Vec<_> = (0..writers)
+                .map(|w| {
+                    let files = &files;
+                    let graph = &loaded.stack_graph;
+                    let db_path = &db_path;
+                    s.spawn(move || -> Result<(), StorageError> {
+                        let mut db = open_writer(db_path)?;
+                        for (path, tag) in files.iter().skip(w).step_by(writers) {
+                            let file = graph.get_file(&path.to_string_lossy()).unwrap();
+                            let mut partials = PartialPaths::new();
+                            let paths: Vec<PartialPath> = vec![];
+                            with_busy_retry(|| {
+                                db.store_result_for_file(graph, file, tag, &mut partials, &paths)
+                            })?;
+                        }
+                        Ok(())
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap().unwrap();
+            }
+        });
+
+        let mut reader = SQLiteReader::open(&db_path).unwrap();
+        let stored = reader.list_all().unwrap().try_iter().unwrap().count();
+        assert_eq!(stored, files.len());
+    }
+
+    #[test]
+    fn readers_are_opened_with_the_pragmas() {
+        let dir = TestDir::new("read-pragmas");
+        for i in 0..200 {
+            fs::write(
+                dir.join(format!("File{}.cs", i)),
+                format!(
+                    "using Demo.Big;\nnamespace Demo.Big {{ public class C{i} {{ public void M() {{ new C{i}().M(); }} }} }}",
+                ),
+            )
+            .unwrap();
+        }
+        let db_path = dir.join("test.db");
+        let lc = language_config();
+        init_stack_graph(
+            &dir,
+            &db_path,
+            &lc.source_type_node_info,
+            &lc.language_config,
+            &IndexOptions::default(),
+            &NoCancellation,
+            None,
+        )
+        .unwrap();
+
+        let db_size = fs::metadata(&db_path).unwrap().len();
+        let scaled = ReadPragmas::for_database(&db_path, None, None);
+        assert_eq!(scaled.cache_size, ReadPragmas::MIN_CACHE_SIZE);
+        assert_eq!(scaled.mmap_size, db_size);
+        let pragmas = ReadPragmas::for_database(&db_path, Some(8 << 20), Some(64 << 20));
+        assert_eq!(
+            pragmas,
+            ReadPragmas {
+                cache_size: 8 << 20,
+                mmap_size: 64 << 20,
+            }
+        );
+
+        let pragma = |conn: &Connection, name: &str| -> i64 {
+            conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+                .unwrap()
+        };
+        let conn = with_pragmas(&pragmas, || Connection::open(&db_path)).unwrap();
+        assert_eq!(pragma(&conn, "cache_size"), -8192);
+        assert_eq!(pragma(&conn, "mmap_size"), 64 << 20);
+        // Only the connections opened for reading are changed.
+        let conn = Connection::open(&db_path).unwrap();
+        assert_eq!(pragma(&conn, "mmap_size"), 0);
+
+        let mut reader = open_reader(&db_path, &pragmas).unwrap();
+        reader
+            .load_graphs_for_file_or_directory(&dir, &stack_graphs::NoCancellation)
+            .unwrap();
+        let mut graph = StackGraph::new();
+        reader
+            .get()
+            .0
+            .to_serializable()
+            .load_into(&mut graph)
+            .unwrap();
+        assert_eq!(
+            graph
+                .iter_files()
+                .filter(|f| graph[*f].name().ends_with(".cs"))
+                .count(),
+            200
+        );
+        let mut index = NamespaceIndex::new(&graph);
+        let results = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("Demo.Big.*".to_string())
+        .unwrap();
+        assert!(results.len() >= 200, "{}", results.len());
+    }
+}
@@ -5,15 +5,17 @@ use anyhow::anyhow;
 use anyhow::Error;
 use tracing::debug;
 
+use crate::c_sharp_graph::query::NamespaceIndex;
 use crate::c_sharp_graph::query::Querier;
 use crate::c_sharp_graph::query::Query;
+use crate::c_sharp_graph::query::QueryOptions;
 use crate::c_sharp_graph::results::ResultNode;
-use crate::provider::Project;
+use crate::provider::{AnalysisMode, AtomicCancellationFlag, Project, QueryMode};
 
+#[derive(Debug)]
 pub struct FindNode {
-    #[allow(dead_code)]
-    pub node_type: Option<String>,
     pub regex: String,
+    pub options: QueryOptions,
 }
 
 impl FindNode {
@@ -30,6 +32,30 @@ impl FindNode {
                 ));
             }
         };
+        let options = QueryOptions {
+            dependencies_only: project.analysis_mode == AnalysisMode::DependenciesOnly,
+            ..self.options
+        };
+        if project.query_mode == QueryMode::DbBacked {
+            let files = project
+                .namespace_files
+                .lock()
+                .expect("unable to get namespace files")
+                .as_ref()
+                .ok_or_else(|| anyhow!("project graph not found, may not be initialized"))?
+                .files(&self.regex, &options)?;
+            debug!("reading {} files back from the databases", files.len());
+            return project.with_graph_of_files(&files, &AtomicCancellationFlag::new(), |graph| {
+                let mut index = NamespaceIndex::new(graph);
+                let mut q = Querier::get_query(
+                    graph,
+                    Arc::as_ref(&source_node_type_info),
+                    &mut index,
+                    options,
+                );
+                q.query(self.regex)
+            });
+        }
         let mut graph_guard = project.graph.lock().expect("unable to get project graph");
         let graph = match graph_guard.deref_mut() {
             Some(x) => x,
@@ -37,7 +63,12 @@ impl FindNode {
                 return Err(anyhow!("project graph not found, may not be initialized"));
             }
         };
-        let mut q = Querier::get_query(graph, Arc::as_ref(&source_node_type_info));
+        let mut index_guard = project
+            .namespace_index
+            .lock()
+            .expect("unable to get namespace index");
+        let index = index_guard.get_or_insert_with(|| NamespaceIndex::new(graph));
+        let mut q = Querier::get_query(graph, Arc::as_ref(&source_node_type_info), index, options);
 
         q.query(self.regex)
     }
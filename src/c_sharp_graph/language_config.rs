@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 use std::borrow::Cow;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use anyhow::Error;
 use stack_graphs::graph::NodeID;
@@ -14,6 +14,7 @@ use tree_sitter_stack_graphs::loader::Loader;
 use tree_sitter_stack_graphs::CancellationFlag;
 use tree_sitter_stack_graphs::StackGraphLanguage;
 use tree_sitter_stack_graphs::FILE_PATH_VAR;
+use tree_sitter_stack_graphs::ROOT_PATH_VAR;
 
 use crate::c_sharp_graph::loader::SourceType;
 use crate::c_sharp_graph::loader::SOURCE_TYPE_NODE;
@@ -38,6 +39,19 @@ pub struct SourceNodeLanguageConfiguration {
 }
 
 impl SourceNodeLanguageConfiguration {
+    /// The configuration built by the first caller, for every project after it. It is only
+    /// read once built, and building it compiles the TSG rules again.
+    pub fn shared(
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Arc<SourceNodeLanguageConfiguration>, Error> {
+        static SHARED: OnceLock<Arc<SourceNodeLanguageConfiguration>> = OnceLock::new();
+        if let Some(shared) = SHARED.get() {
+            return Ok(shared.clone());
+        }
+        let built = Arc::new(Self::new(cancellation_flag)?);
+        Ok(SHARED.get_or_init(|| built).clone())
+    }
+
     pub fn new(
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<SourceNodeLanguageConfiguration, Error> {
@@ -66,6 +80,10 @@ impl SourceNodeLanguageConfiguration {
         builtins_globals
             .add(FILE_PATH_VAR.into(), BUILTINS_FILENAME.into())
             .unwrap_or_default();
+        // The builtins are shared by every project, so they are under no root.
+        builtins_globals
+            .add(ROOT_PATH_VAR.into(), "".into())
+            .unwrap_or_default();
 
         let file = builtins.add_file(BUILTINS_FILENAME).unwrap();
         let source_type_node_id = source_type_node_info.load_node_to_graph(&mut builtins, file)?;
@@ -2,10 +2,12 @@ use std::fmt::Debug;
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    sync::mpsc,
 };
 
 use anyhow::{anyhow, Error, Result};
 use base64::Engine;
+use ignore::WalkBuilder;
 use sha1::{Digest, Sha1};
 use stack_graphs::{
     arena::Handle,
@@ -13,13 +15,15 @@ use stack_graphs::{
     partial::{PartialPath, PartialPaths},
     storage::SQLiteWriter,
 };
-use tracing::{debug, error, trace};
+use tracing::{debug, error, trace, warn};
 use tree_sitter_stack_graphs::{
     loader::{FileReader, LanguageConfiguration},
-    NoCancellation, Variables, FILE_PATH_VAR, ROOT_PATH_VAR,
+    BuildError, CancellationFlag, Variables, FILE_PATH_VAR, ROOT_PATH_VAR,
 };
 use walkdir::WalkDir;
 
+use crate::c_sharp_graph::database::{open_writer, stamp_index_format, with_busy_retry};
+
 pub const SOURCE_TYPE_NODE: &str = "SOURCE_TYPE_NODE";
 
 #[derive(PartialEq, Eq, Hash)]
@@ -109,15 +113,58 @@ impl SourceType {
     }
 }
 
+/// A file left out of the graph because it could not be parsed or its graph could not be
+/// built, the rest of the files are still indexed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiagnostic {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+impl std::fmt::Display for FileDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for FileDiagnostic {}
+
+/// What happened to a file while indexing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexOutcome {
+    Loaded,
+    /// Not a file the language indexes.
+    Skipped,
+    /// Left out of the graph, see the diagnostics.
+    Failed,
+}
+
+/// Sent once for every file visited by `init_stack_graph`, so logs and a streaming response
+/// can follow the same indexing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexProgress {
+    pub path: PathBuf,
+    /// Files visited so far, this one included.
+    pub files_visited: usize,
+    pub outcome: IndexOutcome,
+}
+
 pub struct InitializedGraph {
     pub files_loaded: usize,
     pub stack_graph: StackGraph,
+    pub diagnostics: Vec<FileDiagnostic>,
 }
 
 pub struct AsyncInitializeGraph {
     pub files_loaded: usize,
+    /// Files left out for being larger than the size limit.
+    pub files_skipped: usize,
+    /// Files left out because they could not be read, ilspy can write decompiled sources
+    /// that are not valid UTF-8 for assemblies with unusual metadata.
+    pub files_unreadable: usize,
     pub stack_graph: StackGraph,
     pub file_to_tag: HashMap<PathBuf, String>,
+    pub diagnostics: Vec<FileDiagnostic>,
 }
 
 pub fn add_dir_to_graph(
@@ -125,11 +172,17 @@ pub fn add_dir_to_graph(
     source_type: &SourceType,
     language_config: &LanguageConfiguration,
     original_graph: StackGraph,
+    max_file_bytes: Option<u64>,
+    cancellation_flag: &dyn CancellationFlag,
 ) -> Result<AsyncInitializeGraph, Error> {
     let mut stack_graph = original_graph;
     let mut files_loaded = 0;
+    let mut files_skipped = 0;
+    let mut files_unreadable = 0;
     let mut file_to_tag: HashMap<PathBuf, String> = HashMap::new();
+    let mut diagnostics: Vec<FileDiagnostic> = vec![];
     for path in WalkDir::new(source_location).into_iter() {
+        cancellation_flag.check("add_dir_to_graph")?;
         let entry = match path {
             Ok(entry) => {
                 if entry.file_type().is_dir() {
@@ -139,25 +192,27 @@ pub fn add_dir_to_graph(
             }
             Err(err) => return Err(Error::new(err)),
         };
-        let entry_path = entry.to_owned().into_path();
-        let entry_path_str = match entry_path.to_str() {
-            Some(path) => path,
-            None => {
-                return Err(anyhow!("unable to get path string"));
+        if let Some(max_file_bytes) = max_file_bytes {
+            let size = entry.metadata()?.len();
+            if size > max_file_bytes {
+                warn!(
+                    "skipped {:?}, {} bytes is over the limit of {}",
+                    entry.path(),
+                    size,
+                    max_file_bytes
+                );
+                files_skipped += 1;
+                continue;
             }
-        };
-        if let Some(file_handle) = &stack_graph.get_file(entry_path_str) {
-            debug!(
-                "already added file to graph: {:?} - handle: {:?}",
-                &entry_path, file_handle
-            );
-            continue;
         }
+        let entry_path = entry.to_owned().into_path();
         match load_graph_for_file(
             entry_path.clone(),
+            source_location,
             &mut stack_graph,
             language_config,
             source_type,
+            cancellation_flag,
         ) {
             Ok(res) => match res {
                 Some((f, tag)) => {
@@ -169,27 +224,58 @@ pub fn add_dir_to_graph(
                     trace!("skipped file: {:?}", entry_path);
                 }
             },
-            Err(e) => {
-                return Err(anyhow!("unable to load file: {:?} - {}", entry_path, e));
+            // Reading happens before anything of the file is added, so the graph is as if it
+            // was never there.
+            Err(e) if e.downcast_ref::<std::io::Error>().is_some() => {
+                warn!("skipped {:?}, unable to read it: {}", entry_path, e);
+                files_unreadable += 1;
             }
+            Err(e) => match e.downcast::<FileDiagnostic>() {
+                Ok(diagnostic) => diagnostics.push(diagnostic),
+                Err(e) => {
+                    return Err(anyhow!("unable to load file: {:?} - {}", entry_path, e));
+                }
+            },
         }
     }
     Ok(AsyncInitializeGraph {
         files_loaded,
+        files_skipped,
+        files_unreadable,
         stack_graph,
         file_to_tag,
+        diagnostics,
     })
 }
 
-fn load_graph_for_file(
+/// Adds the graph of one file. `root` is the directory being indexed, every file under it gets
+/// the same `ROOT_PATH` so where a file sits below the root never changes how its names are
+/// scoped, files of a namespace resolve each other through the namespace wherever they are.
+pub fn load_graph_for_file(
     entry: PathBuf,
+    root: &Path,
     stack_graph: &mut StackGraph,
     language_config: &LanguageConfiguration,
     source_type: &SourceType,
+    cancellation_flag: &dyn CancellationFlag,
 ) -> Result<Option<(Handle<File>, String)>, Error> {
     let mut file_reader = FileReader::new();
     trace!("loading file: {:?}", entry);
-    let entry_parent = entry.parent().expect("parent path should be available");
+    let entry_str = match entry.to_str() {
+        Some(path) => path,
+        None => {
+            return Err(anyhow!("unable to get path string"));
+        }
+    };
+    // Loading the same directory twice, or source and dependencies that overlap, must not
+    // add a file again.
+    if let Some(file_handle) = stack_graph.get_file(entry_str) {
+        debug!(
+            "already added file to graph: {:?} - handle: {:?}",
+            &entry, file_handle
+        );
+        return Ok(None);
+    }
 
     if !language_config.matches_file(&entry, &mut file_reader)? {
         return Ok(None);
@@ -199,20 +285,17 @@ fn load_graph_for_file(
 
     let mut globals = Variables::new();
     globals
-        .add(
-            FILE_PATH_VAR.into(),
-            entry.to_str().expect("path to string").into(),
-        )
+        .add(FILE_PATH_VAR.into(), entry_str.into())
         .expect("failed to add file path variable");
 
     globals
         .add(
             ROOT_PATH_VAR.into(),
-            entry_parent.to_str().expect("to string").into(),
+            root.to_str().expect("to string").into(),
         )
         .expect("failed to add root path variable");
 
-    let file = match stack_graph.add_file(&entry.to_str().unwrap()) {
+    let file = match stack_graph.add_file(entry_str) {
         Ok(x) => x,
         Err(_) => {
             debug!("this found: {:?}", entry);
@@ -233,48 +316,226 @@ fn load_graph_for_file(
         .add(SOURCE_TYPE_NODE.into(), graph_node.into())
         .expect("adding source type node");
 
-    let build_result = builder.build(&globals, &NoCancellation);
+    let build_result = builder.build(&globals, cancellation_flag);
     if let Err(e) = build_result {
+        cancellation_flag.check("load_graph_for_file")?;
         error!("unable to build graph for {:?}: {:?}", entry, e);
-        return Err(anyhow!("unable to build graph"));
+        // What the file added to the graph so far stays, without a compilation unit nothing
+        // searches it.
+        return Err(Error::new(FileDiagnostic {
+            path: entry.clone(),
+            error: build_error_message(&e),
+        }));
     }
     Ok(Some((file, tag)))
 }
 
+/// Why a file's graph could not be built, where the first syntax error is for a file that
+/// does not parse.
+fn build_error_message(error: &BuildError) -> String {
+    match error {
+        BuildError::ParseErrors(parse_errors) => {
+            let errors = parse_errors.errors();
+            match errors.first() {
+                Some(first) => {
+                    let position = first.node().start_position();
+                    format!(
+                        "{} syntax error(s), the first at line {}, column {}",
+                        errors.len(),
+                        position.row + 1,
+                        position.column + 1
+                    )
+                }
+                None => error.to_string(),
+            }
+        }
+        _ => error.to_string(),
+    }
+}
+
+/// Build output under `obj/` is not part of the project's source, except for the files source
+/// generators emit into `obj/**/generated/`, which are indexed when `include_generated` is set.
+pub fn is_indexed_source(source_location: &Path, path: &Path, include_generated: bool) -> bool {
+    let relative = path.strip_prefix(source_location).unwrap_or(path);
+    let mut components = relative.components().map(|c| c.as_os_str());
+    if !components.any(|c| c == "obj") {
+        return true;
+    }
+    include_generated && components.any(|c| c == "generated")
+}
+
+/// Whether a C# file declares a namespace that is, or is nested in, one of
+/// `include_namespaces`. Every file is included when none are given. The `namespace`
+/// declarations are read from the text rather than parsed, so leaving a file out is cheap, a
+/// nested declaration only counts with the name it is written with. A file that cannot be read
+/// or is not C# is left to be loaded, which decides what to do with it.
+pub fn declares_included_namespace(path: &Path, include_namespaces: &[String]) -> bool {
+    if include_namespaces.is_empty() || path.extension().is_none_or(|ext| ext != "cs") {
+        return true;
+    }
+    let Ok(source) = std::fs::read_to_string(path) else {
+        return true;
+    };
+    source
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("namespace"))
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map(|rest| {
+            rest.trim_start()
+                .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .next()
+                .unwrap_or_default()
+        })
+        .any(|declared| {
+            include_namespaces.iter().any(|allowed| {
+                declared
+                    .strip_prefix(allowed.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+        })
+}
+
+/// Files under `source_location`, or only those of `file_list` when there is one. With
+/// `respect_gitignore` the `.gitignore` files of the project, and the repository's
+/// `info/exclude`, are honored the same way `git` would, whether or not the location is
+/// checked out as a repository.
+fn source_files(
+    source_location: &Path,
+    respect_gitignore: bool,
+    file_list: Option<&[PathBuf]>,
+) -> Box<dyn Iterator<Item = Result<PathBuf, Error>>> {
+    if let Some(file_list) = file_list {
+        let files: Vec<Result<PathBuf, Error>> = file_list.iter().cloned().map(Ok).collect();
+        return Box::new(files.into_iter());
+    }
+    if !respect_gitignore {
+        return Box::new(
+            WalkDir::new(source_location)
+                .into_iter()
+                .filter(|entry| !entry.as_ref().is_ok_and(|e| e.file_type().is_dir()))
+                .map(|entry| entry.map(|e| e.into_path()).map_err(Error::new)),
+        );
+    }
+    let walk = WalkBuilder::new(source_location)
+        .standard_filters(false)
+        .git_ignore(true)
+        .git_exclude(true)
+        .parents(true)
+        .require_git(false)
+        .build();
+    Box::new(
+        walk.filter(|entry| {
+            !entry
+                .as_ref()
+                .is_ok_and(|e| e.file_type().is_none_or(|t| t.is_dir()))
+        })
+        .map(|entry| entry.map(|e| e.into_path()).map_err(Error::new)),
+    )
+}
+
+/// Which of the files under the source location `init_stack_graph` indexes.
+#[derive(Clone, Debug, Default)]
+pub struct IndexOptions {
+    /// Index the output of source generators under `obj/**/generated/`, the rest of `obj/` is
+    /// always skipped.
+    pub include_generated: bool,
+    /// Skip the files the project's `.gitignore` files exclude, such as vendored trees or test
+    /// data that happen to contain `.cs` files.
+    pub respect_gitignore: bool,
+    /// Only the source files declaring a namespace under one of these prefixes are indexed, for
+    /// analyzing one part of a large solution. Every file is indexed when empty.
+    pub include_namespaces: Vec<String>,
+    /// Only these files are indexed instead of every file under the location, for CI jobs that
+    /// index the files a change touched.
+    pub file_list: Option<Vec<PathBuf>>,
+}
+
+impl IndexOptions {
+    /// The options as recorded with the database. A database whose files were picked
+    /// differently does not have the files these pick. A file list is recorded by its hash, it
+    /// can name thousands of files.
+    pub fn filters(&self) -> String {
+        let mut include_namespaces = self.include_namespaces.clone();
+        include_namespaces.sort();
+        let file_list = self.file_list.as_ref().map_or(String::new(), |file_list| {
+            let mut files: Vec<String> = file_list
+                .iter()
+                .map(|file| file.to_string_lossy().to_string())
+                .collect();
+            files.sort();
+            sha1(&files.join("\n"))
+        });
+        format!(
+            "include_generated={} respect_gitignore={} include_namespaces={} file_list={}",
+            self.include_generated,
+            self.respect_gitignore,
+            include_namespaces.join(","),
+            file_list
+        )
+    }
+}
+
 pub fn init_stack_graph(
     source_location: &Path,
     db_path: &Path,
     source_type: &SourceType,
     language_config: &LanguageConfiguration,
+    options: &IndexOptions,
+    cancellation_flag: &dyn CancellationFlag,
+    progress: Option<mpsc::Sender<IndexProgress>>,
 ) -> Result<InitializedGraph, Error> {
-    let mut db: SQLiteWriter = SQLiteWriter::open(db_path)?;
+    let mut db: SQLiteWriter = open_writer(db_path)?;
+    stamp_index_format(db_path, &options.filters())?;
 
     let mut files_loaded = 0;
+    let mut files_visited = 0;
+    // No one listening is not a reason to stop indexing.
+    let report = |path: &Path, files_visited: usize, outcome: IndexOutcome| {
+        if let Some(progress) = progress.as_ref() {
+            let _ = progress.send(IndexProgress {
+                path: path.to_path_buf(),
+                files_visited,
+                outcome,
+            });
+        }
+    };
+    let mut diagnostics: Vec<FileDiagnostic> = vec![];
 
     let mut stack_graph = StackGraph::new();
     let _ = stack_graph.add_from_graph(&language_config.builtins);
-    for path in WalkDir::new(source_location).into_iter() {
+    for path in source_files(
+        source_location,
+        options.respect_gitignore,
+        options.file_list.as_deref(),
+    ) {
+        cancellation_flag.check("init_stack_graph")?;
         trace!(
             "stack_graph files: {}, nodes: {}, symbols: {}",
             stack_graph.iter_files().count(),
             stack_graph.iter_nodes().count(),
             stack_graph.iter_symbols().count()
         );
-        let entry = match path {
-            Ok(entry) => {
-                if entry.file_type().is_dir() {
-                    continue;
-                }
-                entry
-            }
-            Err(err) => return Err(Error::new(err)),
-        };
-        let entry_path = entry.to_owned().into_path();
+        let entry_path = path?;
+        if !is_indexed_source(source_location, &entry_path, options.include_generated) {
+            trace!("skipped build output: {:?}", entry_path);
+            continue;
+        }
+        files_visited += 1;
+        if !declares_included_namespace(&entry_path, &options.include_namespaces) {
+            trace!(
+                "skipped file outside the included namespaces: {:?}",
+                entry_path
+            );
+            report(&entry_path, files_visited, IndexOutcome::Skipped);
+            continue;
+        }
         match load_graph_for_file(
             entry_path.clone(),
+            source_location,
             &mut stack_graph,
             language_config,
             source_type,
+            cancellation_flag,
         ) {
             Ok(res) => match res {
                 Some((f, tag)) => {
@@ -282,26 +543,39 @@ pub fn init_stack_graph(
                     let mut partials = PartialPaths::new();
                     let paths: Vec<PartialPath> = Vec::new();
 
-                    match db.store_result_for_file(&stack_graph, f, &tag, &mut partials, &paths) {
+                    match with_busy_retry(|| {
+                        db.store_result_for_file(&stack_graph, f, &tag, &mut partials, &paths)
+                    }) {
                         Ok(_) => (),
                         Err(err) => {
                             error!("error: {}", err);
                             return Err(anyhow!(err));
                         }
                     }
-                    debug!("loaded file handle: {:?} - file: {:?}", f, entry_path)
+                    debug!("loaded file handle: {:?} - file: {:?}", f, entry_path);
+                    report(&entry_path, files_visited, IndexOutcome::Loaded);
+                }
+                None => {
+                    trace!("skipped file: {:?}", entry_path);
+                    report(&entry_path, files_visited, IndexOutcome::Skipped);
+                }
+            },
+            Err(e) => match e.downcast::<FileDiagnostic>() {
+                Ok(diagnostic) => {
+                    report(&entry_path, files_visited, IndexOutcome::Failed);
+                    diagnostics.push(diagnostic);
+                }
+                Err(e) => {
+                    return Err(anyhow!("unable to load file: {:?} - {}", entry_path, e));
                 }
-                None => trace!("skipped file: {:?}", entry_path),
             },
-            Err(e) => {
-                return Err(anyhow!("unable to load file: {:?} - {}", entry_path, e));
-            }
         }
     }
 
     Ok(InitializedGraph {
         files_loaded,
         stack_graph,
+        diagnostics,
     })
 }
 
@@ -310,3 +584,322 @@ fn sha1(source: &str) -> String {
     hasher.update(source);
     base64::prelude::BASE64_STANDARD_NO_PAD.encode(hasher.finalize())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tree_sitter_stack_graphs::{CancellationError, NoCancellation};
+
+    use super::*;
+    use crate::test_support::{language_config, TestDir};
+
+    /// Trips after the given number of checks at the top of the walk loop.
+    struct CancelAfterFiles {
+        remaining: AtomicUsize,
+    }
+
+    impl CancellationFlag for CancelAfterFiles {
+        fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+            if at != "init_stack_graph" {
+                return Ok(());
+            }
+            if self.remaining.load(Ordering::SeqCst) == 0 {
+                return Err(CancellationError(at));
+            }
+            self.remaining.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn init_stack_graph_stops_when_cancelled_mid_walk() {
+        let dir = TestDir::new("cancel-walk");
+        for i in 0..5 {
+            fs::write(
+                dir.join(format!("File{}.cs", i)),
+                format!("namespace Demo {{ class C{} {{ }} }}", i),
+            )
+            .unwrap();
+        }
+        let db_path = dir.join("test.db");
+        let lc = language_config();
+
+        // The walk yields the directory itself first, then the files.
+        let flag = CancelAfterFiles {
+            remaining: AtomicUsize::new(3),
+        };
+        let res = init_stack_graph(
+            &dir,
+            &db_path,
+            &lc.source_type_node_info,
+            &lc.language_config,
+            &IndexOptions::default(),
+            &flag,
+            None,
+        );
+        let err = res.err().expect("walk should have been cancelled");
+        assert!(err.to_string().contains("Cancelled"), "{}", err);
+    }
+
+    #[test]
+    fn init_stack_graph_reports_files_that_do_not_parse() {
+        let dir = TestDir::new("parse-errors");
+        dir.write("Good.cs", "namespace Demo { class Good { } }");
+        dir.write(
+            "Broken.cs",
+            "namespace Demo {\n  class Broken { void M( } \n",
+        );
+
+        let initialized = dir.index(&IndexOptions::default());
+        assert_eq!(initialized.files_loaded, 1);
+        let paths: Vec<&Path> = initialized
+            .diagnostics
+            .iter()
+            .map(|d| d.path.as_path())
+            .collect();
+        assert_eq!(paths, vec![dir.join("Broken.cs").as_path()]);
+        assert!(
+            initialized.diagnostics[0].error.contains("syntax error"),
+            "{}",
+            initialized.diagnostics[0]
+        );
+    }
+
+    #[test]
+    fn init_stack_graph_sends_progress_for_every_file() {
+        let dir = TestDir::new("index-progress");
+        dir.write("A.cs", "namespace Demo { class A { } }");
+        dir.write("B.cs", "namespace Demo { class B { } }");
+        dir.write("Broken.cs", "namespace Demo { class Broken { void M( }");
+        dir.write("notes.txt", "not source");
+        let lc = language_config();
+
+        // The database is kept out of the walked directory so it is not reported.
+        let db_dir = TestDir::new("index-progress-db");
+        let db_path = db_dir.join("test.db");
+        let (sender, receiver) = mpsc::channel();
+        let initialized = init_stack_graph(
+            &dir,
+            &db_path,
+            &lc.source_type_node_info,
+            &lc.language_config,
+            &IndexOptions::default(),
+            &NoCancellation,
+            Some(sender),
+        )
+        .unwrap();
+        let mut events: Vec<IndexProgress> = receiver.iter().collect();
+        assert_eq!(
+            events.iter().map(|e| e.files_visited).collect::<Vec<_>>(),
+            (1..=events.len()).collect::<Vec<_>>()
+        );
+        events.sort_by(|a, b| a.path.cmp(&b.path));
+        let outcomes: Vec<(PathBuf, IndexOutcome)> =
+            events.into_iter().map(|e| (e.path, e.outcome)).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (dir.join("A.cs"), IndexOutcome::Loaded),
+                (dir.join("B.cs"), IndexOutcome::Loaded),
+                (dir.join("Broken.cs"), IndexOutcome::Failed),
+                (dir.join("notes.txt"), IndexOutcome::Skipped),
+            ]
+        );
+        assert_eq!(initialized.files_loaded, 2);
+    }
+
+    #[test]
+    fn init_stack_graph_includes_generated_sources_on_request() {
+        let dir = TestDir::new("generated-walk");
+        let generated = dir.join("obj/Debug/net8.0/generated/Demo.Generators");
+        fs::create_dir_all(&generated).unwrap();
+        dir.write("Program.cs", "namespace Demo { class Program { } }");
+        dir.write(
+            "obj/Debug/net8.0/Demo.AssemblyInfo.cs",
+            "namespace Demo { class AssemblyInfo { } }",
+        );
+        fs::write(
+            generated.join("Settings.g.cs"),
+            "namespace Demo { partial class Settings { } }",
+        )
+        .unwrap();
+
+        let load = |include_generated| {
+            let _ = fs::remove_file(dir.join("test.db"));
+            let graph = dir.index(&IndexOptions {
+                include_generated,
+                ..Default::default()
+            });
+            let mut files: Vec<String> = graph
+                .stack_graph
+                .iter_files()
+                .map(|f| graph.stack_graph[f].name().to_string())
+                .filter(|name| name.ends_with(".cs"))
+                .collect();
+            files.sort();
+            files
+        };
+
+        assert_eq!(load(false), vec![dir.join("Program.cs").to_string_lossy()]);
+        assert_eq!(
+            load(true),
+            vec![
+                dir.join("Program.cs").to_string_lossy(),
+                generated.join("Settings.g.cs").to_string_lossy(),
+            ]
+        );
+    }
+
+    #[test]
+    fn init_stack_graph_skips_gitignored_files_when_asked() {
+        let dir = TestDir::new("gitignore-walk");
+        fs::create_dir_all(dir.join("vendor/samples")).unwrap();
+        dir.write(".gitignore", "vendor/\n");
+        dir.write("Program.cs", "namespace Demo { class Program { } }");
+        dir.write(
+            "vendor/samples/Sample.cs",
+            "namespace Vendor { class Sample { } }",
+        );
+
+        let load = |respect_gitignore| {
+            let _ = fs::remove_file(dir.join("test.db"));
+            let graph = dir.index(&IndexOptions {
+                respect_gitignore,
+                ..Default::default()
+            });
+            let mut files: Vec<String> = graph
+                .stack_graph
+                .iter_files()
+                .map(|f| graph.stack_graph[f].name().to_string())
+                .filter(|name| name.ends_with(".cs"))
+                .collect();
+            files.sort();
+            files
+        };
+
+        assert_eq!(
+            load(false),
+            vec![
+                dir.join("Program.cs").to_string_lossy(),
+                dir.join("vendor/samples/Sample.cs").to_string_lossy(),
+            ]
+        );
+        assert_eq!(load(true), vec![dir.join("Program.cs").to_string_lossy()]);
+    }
+
+    #[test]
+    fn init_stack_graph_only_indexes_the_included_namespaces() {
+        let dir = TestDir::new("namespace-walk");
+        for (file, source) in [
+            ("Billing.cs", "namespace Shop.Billing { class Invoice { } }"),
+            ("Scoped.cs", "namespace Shop.Billing.Tax;\nclass Rate { }"),
+            (
+                "Shipping.cs",
+                "namespace Shop.Shipping { class Parcel { } }",
+            ),
+            (
+                "Lookalike.cs",
+                "namespace Shop.BillingExtras { class Extra { } }",
+            ),
+            ("Global.cs", "// namespace Shop.Billing\nclass Loose { }"),
+        ] {
+            dir.write(file, source);
+        }
+
+        let graph = dir.index(&IndexOptions {
+            include_namespaces: vec!["Shop.Billing".to_string()],
+            ..Default::default()
+        });
+        let mut files: Vec<String> = graph
+            .stack_graph
+            .iter_files()
+            .map(|f| graph.stack_graph[f].name().to_string())
+            .filter(|name| name.ends_with(".cs"))
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                dir.join("Billing.cs").to_string_lossy(),
+                dir.join("Scoped.cs").to_string_lossy(),
+            ]
+        );
+        assert_eq!(graph.files_loaded, 2);
+    }
+
+    #[test]
+    fn init_stack_graph_only_indexes_the_listed_files() {
+        let dir = TestDir::new("file-list");
+        fs::create_dir_all(dir.join("Orders")).unwrap();
+        for (file, source) in [
+            ("Orders/Order.cs", "namespace Shop { class Order { } }"),
+            ("Orders/Line.cs", "namespace Shop { class Line { } }"),
+            ("Customer.cs", "namespace Shop { class Customer { } }"),
+        ] {
+            dir.write(file, source);
+        }
+
+        let file_list = vec![dir.join("Customer.cs"), dir.join("Orders/Order.cs")];
+        let graph = dir.index(&IndexOptions {
+            file_list: Some(file_list),
+            ..Default::default()
+        });
+        let mut files: Vec<String> = graph
+            .stack_graph
+            .iter_files()
+            .map(|f| graph.stack_graph[f].name().to_string())
+            .filter(|name| name.ends_with(".cs"))
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                dir.join("Customer.cs").to_string_lossy(),
+                dir.join("Orders/Order.cs").to_string_lossy(),
+            ]
+        );
+        assert_eq!(graph.files_loaded, 2);
+    }
+
+    #[test]
+    fn add_dir_to_graph_skips_files_already_in_graph() {
+        let dir = TestDir::new("add-twice");
+        for i in 0..3 {
+            fs::write(
+                dir.join(format!("File{}.cs", i)),
+                format!("namespace Demo {{ class C{} {{ void M() {{ }} }} }}", i),
+            )
+            .unwrap();
+        }
+        let lc = language_config();
+        let mut graph = StackGraph::new();
+        let _ = SourceType::load_symbols_into_graph(&mut graph);
+
+        let first = add_dir_to_graph(
+            &dir,
+            &lc.dependnecy_type_node_info,
+            &lc.language_config,
+            graph,
+            None,
+            &NoCancellation,
+        )
+        .unwrap();
+        assert_eq!(first.files_loaded, 3);
+        let nodes = first.stack_graph.iter_nodes().count();
+
+        let second = add_dir_to_graph(
+            &dir,
+            &lc.dependnecy_type_node_info,
+            &lc.language_config,
+            first.stack_graph,
+            None,
+            &NoCancellation,
+        )
+        .unwrap();
+        assert_eq!(second.files_loaded, 0);
+        assert_eq!(second.stack_graph.iter_nodes().count(), nodes);
+        assert_eq!(second.stack_graph.iter_files().count(), 3);
+    }
+}
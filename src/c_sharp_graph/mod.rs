@@ -1,3 +1,4 @@
+pub mod database;
 pub mod find_node;
 pub mod language_config;
 pub mod loader;
@@ -1,26 +1,318 @@
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    cell::{OnceCell, RefCell},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
     vec,
 };
 
-use anyhow::{Error, Ok};
-use regex::Regex;
+use anyhow::{anyhow, Error, Ok};
+use regex::{Regex, RegexBuilder};
 use serde_json::Value;
 use stack_graphs::{
     arena::Handle,
-    graph::{Edge, File, Node, StackGraph},
+    graph::{File, Node, SourceInfo, StackGraph, Symbol},
+    CancelAfterDuration, CancellationError, CancellationFlag, NoCancellation,
 };
-use tracing::{debug, error, trace};
+use tracing::{debug, trace};
 use url::Url;
 
 use crate::c_sharp_graph::{
-    loader::SourceType,
+    language_config::SourceNodeLanguageConfiguration,
+    loader::{load_graph_for_file, SourceType},
     results::{Location, Position, ResultNode},
 };
 
 pub struct Querier<'a> {
-    db: &'a mut StackGraph,
+    db: &'a StackGraph,
     source_type: &'a SourceType,
+    index: &'a mut NamespaceIndex,
+    options: QueryOptions,
+    // alias of the file being searched to the short name of the type it stands for.
+    aliases: HashMap<String, String>,
+    // lines of the files results are in, read from disk as the database does not keep the
+    // source.
+    lines: RefCell<HashMap<Handle<File>, Vec<String>>>,
+    // symbol and location of the nodes of files whose spans the database is missing, by
+    // local id, from building the graph of the file on disk again.
+    rebuilt_locations: RefCell<HashMap<Handle<File>, HashMap<u32, RebuiltLocation>>>,
+    // only built when a file's graph has to be built again.
+    language_config: OnceCell<Option<Arc<SourceNodeLanguageConfiguration>>>,
+    // the method searched for is a constructor, named after its class, so `new` expressions
+    // are matched as calls of it.
+    constructor: bool,
+}
+
+/// A literal a method must be called with, a query given one reports the literal arguments of
+/// the calls to the method rather than the references to the pattern.
+#[derive(Debug, Clone)]
+pub struct ArgumentValue {
+    /// Fully qualified name of the called method.
+    pub method: String,
+    /// Zero based position of the argument, as written in the call.
+    pub position: usize,
+    /// Matched against the literal, strings and characters without their quotes.
+    pub value: Regex,
+}
+
+/// What a matched name has to be declared as, from the `location` of a condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    /// A class, interface, enum or record.
+    Type,
+    Method,
+    /// A field or property.
+    Field,
+}
+
+impl FromStr for SymbolKind {
+    type Err = Error;
+
+    fn from_str(location: &str) -> Result<SymbolKind, Error> {
+        match location.to_ascii_lowercase().as_str() {
+            "type" => Ok(SymbolKind::Type),
+            "method" => Ok(SymbolKind::Method),
+            "field" => Ok(SymbolKind::Field),
+            _ => Err(anyhow!(
+                "unknown location `{}`, expected `type`, `method` or `field`",
+                location
+            )),
+        }
+    }
+}
+
+/// Where a match has to be written, from a `location` naming the syntax of the node rather
+/// than a [`SymbolKind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxType {
+    /// A `using` directive.
+    Import,
+    NamespaceDeclaration,
+    /// The declaration of a class, enum or record.
+    ClassDef,
+    InterfaceDef,
+    /// The declaration of a method.
+    MethodName,
+    Constructor,
+    /// The declaration of a field.
+    FieldName,
+    /// The declaration of a property.
+    Property,
+    /// A member used through its receiver, `config.GetSection`.
+    MemberAccess,
+    IndexerAccess,
+    /// A member set in an object initializer.
+    InitializerMember,
+    Attribute,
+    /// A type in a `where` constraint of a type parameter.
+    Constraint,
+    Nameof,
+    Reflection,
+}
+
+impl SyntaxType {
+    const ALL: [SyntaxType; 15] = [
+        SyntaxType::Import,
+        SyntaxType::NamespaceDeclaration,
+        SyntaxType::ClassDef,
+        SyntaxType::InterfaceDef,
+        SyntaxType::MethodName,
+        SyntaxType::Constructor,
+        SyntaxType::FieldName,
+        SyntaxType::Property,
+        SyntaxType::MemberAccess,
+        SyntaxType::IndexerAccess,
+        SyntaxType::InitializerMember,
+        SyntaxType::Attribute,
+        SyntaxType::Constraint,
+        SyntaxType::Nameof,
+        SyntaxType::Reflection,
+    ];
+
+    /// The `syntax_type` the TSG gives the node.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyntaxType::Import => "import",
+            SyntaxType::NamespaceDeclaration => "namespace-declaration",
+            SyntaxType::ClassDef => "class-def",
+            SyntaxType::InterfaceDef => "interface-def",
+            SyntaxType::MethodName => "method_name",
+            SyntaxType::Constructor => "constructor",
+            SyntaxType::FieldName => "field_name",
+            SyntaxType::Property => "property",
+            SyntaxType::MemberAccess => "member-access",
+            SyntaxType::IndexerAccess => "indexer-access",
+            SyntaxType::InitializerMember => "initializer-member",
+            SyntaxType::Attribute => "attribute",
+            SyntaxType::Constraint => "constraint",
+            SyntaxType::Nameof => "nameof",
+            SyntaxType::Reflection => "reflection",
+        }
+    }
+}
+
+impl FromStr for SyntaxType {
+    type Err = Error;
+
+    /// Takes the names with either `_` or `-` between their words, `class_def`.
+    fn from_str(location: &str) -> Result<SyntaxType, Error> {
+        let name = location.to_ascii_lowercase().replace('-', "_");
+        SyntaxType::ALL
+            .into_iter()
+            .find(|syntax_type| syntax_type.as_str().replace('-', "_") == name)
+            .ok_or_else(|| anyhow!("unknown syntax type `{}`", location))
+    }
+}
+
+/// What the `location` of a condition restricts the matches to, either what the name is
+/// declared as or where it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchLocation {
+    Kind(SymbolKind),
+    Syntax(SyntaxType),
+}
+
+impl FromStr for MatchLocation {
+    type Err = Error;
+
+    fn from_str(location: &str) -> Result<MatchLocation, Error> {
+        location
+            .parse()
+            .map(MatchLocation::Kind)
+            .or_else(|_| location.parse().map(MatchLocation::Syntax))
+            .map_err(|_| {
+                anyhow!(
+                    "unknown location `{}`, expected `type`, `method`, `field` or a syntax type, {}",
+                    location,
+                    SyntaxType::ALL
+                        .map(|syntax_type| format!("`{}`", syntax_type.as_str().replace('-', "_")))
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// Extra work a query can be asked to do for each result.
+#[derive(Debug, Default, Clone)]
+pub struct QueryOptions {
+    /// Add the types the enclosing class derives from as the `base_types` variable.
+    pub include_base_types: bool,
+    /// The pattern names a namespace, match anything whose fully qualified name is under it
+    /// rather than the short names of its members.
+    pub subtree: bool,
+    /// Stop searching once this much time has passed and return what was found so far, each
+    /// result gets a `truncated` variable.
+    pub timeout: Option<Duration>,
+    /// Add the fully qualified name of the method each result is in as `enclosing_method`.
+    pub include_enclosing_method: bool,
+    /// When only the source is searched, also report where the matched types and members are
+    /// defined in the dependencies.
+    pub include_dependency_definitions: bool,
+    pub argument_value: Option<ArgumentValue>,
+    /// Only search the files indexed as dependencies, rather than every file when given the
+    /// dependency source type.
+    pub dependencies_only: bool,
+    /// Also match the names written in comments, such as commented out code.
+    pub include_comments: bool,
+    /// Also match the names written in string literals, such as a provider in a connection
+    /// string.
+    pub include_string_literals: bool,
+    /// Report the types declared under the namespace the pattern names, with the kind of
+    /// declaration, rather than the references to them.
+    pub declarations: bool,
+    /// Only match names declared as this kind, a method query does not match a class with
+    /// the same name.
+    pub location: Option<SymbolKind>,
+    /// Only match the names written as this syntax, such as in a `using` directive.
+    pub syntax_type: Option<SyntaxType>,
+    /// Compare the namespace segments of the pattern ignoring case, for code whose namespaces
+    /// are not cased consistently. Type and member names are still matched exactly.
+    pub namespace_case_insensitive: bool,
+    /// Only match the calls of a method whose arguments fit these parameter types, picking
+    /// one overload. An argument whose type can not be told from the call matches any type,
+    /// leaving only the number of arguments to compare. Methods that are not called are not
+    /// matched.
+    pub parameter_types: Option<Vec<String>>,
+    /// Search for this symbol, given field by field, instead of the dotted query.
+    pub symbol: Option<SymbolPattern>,
+    /// Only search the files whose path matches one of these, every file when not given.
+    pub file_paths: Option<FilePaths>,
+    /// Report the methods declared to return a type this pattern names, such as
+    /// `System.Data.DataSet`, rather than the references to the query. The query is what the
+    /// files that can name the type use.
+    pub returns_type: Option<String>,
+    /// Only match the attributes this pattern names, such as `System.Web.Services.WebMethod`,
+    /// with or without the `Attribute` suffix of their class.
+    pub attribute: Option<String>,
+}
+
+/// The paths a search is limited to, a file is searched when its path ends with one of them,
+/// whole segment by whole segment so `Api.cs` is not `MyApi.cs`. A `*` stands for any
+/// characters within one segment of the path, `Controllers/*.cs`.
+#[derive(Debug, Clone)]
+pub struct FilePaths(Vec<Regex>);
+
+impl FilePaths {
+    pub fn new(filters: &[String]) -> Result<FilePaths, Error> {
+        let regexes = filters
+            .iter()
+            .map(|filter| {
+                let pattern = normalize_path(filter)
+                    .split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<String>>()
+                    .join("[^/]*");
+                // Windows paths are case-insensitive, everywhere else case is significant.
+                RegexBuilder::new(&format!("(^|/){}$", pattern))
+                    .case_insensitive(cfg!(windows))
+                    .build()
+                    .map_err(|e| anyhow!("invalid file path `{}`: {}", filter, e))
+            })
+            .collect::<Result<Vec<Regex>, Error>>()?;
+        Ok(FilePaths(regexes))
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let path = normalize_path(path);
+        self.0.iter().any(|regex| regex.is_match(&path))
+    }
+}
+
+/// A symbol to search for given field by field rather than as a dotted pattern, so a class or
+/// method name is never split on a `.` that it contains. Every field can use `*` wildcards.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolPattern {
+    /// The namespace, its dotted segments are matched one by one like a pattern's.
+    pub namespace: String,
+    /// The type in the namespace, every type when there is none.
+    pub class: Option<String>,
+    /// The member of the type, every member when there is none.
+    pub method: Option<String>,
+}
+
+impl SymbolPattern {
+    /// The search parts naming the type, or the namespace when no type is given.
+    fn type_parts(&self) -> Vec<String> {
+        self.namespace
+            .split('.')
+            .map(str::to_string)
+            .chain(self.class.clone())
+            .collect()
+    }
+}
+
+/// The declarations a node is nested in, carried down the traversal of a file.
+#[derive(Debug, Default, Clone)]
+struct Enclosing {
+    class: Option<Handle<Node>>,
+    // The namespaces and classes the node is in, joined by ".".
+    scope: String,
+    method: Option<String>,
+    method_node: Option<Handle<Node>>,
+    // The argument list of the call the node is the function of.
+    arguments: Option<Handle<Node>>,
 }
 
 pub trait Query {
@@ -29,11 +321,17 @@ pub trait Query {
 
 impl Query for Querier<'_> {
     fn query(&mut self, query: String) -> anyhow::Result<Vec<ResultNode>, Error> {
-        let search: Search = self.get_search(query)?;
+        let (search, method, constructor) = searches(query, &mut self.options)?;
+        self.constructor = constructor;
 
         debug!("search: {:?}", search);
 
         let mut results: Vec<ResultNode> = vec![];
+        let cancellation_flag: Box<dyn CancellationFlag> = match self.options.timeout {
+            Some(timeout) => Box::new(CancelAfterDuration::new(timeout)),
+            None => Box::new(NoCancellation),
+        };
+        let mut truncated = false;
 
         // If we are search for all things from a ref
         // ex: System.Configuration.ConfigurationManager.* or System.Configuration.*
@@ -48,117 +346,226 @@ impl Query for Querier<'_> {
             // declaration. then we need to capture all the nodes that are definitions attached to
             // this (for instance namespace System.Configuration; Class ConfigurationManager; method
             // AppSettings)
-            let mut definition_root_nodes: Vec<Handle<Node>> = vec![];
-            let mut referenced_files: HashSet<Handle<File>> = HashSet::new();
-            let mut file_to_compunit_handle: HashMap<Handle<File>, Handle<Node>> = HashMap::new();
-
-            for node_handle in self.db.iter_nodes() {
-                let node: &Node = &self.db[node_handle];
-                let file_handle = match node.file() {
-                    Some(h) => h,
-                    None => {
-                        continue;
-                    }
-                };
-                let symbol_option = node.symbol();
-                if symbol_option.is_none() {
-                    // If the node doesn't have a symbol to look at, then we should continue and it
-                    // only used to tie together other nodes.
-                    continue;
-                }
-                let symbol = &self.db[node.symbol().unwrap()];
-                let source_info = self.db.source_info(node_handle);
-                if source_info.is_none() {
-                    continue;
-                }
-                match source_info.unwrap().syntax_type.into_option() {
-                    None => continue,
-                    Some(handle) => {
-                        let syntax_type = &self.db[handle];
-                        match syntax_type {
-                            "comp-unit" => {
-                                file_to_compunit_handle.insert(file_handle, node_handle);
-                            }
-                            "import" => {
-                                if search.partial_namespace(symbol) {
-                                    referenced_files.insert(file_handle);
-                                }
-                            }
-                            "namespace-declaration" => {
-                                if search.match_namespace(symbol) {
-                                    definition_root_nodes.push(node_handle);
-                                    referenced_files.insert(file_handle);
-                                }
-                            }
-                            &_ => continue,
-                        }
-                    }
-                }
-            }
+            let starting_nodes = self.index.starting_nodes(self.db, &search);
             // Now that we have the all the nodes we need to build the reference symbols to match the *
-            let namespace_symbols = NamespaceSymbols::new(self.db, definition_root_nodes)?;
+            let namespace_symbols =
+                NamespaceSymbols::new(self.db, starting_nodes.definition_root_nodes.clone())?;
 
-            for file in referenced_files.iter() {
-                let comp_unit_node_handle = match file_to_compunit_handle.get(file) {
+            // Full analysis searches every file, the others only the files of their source
+            // type.
+            let restrict_to = match self.source_type {
+                SourceType::Source { .. } => Some(self.source_type_symbol(self.source_type)),
+                SourceType::Dependency { .. } if self.options.dependencies_only => {
+                    Some(self.source_type_symbol(self.source_type))
+                }
+                SourceType::Dependency { .. } => None,
+            };
+            if self.options.declarations {
+                return Ok(self.declarations(&namespace_symbols, &search, restrict_to));
+            }
+            let dependency = self.dependency_symbol();
+            let returns_type = self
+                .options
+                .returns_type
+                .clone()
+                .map(|pattern| {
+                    Search::create_search(pattern, self.options.namespace_case_insensitive)
+                })
+                .transpose()?;
+            for file in starting_nodes.referenced_files.iter() {
+                let comp_unit_node_handle = match self.index.comp_units.get(file).copied() {
                     Some(x) => x,
                     None => {
                         debug!("unable to find compulation unit for file");
                         break;
                     }
                 };
-                let (is_source, symbol_handle) = match self.source_type {
-                    SourceType::Source { symbol_handle } => (true, Some(symbol_handle)),
-                    _ => (false, None),
-                };
-
-                if is_source
-                    && !self.db.nodes_for_file(*file).any(|node_handle| {
-                        let node = &self.db[node_handle];
-
-                        let symobl_handle = symbol_handle.unwrap();
-                        if let Some(sh) = node.symbol() {
-                            if sh.as_usize() == symobl_handle.as_usize() {
-                                if self.source_type.get_string() != self.db[sh] {
-                                    error!("SOMETHING IS VERY WRONG!!!!");
-                                }
-                                let edges: Vec<Edge> =
-                                    self.db.outgoing_edges(node_handle).collect();
-                                for edge in edges {
-                                    if edge.sink == *comp_unit_node_handle {
-                                        return true;
-                                    }
-                                }
-                            }
-                        }
-                        false
-                    })
-                {
+                if !self.in_file_paths(*file) {
                     continue;
                 }
+                if let Some(source_type) = restrict_to {
+                    if !source_type.is_some_and(|s| self.has_source_type(*file, s)) {
+                        continue;
+                    }
+                }
                 let f = &self.db[*file];
                 let file_url = Url::from_file_path(f.name());
                 if file_url.is_err() {
                     break;
                 }
                 let file_uri = file_url.unwrap().as_str().to_string();
-                self.traverse_node_search(
-                    *comp_unit_node_handle,
-                    &namespace_symbols,
-                    &mut results,
-                    file_uri,
-                );
+                self.aliases = self.type_aliases(comp_unit_node_handle);
+                let found = results.len();
+                let searched = match (&returns_type, self.options.argument_value.clone()) {
+                    (Some(returns_type), _) => self.traverse_returns_type_search(
+                        comp_unit_node_handle,
+                        &namespace_symbols,
+                        returns_type,
+                        &mut results,
+                        &Enclosing::default(),
+                        cancellation_flag.as_ref(),
+                    ),
+                    (None, Some(argument_value)) => self.traverse_argument_search(
+                        comp_unit_node_handle,
+                        &namespace_symbols,
+                        &argument_value,
+                        &mut results,
+                        &file_uri,
+                        &Enclosing::default(),
+                        cancellation_flag.as_ref(),
+                    ),
+                    (None, None) => self.traverse_node_search(
+                        comp_unit_node_handle,
+                        &namespace_symbols,
+                        &mut results,
+                        file_uri,
+                        &Enclosing::default(),
+                        cancellation_flag.as_ref(),
+                    ),
+                };
+                if dependency.is_some_and(|d| self.has_source_type(*file, d)) {
+                    results[found..]
+                        .iter_mut()
+                        .for_each(flag_dependency_incident);
+                }
+                if let Err(e) = searched {
+                    debug!("query timed out, returning partial results: {}", e);
+                    truncated = true;
+                    break;
+                }
+            }
+            // When everything is searched the definitions in dependencies are already found.
+            if self.options.include_dependency_definitions
+                && matches!(self.source_type, SourceType::Source { .. })
+                && !truncated
+            {
+                results.extend(self.dependency_definitions(&namespace_symbols));
+            }
+        }
+        if truncated {
+            for result in results.iter_mut() {
+                result
+                    .variables
+                    .insert("truncated".to_string(), Value::from(true));
             }
         }
+        if self.options.subtree {
+            // Parent namespaces of the pattern are searched too, drop what they declare.
+            results.retain(|r| r.fqdn.as_deref().is_some_and(|f| search.declared_under(f)));
+        }
+        if let Some(method) = &method {
+            results.retain(|r| r.fqdn.as_deref().is_some_and(|f| method.names(f)));
+        }
+        if let Some(attribute) = self.options.attribute.clone() {
+            let attribute = self.get_search(attribute)?;
+            results.retain(|r| {
+                r.fqdn.as_deref().is_some_and(|f| {
+                    attribute.names(f)
+                        || f.strip_suffix("Attribute")
+                            .is_some_and(|f| attribute.names(f))
+                })
+            });
+        }
         Ok(results)
     }
 }
 
+/// The search for the namespaces a query starts from, along with the search for the one
+/// method it names and whether that method is a constructor. Naming a method narrows
+/// `options` down to methods.
+fn searches(
+    query: String,
+    options: &mut QueryOptions,
+) -> anyhow::Result<(Search, Option<Search>, bool), Error> {
+    match options.symbol.clone() {
+        Some(symbol) => symbol_searches(&symbol, options),
+        None => pattern_searches(query, options),
+    }
+}
+
+/// The search for a dotted pattern, along with the search for the one method it names when it
+/// has a signature.
+fn pattern_searches(
+    query: String,
+    options: &mut QueryOptions,
+) -> anyhow::Result<(Search, Option<Search>, bool), Error> {
+    let case_insensitive = options.namespace_case_insensitive;
+    let (query, signature) = split_signature(&query)?;
+    let Some(signature) = signature else {
+        let query = match options.subtree {
+            true => format!("{}.*", query.trim_end_matches(".*")),
+            false => query,
+        };
+        return Ok((Search::create_search(query, case_insensitive)?, None, false));
+    };
+    // A method with a signature is searched for in everything its type's namespace
+    // reaches, then narrowed down to the method.
+    let Some((declaring_type, method)) = query.rsplit_once('.') else {
+        return Err(anyhow!(
+            "method signatures need the type of the method, `Type.Method(...)`"
+        ));
+    };
+    let constructor = declaring_type.rsplit('.').next() == Some(method);
+    if let Signature::Parameters(parameter_types) = signature {
+        options.parameter_types = Some(parameter_types);
+    }
+    options.location.get_or_insert(SymbolKind::Method);
+    let search = Search::create_search(format!("{}.*", declaring_type), case_insensitive)?;
+    let method = Search::create_search(query, case_insensitive)?;
+    Ok((search, Some(method), constructor))
+}
+
+/// The searches for a symbol given field by field, the same as for the dotted pattern naming
+/// it but without splitting the class or method.
+fn symbol_searches(
+    symbol: &SymbolPattern,
+    options: &mut QueryOptions,
+) -> anyhow::Result<(Search, Option<Search>, bool), Error> {
+    let case_insensitive = options.namespace_case_insensitive;
+    let type_parts = symbol.type_parts();
+    let namespace_parts = symbol.namespace.split('.').count();
+    let search = Search::from_parts(
+        [type_parts.clone(), vec!["*".to_string()]].concat(),
+        namespace_parts,
+        case_insensitive,
+    )?;
+    let Some(method) = symbol.method.clone() else {
+        return Ok((search, None, false));
+    };
+    if symbol.class.is_none() {
+        return Err(anyhow!("a method needs the class it is declared in"));
+    }
+    options.location.get_or_insert(SymbolKind::Method);
+    let constructor = symbol.class.as_ref() == Some(&method);
+    let method = Search::from_parts(
+        [type_parts, vec![method]].concat(),
+        namespace_parts,
+        case_insensitive,
+    )?;
+    Ok((search, Some(method), constructor))
+}
+
 impl<'a> Querier<'a> {
-    pub fn get_query(db: &'a mut StackGraph, source_type: &'a SourceType) -> impl Query + use<'a> {
-        Querier { db, source_type }
+    pub fn get_query(
+        db: &'a StackGraph,
+        source_type: &'a SourceType,
+        index: &'a mut NamespaceIndex,
+        options: QueryOptions,
+    ) -> impl Query + use<'a> {
+        Querier {
+            db,
+            source_type,
+            index,
+            options,
+            aliases: HashMap::new(),
+            lines: RefCell::new(HashMap::new()),
+            rebuilt_locations: RefCell::new(HashMap::new()),
+            language_config: OnceCell::new(),
+            constructor: false,
+        }
     }
     fn get_search(&self, query: String) -> anyhow::Result<Search, Error> {
-        Search::create_search(query)
+        Search::create_search(query, self.options.namespace_case_insensitive)
     }
     fn traverse_node_search(
         &mut self,
@@ -166,8 +573,12 @@ impl<'a> Querier<'a> {
         namespace_symbols: &NamespaceSymbols,
         results: &mut Vec<ResultNode>,
         file_uri: String,
-    ) {
+        enclosing: &Enclosing,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), CancellationError> {
+        cancellation_flag.check("searching nodes")?;
         let mut traverse_nodes: Vec<Handle<Node>> = vec![];
+        let arguments = self.argument_list(node);
         for edge in self.db.outgoing_edges(node) {
             traverse_nodes.push(edge.sink);
             let child_node = &self.db[edge.sink];
@@ -175,7 +586,167 @@ impl<'a> Querier<'a> {
                 None => continue,
                 Some(symbol_handle) => {
                     let symbol = &self.db[symbol_handle];
-                    if namespace_symbols.symbol_in_namespace(symbol.to_string()) {
+                    // Members are matched on what their receiver evaluates to, so that the
+                    // members of a chain like GetConfig().GetSection("x").Value are found.
+                    // Base types are there to describe the class, only the interfaces it
+                    // implements are matched.
+                    let (matched, fqdn) = match self.syntax_type(edge.sink) {
+                        Some("member-access") => {
+                            match self.receiver_type(node, namespace_symbols) {
+                                Some(receiver) => (
+                                    true,
+                                    namespace_symbols
+                                        .fqdn(&receiver)
+                                        .map(|receiver| format!("{}.{}", receiver, symbol)),
+                                ),
+                                None => (false, None),
+                            }
+                        }
+                        Some("indexer-access") => {
+                            match self.indexed_type(node, namespace_symbols, enclosing) {
+                                Some(receiver) => (
+                                    true,
+                                    namespace_symbols
+                                        .fqdn(&receiver)
+                                        .map(|receiver| format!("{}.{}", receiver, symbol)),
+                                ),
+                                None => (false, None),
+                            }
+                        }
+                        Some("initializer-member") => {
+                            let initialized = self
+                                .debug_value(edge.sink, "initialized_type")
+                                .map(|type_name| short_type_name(&type_name).to_string())
+                                .filter(|class| namespace_symbols.type_in_namespace(class));
+                            match initialized {
+                                Some(class) => (
+                                    true,
+                                    namespace_symbols
+                                        .fqdn(&class)
+                                        .map(|class| format!("{}.{}", class, symbol)),
+                                ),
+                                None => (false, None),
+                            }
+                        }
+                        // Names written in the source rather than used, resolved to what they
+                        // refer to.
+                        Some("nameof") => {
+                            let fqdn = namespace_symbols.named_fqdn(symbol);
+                            (fqdn.is_some(), fqdn)
+                        }
+                        Some("reflection") => {
+                            let member = literal_value(symbol);
+                            let name = match self.debug_value(edge.sink, "reflected_type") {
+                                Some(type_name) => format!("{}.{}", type_name, member),
+                                None => member.to_string(),
+                            };
+                            let fqdn = namespace_symbols.named_fqdn(&name);
+                            (fqdn.is_some(), fqdn)
+                        }
+                        // Text rather than code, only matched when asked for.
+                        Some("comment") if self.options.include_comments => {
+                            let fqdn = namespace_symbols.text_fqdn(symbol);
+                            (fqdn.is_some(), fqdn)
+                        }
+                        Some("literal")
+                            if self.options.include_string_literals && is_string(symbol) =>
+                        {
+                            let fqdn = namespace_symbols.text_fqdn(literal_value(symbol));
+                            (fqdn.is_some(), fqdn)
+                        }
+                        Some("comment" | "literal") => (false, None),
+                        // A search for an interface finds what implements it.
+                        Some("base-type") => {
+                            let name = short_type_name(symbol);
+                            match namespace_symbols.interfaces.contains(name) {
+                                true => (true, namespace_symbols.fqdn(name)),
+                                false => (false, None),
+                            }
+                        }
+                        Some("constraint") => {
+                            let name = short_type_name(symbol);
+                            (
+                                namespace_symbols.type_in_namespace(name),
+                                namespace_symbols.fqdn(name),
+                            )
+                        }
+                        // The directives only lead to the namespaces searched, they are
+                        // matched when asked for.
+                        Some("import") if self.options.syntax_type == Some(SyntaxType::Import) => {
+                            let imported = without_type_arguments(symbol);
+                            (
+                                namespace_symbols.imported_by(imported),
+                                Some(imported.to_string()),
+                            )
+                        }
+                        Some("attribute") => match namespace_symbols.attribute_class(symbol) {
+                            Some(class) => (true, namespace_symbols.fqdn(class)),
+                            None => (false, None),
+                        },
+                        _ => {
+                            let symbol = self.aliased(symbol);
+                            let location = self.options.location;
+                            let used_as = self
+                                .debug_value(edge.sink, "symbol_kind")
+                                .and_then(|kind| kind.parse::<SymbolKind>().ok());
+                            if self.constructor
+                                && self.debug_value(edge.sink, "constructor_call").is_some()
+                            {
+                                let fqdn = namespace_symbols
+                                    .kind_fqdn(symbol, Some(SymbolKind::Type))
+                                    .map(|class| format!("{}.{}", class, symbol));
+                                (fqdn.is_some(), fqdn)
+                            } else if location
+                                .is_some_and(|kind| used_as.is_some_and(|u| u != kind))
+                            {
+                                (false, None)
+                            } else if let Some(declared) =
+                                self.declared_fqdn(edge.sink, symbol, enclosing)
+                            {
+                                // A declaration outside the namespace only shares the name.
+                                (
+                                    namespace_symbols.kinds.contains_key(&declared),
+                                    Some(declared),
+                                )
+                            } else {
+                                (
+                                    namespace_symbols.symbol_in_namespace(symbol, location),
+                                    namespace_symbols.kind_fqdn(symbol, location),
+                                )
+                            }
+                        }
+                    };
+                    let matched = matched
+                        && self.options.syntax_type.is_none_or(|syntax_type| {
+                            self.syntax_type(edge.sink) == Some(syntax_type.as_str())
+                        });
+                    let matched = matched
+                        && self.options.location.is_none_or(|kind| {
+                            fqdn.as_deref()
+                                .and_then(|fqdn| namespace_symbols.kinds.get(fqdn))
+                                == Some(&kind)
+                        });
+                    let matched = matched
+                        && match &self.options.parameter_types {
+                            Some(parameter_types)
+                                if fqdn
+                                    .as_deref()
+                                    .and_then(|fqdn| namespace_symbols.kinds.get(fqdn))
+                                    == Some(&SymbolKind::Method) =>
+                            {
+                                // A member is called through its receiver, the call is the
+                                // parent of the node.
+                                let call = match self.syntax_type(edge.sink) {
+                                    Some("member-access") => enclosing.arguments,
+                                    _ => arguments.filter(|arguments| *arguments != edge.sink),
+                                };
+                                call.is_some_and(|call| {
+                                    self.arguments_fit(call, parameter_types, enclosing)
+                                })
+                            }
+                            _ => true,
+                        };
+                    if matched {
                         let debug_node = self.db.node_debug_info(edge.sink).map_or(vec![], |d| {
                             d.iter()
                                 .map(|e| {
@@ -199,39 +770,87 @@ impl<'a> Querier<'a> {
                                         .collect()
                                 });
 
-                        let code_location: Location;
-                        let line_number: usize;
-                        match self.db.source_info(edge.sink) {
-                            None => {
-                                continue;
+                        let Some((line_number, code_location)) = self.code_location(edge.sink)
+                        else {
+                            continue;
+                        };
+                        let mut var: BTreeMap<String, Value> = BTreeMap::from([
+                            ("file".to_string(), Value::from(file_uri.clone())),
+                            (
+                                "character_utf16".to_string(),
+                                Value::from(code_location.start_position.character_utf16),
+                            ),
+                            (
+                                "access_kind".to_string(),
+                                Value::from(self.access_kind(edge.sink)),
+                            ),
+                        ]);
+                        if self.options.include_base_types {
+                            if let Some(class) = enclosing.class {
+                                var.insert(
+                                    "base_types".to_string(),
+                                    Value::from(self.base_types(class, namespace_symbols)),
+                                );
                             }
-                            Some(source_info) => {
-                                line_number = source_info.span.start.line;
-                                code_location = Location {
-                                    start_position: Position {
-                                        line: source_info.span.start.line,
-                                        character: source_info.span.start.column.utf8_offset,
-                                    },
-                                    end_position: Position {
-                                        line: source_info.span.end.line,
-                                        character: source_info.span.end.column.utf8_offset,
-                                    },
-                                };
-                                // source info is containing line is never saved or restored to the
-                                // database.
-                                //match source_info.containing_line.into_option() {
-                                //   None => (),
-                                //  Some(string_handle) => {
-                                //     line = Some(self.db[string_handle].to_string());
-                                //}
-                                //}
+                        }
+                        if self.options.include_enclosing_method {
+                            if let Some(method) = &enclosing.method {
+                                var.insert(
+                                    "enclosing_method".to_string(),
+                                    Value::from(method.clone()),
+                                );
+                            }
+                        }
+                        if let Some(context) = self.debug_value(edge.sink, "context") {
+                            var.insert("context".to_string(), Value::from(context));
+                        }
+                        if let Some(target) = self.attribute_target(edge.sink) {
+                            var.insert("attribute_target".to_string(), Value::from(target));
+                        }
+                        let (arguments, named_arguments) = self.attribute_arguments(edge.sink);
+                        if !arguments.is_empty() {
+                            var.insert("arguments".to_string(), Value::from(arguments));
+                        }
+                        if !named_arguments.is_empty() {
+                            var.insert(
+                                "named_arguments".to_string(),
+                                Value::Object(named_arguments),
+                            );
+                        }
+                        if let Some(type_parameter) = self.debug_value(edge.sink, "type_parameter")
+                        {
+                            var.insert("kind".to_string(), Value::from("constraint"));
+                            var.insert("type_parameter".to_string(), Value::from(type_parameter));
+                        }
+                        let accessor = self.debug_value(edge.sink, "accessor");
+                        if self.syntax_type(edge.sink) == Some("indexer-access") {
+                            var.insert("kind".to_string(), Value::from("indexer"));
+                            var.insert(
+                                "accessor".to_string(),
+                                Value::from(accessor.unwrap_or_else(|| "get".to_string())),
+                            );
+                        } else if let Some(accessor) = accessor {
+                            var.insert("accessor".to_string(), Value::from(accessor));
+                        }
+                        match self.syntax_type(edge.sink) {
+                            Some(kind @ ("nameof" | "reflection" | "comment")) => {
+                                var.insert("kind".to_string(), Value::from(kind));
+                            }
+                            Some("base-type") => {
+                                var.insert("kind".to_string(), Value::from("implementation"));
+                                var.insert(
+                                    "implemented_by".to_string(),
+                                    Value::from(enclosing.scope.clone()),
+                                );
+                            }
+                            Some("literal") => {
+                                var.insert("kind".to_string(), Value::from("string_literal"));
                             }
+                            _ => {}
+                        }
+                        if let Some(line) = self.source_line(edge.sink, line_number) {
+                            var.insert("line".to_string(), Value::from(line));
                         }
-                        let var: BTreeMap<String, Value> =
-                            BTreeMap::from([("file".to_string(), Value::from(file_uri.clone()))]);
-                        //if let Some(line) = line {
-                        //   var.insert("line".to_string(), Value::from(line.trim()));
-                        //}
                         trace!(
                             "found result for node: {:?} and edge: {:?}",
                             debug_node,
@@ -242,186 +861,3027 @@ impl<'a> Querier<'a> {
                             line_number,
                             code_location,
                             variables: var,
+                            fqdn,
+                            effort: None,
+                            is_dependency_incident: false,
                         });
                     }
                 }
             }
         }
         for n in traverse_nodes {
-            self.traverse_node_search(n, namespace_symbols, results, file_uri.clone());
+            let mut enclosing = self.enter(n, enclosing);
+            enclosing.arguments = arguments.filter(|arguments| *arguments != n);
+            self.traverse_node_search(
+                n,
+                namespace_symbols,
+                results,
+                file_uri.clone(),
+                &enclosing,
+                cancellation_flag,
+            )?;
         }
+        Result::Ok(())
     }
-}
-
-pub struct NamespaceSymbols {
-    classes: HashMap<String, Handle<Node>>,
-    class_fields: HashMap<String, Handle<Node>>,
-    class_methods: HashMap<String, Handle<Node>>,
-}
 
-impl NamespaceSymbols {
-    fn new(
-        db: &mut StackGraph,
-        nodes: Vec<Handle<Node>>,
-    ) -> anyhow::Result<NamespaceSymbols, Error> {
-        let mut classes: HashMap<String, Handle<Node>> = HashMap::new();
-        let mut class_fields: HashMap<String, Handle<Node>> = HashMap::new();
-        let mut class_methods: HashMap<String, Handle<Node>> = HashMap::new();
-
-        for node_handle in nodes {
-            //Get all the edges
-            Self::traverse_node(
-                db,
-                node_handle,
-                &mut classes,
-                &mut class_fields,
-                &mut class_methods,
-            )
+    /// Reports the calls to the method of `argument_value` whose argument at its position is a
+    /// literal that matches, located at the literal.
+    #[allow(clippy::too_many_arguments)]
+    fn traverse_argument_search(
+        &mut self,
+        node: Handle<Node>,
+        namespace_symbols: &NamespaceSymbols,
+        argument_value: &ArgumentValue,
+        results: &mut Vec<ResultNode>,
+        file_uri: &str,
+        enclosing: &Enclosing,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), CancellationError> {
+        cancellation_flag.check("searching arguments")?;
+        let children: Vec<Handle<Node>> = self.db.outgoing_edges(node).map(|e| e.sink).collect();
+        if let Some(arguments) = children
+            .iter()
+            .find(|child| self.syntax_type(**child) == Some("argument-list"))
+        {
+            let called = children
+                .iter()
+                .filter(|child| *child != arguments)
+                .find_map(|function| self.called_method(*function, namespace_symbols, enclosing));
+            if called.as_deref() == Some(argument_value.method.as_str()) {
+                if let Some(result) =
+                    self.argument_result(*arguments, argument_value, file_uri, enclosing)
+                {
+                    results.push(result);
+                }
+            }
         }
-
-        Ok(NamespaceSymbols {
-            classes,
-            class_fields,
-            class_methods,
-        })
+        for child in children {
+            let enclosing = self.enter(child, enclosing);
+            self.traverse_argument_search(
+                child,
+                namespace_symbols,
+                argument_value,
+                results,
+                file_uri,
+                &enclosing,
+                cancellation_flag,
+            )?;
+        }
+        Result::Ok(())
     }
 
-    fn traverse_node(
-        db: &mut StackGraph,
+    /// Reports the methods whose return type is one `returns_type` names, located at the
+    /// method declaration.
+    fn traverse_returns_type_search(
+        &mut self,
         node: Handle<Node>,
-        classes: &mut HashMap<String, Handle<Node>>,
-        _class_fields: &mut HashMap<String, Handle<Node>>,
-        class_methods: &mut HashMap<String, Handle<Node>>,
-    ) {
-        let mut child_edges: Vec<Handle<Node>> = vec![];
-        for edge in db.outgoing_edges(node) {
-            child_edges.push(edge.sink);
-            let child_node = &db[edge.sink];
-            let symbol = match child_node.symbol() {
-                None => continue,
-                Some(symbol) => &db[symbol],
-            };
-            match db.source_info(edge.sink) {
-                None => continue,
-                Some(source_info) => match source_info.syntax_type.into_option() {
-                    None => continue,
-                    Some(syntax_type) => match &db[syntax_type] {
-                        "method_name" => {
-                            class_methods.insert(symbol.to_string(), edge.sink);
-                        }
-                        "class-def" => {
-                            classes.insert(symbol.to_string(), edge.sink);
-                        }
-                        &_ => {}
-                    },
-                },
+        namespace_symbols: &NamespaceSymbols,
+        returns_type: &Search,
+        results: &mut Vec<ResultNode>,
+        enclosing: &Enclosing,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), CancellationError> {
+        cancellation_flag.check("searching return types")?;
+        let children: Vec<Handle<Node>> = self.db.outgoing_edges(node).map(|e| e.sink).collect();
+        for child in children {
+            let enclosing = self.enter(child, enclosing);
+            if self.syntax_type(child) == Some("method_name") {
+                if let Some(result) =
+                    self.returns_type_result(child, namespace_symbols, returns_type, &enclosing)
+                {
+                    results.push(result);
+                }
             }
+            self.traverse_returns_type_search(
+                child,
+                namespace_symbols,
+                returns_type,
+                results,
+                &enclosing,
+                cancellation_flag,
+            )?;
         }
-        for child_edge in child_edges {
-            Self::traverse_node(db, child_edge, classes, _class_fields, class_methods);
-        }
+        Result::Ok(())
     }
 
-    fn symbol_in_namespace(&self, symbol: String) -> bool {
-        let class_match = self.classes.get(&symbol);
-        let method_match = self.class_methods.get(&symbol);
-        let field_match = self.class_fields.get(&symbol);
-
-        class_match.is_some() || method_match.is_some() || field_match.is_some()
+    /// A result at the declaration of `method` when the type it returns, without its type
+    /// arguments or `?`, resolves to a type `returns_type` names.
+    fn returns_type_result(
+        &self,
+        method: Handle<Node>,
+        namespace_symbols: &NamespaceSymbols,
+        returns_type: &Search,
+        enclosing: &Enclosing,
+    ) -> Option<ResultNode> {
+        let written = self.debug_value(method, "return_type")?;
+        let written = without_type_arguments(&written).trim_end_matches('?');
+        let fqdn = namespace_symbols
+            .named_fqdn(self.aliased(written))
+            .filter(|fqdn| namespace_symbols.kinds.get(fqdn) == Some(&SymbolKind::Type))
+            .filter(|fqdn| returns_type.names(fqdn))?;
+        let name = enclosing.method.clone()?;
+        let mut result = self.definition_result(method, &name, "method")?;
+        result
+            .variables
+            .insert("method".to_string(), Value::from(name));
+        result
+            .variables
+            .insert("return_type".to_string(), Value::from(fqdn));
+        Some(result)
     }
-}
 
-#[derive(Debug)]
-struct SearchPart {
-    part: String,
-    regex: Option<Regex>,
-}
+    /// The fully qualified name of the method a call invokes, called on a type
+    /// (`Encoding.GetEncoding("x")`), on a value whose type resolves, or in the class itself.
+    fn called_method(
+        &self,
+        function: Handle<Node>,
+        namespace_symbols: &NamespaceSymbols,
+        enclosing: &Enclosing,
+    ) -> Option<String> {
+        let function_symbol = self.db[function].symbol().map(|s| &self.db[s]);
+        let Some(member) = self
+            .db
+            .outgoing_edges(function)
+            .find(|e| self.syntax_type(e.sink) == Some("member-access"))
+        else {
+            let method = function_symbol?;
+            if !namespace_symbols.class_methods.contains_key(method) {
+                return None;
+            }
+            return namespace_symbols.fqdn(method);
+        };
+        let method = &self.db[self.db[member.sink].symbol()?];
+        let receiver = function_symbol
+            .filter(|receiver| namespace_symbols.type_in_namespace(receiver))
+            .map(str::to_string)
+            .or_else(|| self.resolved_receiver_type(function, namespace_symbols, enclosing))?;
+        Some(format!("{}.{}", namespace_symbols.fqdn(&receiver)?, method))
+    }
 
-#[derive(Debug)]
-struct Search {
-    parts: Vec<SearchPart>,
-}
+    /// The argument list of a call, `None` for any other node.
+    fn argument_list(&self, node: Handle<Node>) -> Option<Handle<Node>> {
+        self.db
+            .outgoing_edges(node)
+            .find(|e| self.syntax_type(e.sink) == Some("argument-list"))
+            .map(|e| e.sink)
+    }
 
-impl Search {
-    fn create_search(query: String) -> anyhow::Result<Search, Error> {
-        let mut parts: Vec<SearchPart> = vec![];
-        let star_regex = Regex::new(".*")?;
-        for part in query.split(".") {
-            if part.contains("*") {
-                let regex: Regex = if part == "*" {
-                    star_regex.clone()
-                } else {
-                    Regex::new(part)?
-                };
+    /// The arguments of a call in the order they are written.
+    fn positional_arguments(&self, arguments: Handle<Node>) -> Vec<Handle<Node>> {
+        let mut positional: Vec<(usize, usize, Handle<Node>)> = self
+            .db
+            .outgoing_edges(arguments)
+            .filter(|e| self.syntax_type(e.sink) == Some("argument"))
+            .filter_map(|e| {
+                let span = &self.db.source_info(e.sink)?.span;
+                Some((span.start.line, span.start.column.utf8_offset, e.sink))
+            })
+            .collect();
+        positional.sort();
+        positional
+            .into_iter()
+            .map(|(_, _, argument)| argument)
+            .collect()
+    }
 
-                parts.push(SearchPart {
-                    part: part.to_string(),
-                    regex: Some(regex),
-                });
-            } else {
-                parts.push(SearchPart {
-                    part: part.to_string(),
-                    regex: None,
+    /// Whether the arguments of a call can be passed as the parameter types, by count and by
+    /// the type of each argument that can be told.
+    fn arguments_fit(
+        &self,
+        arguments: Handle<Node>,
+        parameter_types: &[String],
+        enclosing: &Enclosing,
+    ) -> bool {
+        let arguments = self.positional_arguments(arguments);
+        arguments.len() == parameter_types.len()
+            && arguments
+                .iter()
+                .zip(parameter_types)
+                .all(|(argument, parameter_type)| {
+                    self.argument_type(*argument, enclosing)
+                        .is_none_or(|argument_type| same_type(&argument_type, parameter_type))
                 })
+    }
+
+    /// The type of an argument, from its syntax or the declaration of the local it names.
+    fn argument_type(&self, argument: Handle<Node>, enclosing: &Enclosing) -> Option<String> {
+        if let Some(argument_type) = self.debug_value(argument, "argument_type") {
+            return Some(argument_type);
+        }
+        let method = enclosing.method_node?;
+        self.db
+            .outgoing_edges(argument)
+            .filter_map(|e| self.db[e.sink].symbol())
+            .filter_map(|symbol| self.find_local(method, symbol))
+            .find_map(|local| self.debug_value(local, "declared_type"))
+            .filter(|declared_type| declared_type != "var")
+    }
+
+    /// The result for the argument at the position when it is a matching literal.
+    fn argument_result(
+        &self,
+        arguments: Handle<Node>,
+        argument_value: &ArgumentValue,
+        file_uri: &str,
+        enclosing: &Enclosing,
+    ) -> Option<ResultNode> {
+        let argument = *self
+            .positional_arguments(arguments)
+            .get(argument_value.position)?;
+        let literal = self
+            .db
+            .outgoing_edges(argument)
+            .find(|e| self.syntax_type(e.sink) == Some("literal"))?
+            .sink;
+        let value = literal_value(&self.db[self.db[literal].symbol()?]).to_string();
+        if !argument_value.value.is_match(&value) {
+            return None;
+        }
+        let (line_number, code_location) = self.code_location(literal)?;
+        let mut variables = BTreeMap::from([
+            ("file".to_string(), Value::from(file_uri)),
+            (
+                "character_utf16".to_string(),
+                Value::from(code_location.start_position.character_utf16),
+            ),
+            (
+                "method".to_string(),
+                Value::from(argument_value.method.clone()),
+            ),
+            ("position".to_string(), Value::from(argument_value.position)),
+            ("value".to_string(), Value::from(value)),
+        ]);
+        if let Some(line) = self.source_line(literal, line_number) {
+            variables.insert("line".to_string(), Value::from(line));
+        }
+        if self.options.include_enclosing_method {
+            if let Some(method) = &enclosing.method {
+                variables.insert("enclosing_method".to_string(), Value::from(method.clone()));
             }
         }
+        Some(ResultNode {
+            file_uri: file_uri.to_string(),
+            line_number,
+            code_location,
+            variables,
+            fqdn: Some(argument_value.method.clone()),
+            effort: None,
+            is_dependency_incident: false,
+        })
+    }
 
-        Ok(Search { parts })
+    /// The text of a line of the file a node is in, trimmed. The containing line of the
+    /// source info is never saved to the database, so the file is read instead, once.
+    fn source_line(&self, node: Handle<Node>, line_number: usize) -> Option<String> {
+        let file = self.db[node].file()?;
+        let mut lines = self.lines.borrow_mut();
+        let lines = lines.entry(file).or_insert_with(|| {
+            std::fs::read(self.db[file].name())
+                .map(|source| {
+                    String::from_utf8_lossy(&source)
+                        .lines()
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        });
+        lines.get(line_number).map(|line| line.trim().to_string())
     }
 
-    fn all_references_search(&self) -> bool {
-        let last = self.parts.last();
-        match last {
-            None => false,
-            Some(part) => {
-                if part.part == "*" {
-                    return true;
+    /// Where a node is in its file. A graph read back from the database can be missing the
+    /// spans of its nodes, the graph of the file is then built again from the source on disk,
+    /// once, and the node looked up in it by its id. The node has to have the same symbol
+    /// there, so a file changed since it was indexed does not give positions of other code.
+    fn code_location(&self, node: Handle<Node>) -> Option<(usize, Location)> {
+        if let Some(location) = self.db.source_info(node).and_then(span_location) {
+            return Some(location);
+        }
+        let file = self.db[node].file()?;
+        let symbol = self.db[node]
+            .symbol()
+            .map(|symbol| self.db[symbol].to_string());
+        let mut rebuilt_locations = self.rebuilt_locations.borrow_mut();
+        let locations = rebuilt_locations
+            .entry(file)
+            .or_insert_with(|| self.rebuild_locations(file));
+        match locations.get(&self.db[node].id().local_id()) {
+            Some((rebuilt_symbol, location)) if *rebuilt_symbol == symbol => Some(location.clone()),
+            _ => None,
+        }
+    }
+
+    /// The symbol and location of every node of `file`, by local id, from building its graph
+    /// again. Nothing when the file can not be read or built.
+    fn rebuild_locations(&self, file: Handle<File>) -> HashMap<u32, RebuiltLocation> {
+        let path = PathBuf::from(self.db[file].name());
+        debug!("spans missing from the database, building {:?} again", path);
+        let language_config = self.language_config.get_or_init(|| {
+            SourceNodeLanguageConfiguration::shared(&tree_sitter_stack_graphs::NoCancellation)
+                .inspect_err(|e| debug!("unable to create language configuration: {}", e))
+                .ok()
+        });
+        let Some(language_config) = language_config else {
+            return HashMap::new();
+        };
+        let mut graph = StackGraph::new();
+        // Indexing adds one node for the source type to the file first, whichever type it
+        // is, so the ids of the nodes after it line up.
+        let (source_type, _) = SourceType::load_symbols_into_graph(&mut graph);
+        let root = path.parent().unwrap_or(&path).to_path_buf();
+        let rebuilt = load_graph_for_file(
+            path.clone(),
+            &root,
+            &mut graph,
+            &language_config.language_config,
+            &source_type,
+            &tree_sitter_stack_graphs::NoCancellation,
+        )
+        .inspect_err(|e| debug!("unable to build {:?} again: {}", path, e))
+        .ok()
+        .flatten();
+        let Some((rebuilt, _)) = rebuilt else {
+            return HashMap::new();
+        };
+        graph
+            .nodes_for_file(rebuilt)
+            .filter_map(|node| {
+                let location = graph.source_info(node).and_then(span_location)?;
+                let symbol = graph[node].symbol().map(|symbol| graph[symbol].to_string());
+                Some((graph[node].id().local_id(), (symbol, location)))
+            })
+            .collect()
+    }
+
+    /// Zero effort incidents for where the types and members of the namespace are defined in
+    /// the dependencies, which a search of the source does not look at.
+    fn dependency_definitions(&self, namespace_symbols: &NamespaceSymbols) -> Vec<ResultNode> {
+        let Some(dependency) = self.dependency_symbol() else {
+            return vec![];
+        };
+        let mut results: Vec<ResultNode> = namespace_symbols
+            .definitions
+            .iter()
+            .filter(|(node, _)| {
+                self.db[*node].file().is_some_and(|file| {
+                    self.has_source_type(file, dependency) && self.in_file_paths(file)
+                })
+            })
+            .filter_map(|(node, fqdn)| self.definition_result(*node, fqdn, "definition"))
+            .map(|mut result| {
+                flag_dependency_incident(&mut result);
+                ResultNode {
+                    effort: Some(0),
+                    ..result
                 }
-                false
-            }
+            })
+            .collect();
+        sort_by_location(&mut results);
+        results
+    }
+
+    /// The classes, interfaces, enums and records declared under the namespace the search
+    /// names, with the kind of declaration as `kind`. Only the files of `restrict_to` are
+    /// looked at when it is given.
+    fn declarations(
+        &self,
+        namespace_symbols: &NamespaceSymbols,
+        search: &Search,
+        restrict_to: Option<Option<Handle<Symbol>>>,
+    ) -> Vec<ResultNode> {
+        let dependency = self.dependency_symbol();
+        let mut results: Vec<ResultNode> = namespace_symbols
+            .definitions
+            .iter()
+            .filter(|(node, fqdn)| {
+                matches!(self.syntax_type(*node), Some("class-def" | "interface-def"))
+                    && search.declared_under(fqdn)
+            })
+            .filter(|(node, _)| {
+                self.db[*node]
+                    .file()
+                    .is_some_and(|file| self.in_file_paths(file))
+            })
+            .filter(|(node, _)| match restrict_to {
+                Some(source_type) => source_type.is_some_and(|source_type| {
+                    self.db[*node]
+                        .file()
+                        .is_some_and(|file| self.has_source_type(file, source_type))
+                }),
+                None => true,
+            })
+            .filter_map(|(node, fqdn)| {
+                let kind = self.debug_value(*node, "declaration_kind")?;
+                let mut result = self.definition_result(*node, fqdn, &kind)?;
+                let from_dependency = dependency.is_some_and(|dependency| {
+                    self.db[*node]
+                        .file()
+                        .is_some_and(|file| self.has_source_type(file, dependency))
+                });
+                if from_dependency {
+                    flag_dependency_incident(&mut result);
+                }
+                Some(result)
+            })
+            .collect();
+        sort_by_location(&mut results);
+        results
+    }
+
+    /// A result at where a type or member is defined.
+    fn definition_result(&self, node: Handle<Node>, fqdn: &str, kind: &str) -> Option<ResultNode> {
+        let file = self.db[node].file()?;
+        let (line_number, code_location) = self.code_location(node)?;
+        let file_uri = Url::from_file_path(self.db[file].name())
+            .ok()?
+            .as_str()
+            .to_string();
+        let mut variables = BTreeMap::from([
+            ("file".to_string(), Value::from(file_uri.clone())),
+            (
+                "character_utf16".to_string(),
+                Value::from(code_location.start_position.character_utf16),
+            ),
+            ("kind".to_string(), Value::from(kind)),
+            ("access_kind".to_string(), Value::from("declaration")),
+        ]);
+        if let Some(line) = self.source_line(node, line_number) {
+            variables.insert("line".to_string(), Value::from(line));
         }
+        Some(ResultNode {
+            file_uri,
+            line_number,
+            code_location,
+            variables,
+            fqdn: Some(fqdn.to_string()),
+            effort: None,
+            is_dependency_incident: false,
+        })
     }
 
-    fn partial_namespace(&self, symbol: &str) -> bool {
-        // We will need to break apart the symbol based on "." then looping through, look at the
-        // same index, and if it matches continue if it doesn't then return false.
-        for (i, symbol_part) in symbol.split(".").enumerate() {
-            if self.parts.len() <= i {
-                break;
+    /// The symbol `source_type` tags files with in this graph. The graph read back from the
+    /// database interns symbols in the order it loads them, so the handle of the language
+    /// configuration can not be used as is.
+    fn source_type_symbol(&self, source_type: &SourceType) -> Option<Handle<Symbol>> {
+        let name = source_type.get_string();
+        self.db.iter_symbols().find(|s| self.db[*s] == name)
+    }
+
+    /// Whether `file` is one the query is limited to by its `file_paths`.
+    fn in_file_paths(&self, file: Handle<File>) -> bool {
+        self.options
+            .file_paths
+            .as_ref()
+            .is_none_or(|file_paths| file_paths.matches(self.db[file].name()))
+    }
+
+    /// The symbol files loaded as dependencies are tagged with, when the graph has any.
+    fn dependency_symbol(&self) -> Option<Handle<Symbol>> {
+        let dependency_string = SourceType::get_dependency_string();
+        self.db
+            .iter_symbols()
+            .find(|s| self.db[*s] == dependency_string)
+    }
+
+    /// Whether `file` was loaded as the given source type, which links a node for it to the
+    /// compilation unit.
+    fn has_source_type(&self, file: Handle<File>, source_type: Handle<Symbol>) -> bool {
+        let Some(comp_unit) = self.index.comp_units.get(&file).copied() else {
+            return false;
+        };
+        self.db.nodes_for_file(file).any(|node| {
+            self.db[node].symbol() == Some(source_type)
+                && self.db.outgoing_edges(node).any(|e| e.sink == comp_unit)
+        })
+    }
+
+    /// The fully qualified name `node` declares, when it is the declaration of a type or
+    /// member.
+    fn declared_fqdn(
+        &self,
+        node: Handle<Node>,
+        symbol: &str,
+        enclosing: &Enclosing,
+    ) -> Option<String> {
+        if !matches!(
+            self.syntax_type(node),
+            Some(
+                "class-def"
+                    | "interface-def"
+                    | "method_name"
+                    | "constructor"
+                    | "field_name"
+                    | "property"
+            )
+        ) {
+            return None;
+        }
+        Some(match enclosing.scope.as_str() {
+            "" => symbol.to_string(),
+            scope => format!("{}.{}", scope, symbol),
+        })
+    }
+
+    /// What the children of `node` are nested in.
+    fn enter(&self, node: Handle<Node>, enclosing: &Enclosing) -> Enclosing {
+        let mut enclosing = enclosing.clone();
+        let Some(symbol) = self.db[node].symbol() else {
+            return enclosing;
+        };
+        let name = &self.db[symbol];
+        let qualified = |scope: &str| match scope {
+            "" => name.to_string(),
+            scope => format!("{}.{}", scope, name),
+        };
+        match self.syntax_type(node) {
+            Some("namespace-declaration") => enclosing.scope = qualified(&enclosing.scope),
+            Some("class-def" | "interface-def") => {
+                enclosing.class = Some(node);
+                enclosing.scope = qualified(&enclosing.scope);
             }
-            if !self.parts[i].matches(symbol_part.to_string()) {
-                return false;
+            Some("method_name" | "constructor") => {
+                enclosing.method = Some(qualified(&enclosing.scope));
+                enclosing.method_node = Some(node);
             }
+            _ => {}
         }
-        true
+        enclosing
     }
 
-    fn match_namespace(&self, symbol: &str) -> bool {
-        for (i, symbol_part) in symbol.split(".").enumerate() {
-            // Because we can assume that the last part here is a '*' right now,
-            // we anything past that should match
-            if self.parts.len() <= i {
-                break;
-            }
-            if !self.parts[i].matches(symbol_part.to_string()) {
-                return false;
+    /// Names of the types `class` derives from, nearest first, continuing up the chain through
+    /// bases that are declared in the searched namespace.
+    fn base_types(&self, class: Handle<Node>, namespace_symbols: &NamespaceSymbols) -> Vec<String> {
+        let mut base_types: Vec<String> = vec![];
+        let mut pending: VecDeque<Handle<Node>> = VecDeque::from([class]);
+        while let Some(class) = pending.pop_front() {
+            for edge in self.db.outgoing_edges(class) {
+                if self.syntax_type(edge.sink) != Some("base-type") {
+                    continue;
+                }
+                let Some(symbol) = self.db[edge.sink].symbol() else {
+                    continue;
+                };
+                let name = self.db[symbol].to_string();
+                if base_types.contains(&name) {
+                    continue;
+                }
+                if let Some(base) = namespace_symbols.classes.get(&name) {
+                    pending.push_back(*base);
+                }
+                base_types.push(name);
             }
         }
-        true
+        base_types
     }
 
-    // fn import_match
-    //Namespace Match
-    //Part Match
-    //Regex Match
-    //???
-}
+    /// What an attribute usage applies to, the explicit target of `[return: X]` when there is
+    /// one, otherwise the kind of declaration it is on.
+    fn attribute_target(&self, node: Handle<Node>) -> Option<String> {
+        self.debug_value(node, "attribute_target_specifier")
+            .map(|specifier| specifier.trim_end_matches(':').trim().to_string())
+            .or_else(|| self.debug_value(node, "attribute_target"))
+    }
 
-impl SearchPart {
-    fn matches(&self, match_string: String) -> bool {
+    /// The positional and named arguments an attribute is applied with, in the order they are
+    /// written and without the quotes of string literals.
+    fn attribute_arguments(
+        &self,
+        attribute: Handle<Node>,
+    ) -> (Vec<String>, serde_json::Map<String, Value>) {
+        let mut arguments: Vec<(usize, usize, Handle<Node>)> = self
+            .db
+            .outgoing_edges(attribute)
+            .filter(|e| self.syntax_type(e.sink) == Some("attribute-argument"))
+            .filter_map(|e| {
+                let span = &self.db.source_info(e.sink)?.span;
+                Some((span.start.line, span.start.column.utf8_offset, e.sink))
+            })
+            .collect();
+        arguments.sort();
+        let mut positional = vec![];
+        let mut named = serde_json::Map::new();
+        for (_, _, argument) in arguments {
+            match (
+                self.debug_value(argument, "name"),
+                self.debug_value(argument, "named_value"),
+                self.debug_value(argument, "value"),
+            ) {
+                (Some(name), Some(value), _) => {
+                    named.insert(name, Value::from(literal_value(&value)));
+                }
+                (_, _, Some(value)) => positional.push(literal_value(&value).to_string()),
+                _ => {}
+            }
+        }
+        (positional, named)
+    }
+
+    /// How the source uses what the node refers to. Declarations are told apart by their syntax
+    /// type, calls and assignments are marked by the TSG, anything else is a read.
+    fn access_kind(&self, node: Handle<Node>) -> &'static str {
+        match self.syntax_type(node) {
+            Some(
+                "class-def"
+                | "interface-def"
+                | "method_name"
+                | "constructor"
+                | "field_name"
+                | "property"
+                | "indexer"
+                | "local_var"
+                | "namespace-declaration",
+            ) => "declaration",
+            _ => match self.debug_value(node, "access").as_deref() {
+                Some("invocation") => "invocation",
+                Some("write") => "write",
+                _ => "read",
+            },
+        }
+    }
+
+    /// A `debug_` attribute the TSG set on the node, without the prefix.
+    fn debug_value(&self, node: Handle<Node>, key: &str) -> Option<String> {
+        self.db
+            .node_debug_info(node)?
+            .iter()
+            .find(|e| &self.db[e.key] == key)
+            .map(|e| self.db[e.value].to_string())
+    }
+
+    fn syntax_type(&self, node: Handle<Node>) -> Option<&str> {
+        let source_info = self.db.source_info(node)?;
+        let syntax_type = source_info.syntax_type.into_option()?;
+        Some(&self.db[syntax_type])
+    }
+
+    /// The type aliases a file declares, `using B = Demo.Generic.Box<int>;` makes `B` stand
+    /// for `Box`.
+    fn type_aliases(&self, comp_unit: Handle<Node>) -> HashMap<String, String> {
+        self.db
+            .outgoing_edges(comp_unit)
+            .filter(|edge| self.syntax_type(edge.sink) == Some("import"))
+            .filter_map(|edge| {
+                let alias = self.debug_value(edge.sink, "alias")?;
+                let type_name = &self.db[self.db[edge.sink].symbol()?];
+                Some((alias, short_type_name(type_name).to_string()))
+            })
+            .collect()
+    }
+
+    /// The short name of the type a name stands for in the file being searched, the name
+    /// itself unless it is an alias.
+    fn aliased<'s>(&'s self, name: &'s str) -> &'s str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+
+    /// The type in the searched namespace the receiver of a member access evaluates to.
+    fn receiver_type(
+        &self,
+        member_access: Handle<Node>,
+        namespace_symbols: &NamespaceSymbols,
+    ) -> Option<String> {
+        self.db
+            .outgoing_edges(member_access)
+            .filter(|edge| {
+                !matches!(
+                    self.syntax_type(edge.sink),
+                    Some("member-access") | Some("indexer-access")
+                )
+            })
+            .filter_map(|edge| self.expression_type(edge.sink, namespace_symbols))
+            .find(|type_name| namespace_symbols.type_in_namespace(type_name))
+    }
+
+    /// The type in the searched namespace with an indexer that an element access uses, from
+    /// what the receiver evaluates to or, for `settings["key"]`, the declared type of the
+    /// local variable.
+    fn indexed_type(
+        &self,
+        element_access: Handle<Node>,
+        namespace_symbols: &NamespaceSymbols,
+        enclosing: &Enclosing,
+    ) -> Option<String> {
+        let receiver = self.resolved_receiver_type(element_access, namespace_symbols, enclosing)?;
+        namespace_symbols
+            .indexers
+            .contains(&receiver)
+            .then_some(receiver)
+    }
+
+    /// The type in the searched namespace the receiver of a member or element access
+    /// evaluates to, following a local variable receiver to the type it is declared or
+    /// initialized with.
+    fn resolved_receiver_type(
+        &self,
+        access: Handle<Node>,
+        namespace_symbols: &NamespaceSymbols,
+        enclosing: &Enclosing,
+    ) -> Option<String> {
+        self.receiver_type(access, namespace_symbols).or_else(|| {
+            let method = enclosing.method_node?;
+            self.db
+                .outgoing_edges(access)
+                .filter(|edge| {
+                    !matches!(
+                        self.syntax_type(edge.sink),
+                        Some("member-access") | Some("indexer-access")
+                    )
+                })
+                .filter_map(|edge| self.db[edge.sink].symbol())
+                .filter_map(|symbol| self.find_local(method, symbol))
+                .find_map(|local| self.local_type(local, namespace_symbols))
+        })
+    }
+
+    /// The declaration of the local variable named `symbol` in a method.
+    fn find_local(&self, method: Handle<Node>, symbol: Handle<Symbol>) -> Option<Handle<Node>> {
+        let file = self.db[method].file()?;
+        let mut visited: HashSet<Handle<Node>> = HashSet::from([method]);
+        let mut pending = vec![method];
+        while let Some(node) = pending.pop() {
+            for edge in self.db.outgoing_edges(node) {
+                if self.db[edge.sink].file() != Some(file) || !visited.insert(edge.sink) {
+                    continue;
+                }
+                if self.db[edge.sink].symbol() == Some(symbol)
+                    && self.syntax_type(edge.sink) == Some("local_var")
+                {
+                    return Some(edge.sink);
+                }
+                pending.push(edge.sink);
+            }
+        }
+        None
+    }
+
+    /// The type of a local variable, declared (`Settings s = ...`) or inferred from what it
+    /// is initialized with (`var s = new Settings()`).
+    fn local_type(
+        &self,
+        local: Handle<Node>,
+        namespace_symbols: &NamespaceSymbols,
+    ) -> Option<String> {
+        self.expression_type(local, namespace_symbols)
+            .filter(|type_name| namespace_symbols.type_in_namespace(type_name))
+            .or_else(|| {
+                self.db
+                    .outgoing_edges(local)
+                    .flat_map(|declarator| self.db.outgoing_edges(declarator.sink))
+                    .filter_map(|value| self.expression_type(value.sink, namespace_symbols))
+                    .find(|type_name| namespace_symbols.type_in_namespace(type_name))
+            })
+    }
+
+    /// Resolves the type an expression evaluates to by following the return type of the
+    /// method it calls, either on a receiver (`config.GetSection("x")`) or directly
+    /// (`GetConfig()`), or the type it creates (`new Customer("x")`). Only types and methods
+    /// defined in the searched namespace can be followed.
+    fn expression_type(
+        &self,
+        expr: Handle<Node>,
+        namespace_symbols: &NamespaceSymbols,
+    ) -> Option<String> {
+        // `typeof(Settings)` evaluates to the `Type`, not to a `Settings`.
+        if self.syntax_type(expr) == Some("typeof") {
+            return None;
+        }
+        // Reading a field or property, their types are not tracked.
+        if self
+            .db
+            .outgoing_edges(expr)
+            .any(|edge| self.syntax_type(edge.sink) == Some("member-access"))
+        {
+            return None;
+        }
+        self.db.outgoing_edges(expr).find_map(|edge| {
+            let member = self
+                .db
+                .outgoing_edges(edge.sink)
+                .find(|e| self.syntax_type(e.sink) == Some("member-access"));
+            let called = member.map_or(edge.sink, |e| e.sink);
+            let symbol = self.aliased(&self.db[self.db[called].symbol()?]);
+            if namespace_symbols.type_in_namespace(symbol) {
+                return Some(symbol.to_string());
+            }
+            // A method called on a receiver is the one of the type the receiver evaluates to.
+            let receiver = member.and_then(|_| self.receiver_type(edge.sink, namespace_symbols));
+            namespace_symbols.method_return(receiver.as_deref(), symbol)
+        })
+    }
+}
+
+/// Orders results by where they are, file, line then column.
+fn sort_by_location(results: &mut [ResultNode]) {
+    results.sort_by(|a, b| {
+        (
+            &a.file_uri,
+            a.line_number,
+            a.code_location.start_position.character,
+        )
+            .cmp(&(
+                &b.file_uri,
+                b.line_number,
+                b.code_location.start_position.character,
+            ))
+    });
+}
+
+/// Whether a type written at a call and one written in a condition name the same type,
+/// ignoring namespaces and whether the keyword or the framework name is used, `string` is
+/// `System.String`. Any argument can be passed as an `object`.
+fn same_type(argument_type: &str, parameter_type: &str) -> bool {
+    let keyword = |type_name: &str| -> String {
+        let type_name = type_name.trim().trim_start_matches("global::");
+        let name = type_name.rsplit('.').next().unwrap_or(type_name);
+        match name {
+            "String" => "string",
+            "Char" => "char",
+            "Boolean" => "bool",
+            "Byte" => "byte",
+            "Int16" => "short",
+            "Int32" => "int",
+            "Int64" => "long",
+            "Single" => "float",
+            "Double" => "double",
+            "Decimal" => "decimal",
+            "Object" => "object",
+            name => name,
+        }
+        .to_string()
+    };
+    let parameter_type = keyword(parameter_type);
+    parameter_type == "object" || keyword(argument_type) == parameter_type
+}
+
+/// The name a type is declared with, without its namespace or type arguments.
+fn short_type_name(type_name: &str) -> &str {
+    let type_name = without_type_arguments(type_name);
+    type_name.rsplit('.').next().unwrap_or(type_name)
+}
+
+/// A type name without its type arguments, `Demo.Generic.Box<int>` is `Demo.Generic.Box`.
+fn without_type_arguments(type_name: &str) -> &str {
+    type_name.split('<').next().unwrap_or(type_name).trim()
+}
+
+/// A path with `/` separators, so the paths of every platform compare the same.
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// The symbol a node had when its file was built again and where it is.
+type RebuiltLocation = (Option<String>, (usize, Location));
+
+/// The line and location of a span, `None` for the empty span of a node saved without one.
+fn span_location(source_info: &SourceInfo) -> Option<(usize, Location)> {
+    let span = &source_info.span;
+    if *span == Default::default() {
+        return None;
+    }
+    Some((
+        span.start.line,
+        Location {
+            start_position: Position {
+                line: span.start.line,
+                character: span.start.column.utf8_offset,
+                character_utf16: span.start.column.utf16_offset,
+            },
+            end_position: Position {
+                line: span.end.line,
+                character: span.end.column.utf8_offset,
+                character_utf16: span.end.column.utf16_offset,
+            },
+        },
+    ))
+}
+
+/// Flags a result found in a dependency, adding where its file is in the source the dependency
+/// was decompiled into as `dependency_relative_path`.
+fn flag_dependency_incident(result: &mut ResultNode) {
+    result.is_dependency_incident = true;
+    if let Some(path) = dependency_relative_path(&result.file_uri) {
+        result
+            .variables
+            .insert("dependency_relative_path".to_string(), Value::from(path));
+    }
+}
+
+/// The path of a decompiled file under the directory its dependency was decompiled into,
+/// `Antlr/Runtime/Lexer.cs` for `packages/Antlr/lib/net40/Antlr-decompiled/Antlr/Runtime/Lexer.cs`.
+fn dependency_relative_path(file_uri: &str) -> Option<String> {
+    let path = Url::parse(file_uri).ok()?.to_file_path().ok()?;
+    let components: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let root = components
+        .iter()
+        .rposition(|c| c.ends_with("-decompiled"))?;
+    Some(components[root + 1..].join("/"))
+}
+
+/// Whether a literal is a string, regular, verbatim, interpolated or raw.
+fn is_string(literal: &str) -> bool {
+    literal.trim_start_matches(['@', '$']).starts_with('"')
+}
+
+/// The value a literal is written for, strings and characters without their quotes.
+fn literal_value(literal: &str) -> &str {
+    let unquoted = |quote: char| {
+        literal
+            .trim_start_matches('@')
+            .strip_prefix(quote)
+            .and_then(|l| l.strip_suffix(quote))
+    };
+    unquoted('"').or_else(|| unquoted('\'')).unwrap_or(literal)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NamespaceKind {
+    Import,
+    Declaration,
+}
+
+#[derive(Debug)]
+struct IndexedNamespace {
+    node: Handle<Node>,
+    file: Handle<File>,
+    symbol: String,
+    kind: NamespaceKind,
+}
+
+/// Where a search starts: the namespace declarations it matches, whose definitions are
+/// searched for, and the files that import or declare them, in a fixed order so results are
+/// reproducible between runs.
+#[derive(Debug)]
+struct StartingNodes {
+    definition_root_nodes: Vec<Handle<Node>>,
+    referenced_files: Vec<Handle<File>>,
+}
+
+/// The nodes a search starts from: the compilation unit of every file, and the imports and
+/// namespace declarations grouped by the first segment of their namespace, so a search only
+/// looks at the namespaces it could match instead of every node in the graph. Built once per
+/// graph, and rebuilt when the graph is replaced.
+#[derive(Debug, Default)]
+pub struct NamespaceIndex {
+    comp_units: HashMap<Handle<File>, Handle<Node>>,
+    by_root: HashMap<String, Vec<IndexedNamespace>>,
+    // namespace prefix of a search to where it starts, shared by every search with that prefix.
+    // Keyed by each part of the prefix with whether it ignores case, as searches only ignore
+    // the case of their namespace segments.
+    starting_nodes: HashMap<Vec<(String, bool)>, Arc<StartingNodes>>,
+}
+
+impl NamespaceIndex {
+    pub fn new(db: &StackGraph) -> NamespaceIndex {
+        let mut index = NamespaceIndex::default();
+        for node_handle in db.iter_nodes() {
+            let node: &Node = &db[node_handle];
+            let (Some(file), Some(symbol)) = (node.file(), node.symbol()) else {
+                continue;
+            };
+            let Some(syntax_type) = db
+                .source_info(node_handle)
+                .and_then(|s| s.syntax_type.into_option())
+            else {
+                continue;
+            };
+            let kind = match &db[syntax_type] {
+                "comp-unit" => {
+                    index.comp_units.insert(file, node_handle);
+                    continue;
+                }
+                "import" => NamespaceKind::Import,
+                "namespace-declaration" => NamespaceKind::Declaration,
+                _ => continue,
+            };
+            // An aliased generic type is imported under the name it is declared with.
+            let symbol = without_type_arguments(&db[symbol]).to_string();
+            let root = symbol.split('.').next().unwrap_or_default().to_string();
+            index
+                .by_root
+                .entry(root)
+                .or_default()
+                .push(IndexedNamespace {
+                    node: node_handle,
+                    file,
+                    symbol,
+                    kind,
+                });
+        }
+        index
+    }
+
+    fn starting_nodes(&mut self, db: &StackGraph, search: &Search) -> Arc<StartingNodes> {
+        let prefix = search.namespace_prefix();
+        if let Some(starting_nodes) = self.starting_nodes.get(&prefix) {
+            return starting_nodes.clone();
+        }
+        let mut definition_root_nodes: Vec<Handle<Node>> = vec![];
+        let mut referenced_files: HashSet<Handle<File>> = HashSet::new();
+        for candidate in candidates(&self.by_root, search) {
+            match candidate.kind {
+                NamespaceKind::Import => {
+                    if search.partial_namespace(&candidate.symbol) {
+                        referenced_files.insert(candidate.file);
+                    }
+                }
+                NamespaceKind::Declaration => {
+                    if search.match_namespace(&candidate.symbol) {
+                        definition_root_nodes.push(candidate.node);
+                        referenced_files.insert(candidate.file);
+                    }
+                }
+            }
+        }
+        let mut referenced_files: Vec<Handle<File>> = referenced_files.into_iter().collect();
+        referenced_files.sort_by(|a, b| db[*a].name().cmp(db[*b].name()));
+        let starting_nodes = Arc::new(StartingNodes {
+            definition_root_nodes,
+            referenced_files,
+        });
+        self.starting_nodes.insert(prefix, starting_nodes.clone());
+        starting_nodes
+    }
+}
+
+/// The imports and namespace declarations, grouped by the first segment of their namespace,
+/// that could match the search. When the search starts with a pattern rather than a plain
+/// segment every namespace is a candidate.
+fn candidates<'a, T>(
+    by_root: &'a HashMap<String, Vec<T>>,
+    search: &Search,
+) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+    match search.parts.first() {
+        Some(SearchPart {
+            part,
+            regex: None,
+            case_insensitive: true,
+        }) => {
+            let part = part.to_lowercase();
+            Box::new(
+                by_root
+                    .iter()
+                    .filter(move |(root, _)| root.to_lowercase() == part)
+                    .flat_map(|(_, namespaces)| namespaces),
+            )
+        }
+        Some(SearchPart {
+            part, regex: None, ..
+        }) => Box::new(by_root.get(part).into_iter().flatten()),
+        _ => Box::new(by_root.values().flatten()),
+    }
+}
+
+#[derive(Debug)]
+struct FileNamespace {
+    file: String,
+    symbol: String,
+    kind: NamespaceKind,
+}
+
+/// The namespaces each file of a graph imports or declares, by the name of the file and
+/// grouped like in `NamespaceIndex`. That is all it takes to tell which files a query
+/// searches, so a graph read back from the databases for each query only needs those files.
+#[derive(Debug, Default)]
+pub struct NamespaceFiles {
+    by_root: HashMap<String, Vec<FileNamespace>>,
+}
+
+impl NamespaceFiles {
+    pub fn new(db: &StackGraph) -> NamespaceFiles {
+        let index = NamespaceIndex::new(db);
+        let mut files = NamespaceFiles::default();
+        for (root, namespaces) in index.by_root {
+            files.by_root.insert(
+                root,
+                namespaces
+                    .into_iter()
+                    .map(|namespace| FileNamespace {
+                        file: db[namespace.file].name().to_string(),
+                        symbol: namespace.symbol,
+                        kind: namespace.kind,
+                    })
+                    .collect(),
+            );
+        }
+        files
+    }
+
+    /// Forgets the namespaces of `file`, for when it is indexed again or removed.
+    pub fn remove_file(&mut self, file: &str) {
+        for namespaces in self.by_root.values_mut() {
+            namespaces.retain(|namespace| namespace.file != file);
+        }
+        self.by_root.retain(|_, namespaces| !namespaces.is_empty());
+    }
+
+    pub fn extend(&mut self, other: NamespaceFiles) {
+        for (root, namespaces) in other.by_root {
+            self.by_root.entry(root).or_default().extend(namespaces);
+        }
+    }
+
+    /// The files a query searches, those importing or declaring a namespace it matches, the
+    /// same files `NamespaceIndex` starts it from.
+    pub fn files(&self, query: &str, options: &QueryOptions) -> Result<BTreeSet<String>, Error> {
+        let (search, _, _) = searches(query.to_string(), &mut options.clone())?;
+        if !search.all_references_search() {
+            return Ok(BTreeSet::new());
+        }
+        Ok(candidates(&self.by_root, &search)
+            .filter(|namespace| match namespace.kind {
+                NamespaceKind::Import => search.partial_namespace(&namespace.symbol),
+                NamespaceKind::Declaration => search.match_namespace(&namespace.symbol),
+            })
+            .map(|namespace| namespace.file.clone())
+            .collect())
+    }
+}
+
+pub struct NamespaceSymbols {
+    classes: HashMap<String, Handle<Node>>,
+    class_fields: HashMap<String, Handle<Node>>,
+    class_methods: HashMap<String, Handle<Node>>,
+    // `Type.Method`, by the short name of the type, to the name of the type it returns.
+    method_returns: HashMap<String, String>,
+    // short name of a type or member to its fully qualified name.
+    fqdns: HashMap<String, String>,
+    // short name and kind of a type or member to its fully qualified name, a class and a
+    // method can share a short name.
+    kind_fqdns: HashMap<(SymbolKind, String), String>,
+    // fully qualified name of a type or member to what it is declared as.
+    kinds: HashMap<String, SymbolKind>,
+    // where each type and member is defined, with its fully qualified name.
+    definitions: Vec<(Handle<Node>, String)>,
+    // short names of the types that declare an indexer.
+    indexers: HashSet<String>,
+    // short names of the interfaces.
+    interfaces: HashSet<String>,
+}
+
+impl NamespaceSymbols {
+    fn new(db: &StackGraph, nodes: Vec<Handle<Node>>) -> anyhow::Result<NamespaceSymbols, Error> {
+        let mut namespace_symbols = NamespaceSymbols {
+            classes: HashMap::new(),
+            class_fields: HashMap::new(),
+            class_methods: HashMap::new(),
+            method_returns: HashMap::new(),
+            fqdns: HashMap::new(),
+            kind_fqdns: HashMap::new(),
+            kinds: HashMap::new(),
+            definitions: vec![],
+            indexers: HashSet::new(),
+            interfaces: HashSet::new(),
+        };
+
+        for node_handle in nodes {
+            let namespace = db[node_handle]
+                .symbol()
+                .map(|s| db[s].to_string())
+                .unwrap_or_default();
+            //Get all the edges
+            namespace_symbols.traverse_node(db, node_handle, &namespace)
+        }
+
+        Ok(namespace_symbols)
+    }
+
+    fn traverse_node(&mut self, db: &StackGraph, node: Handle<Node>, scope: &str) {
+        let mut child_edges: Vec<(Handle<Node>, String)> = vec![];
+        for edge in db.outgoing_edges(node) {
+            let child_node = &db[edge.sink];
+            let symbol = match child_node.symbol() {
+                None => {
+                    child_edges.push((edge.sink, scope.to_string()));
+                    continue;
+                }
+                Some(symbol) => &db[symbol],
+            };
+            let fqdn = format!("{}.{}", scope, symbol);
+            let syntax_type = db
+                .source_info(edge.sink)
+                .and_then(|s| s.syntax_type.into_option())
+                .map(|s| &db[s]);
+            match syntax_type {
+                Some("method_name") => {
+                    self.class_methods.insert(symbol.to_string(), edge.sink);
+                    self.insert_fqdn(symbol, &fqdn, SymbolKind::Method);
+                    self.definitions.push((edge.sink, fqdn));
+                }
+                Some(syntax_type @ ("class-def" | "interface-def")) => {
+                    if syntax_type == "interface-def" {
+                        self.interfaces.insert(symbol.to_string());
+                    }
+                    self.classes.insert(symbol.to_string(), edge.sink);
+                    self.insert_fqdn(symbol, &fqdn, SymbolKind::Type);
+                    if syntax_type == "class-def" {
+                        // Every class can be created, with the constructor the compiler adds
+                        // when none is declared.
+                        self.kinds
+                            .insert(format!("{}.{}", fqdn, symbol), SymbolKind::Method);
+                    }
+                    self.definitions.push((edge.sink, fqdn.clone()));
+                    // Members of the class are scoped to it.
+                    child_edges.push((edge.sink, fqdn));
+                    continue;
+                }
+                Some("constructor") => {
+                    // Named after the class, the short name is left to the class.
+                    self.kinds.insert(fqdn.clone(), SymbolKind::Method);
+                    self.definitions.push((edge.sink, fqdn));
+                }
+                Some("field_name" | "property") => {
+                    self.class_fields.insert(symbol.to_string(), edge.sink);
+                    self.insert_fqdn(symbol, &fqdn, SymbolKind::Field);
+                    self.definitions.push((edge.sink, fqdn));
+                }
+                Some("indexer") => {
+                    let class = scope.rsplit('.').next().unwrap_or(scope);
+                    self.indexers.insert(class.to_string());
+                    self.definitions.push((edge.sink, fqdn));
+                }
+                Some("return-type") => {
+                    // The return type hangs directly off the method declaration, which is
+                    // scoped to the type declaring it.
+                    if let Some(method) = db[node].symbol() {
+                        let class = scope.rsplit('.').next().unwrap_or(scope);
+                        self.method_returns
+                            .insert(format!("{}.{}", class, &db[method]), symbol.to_string());
+                    }
+                }
+                _ => {}
+            }
+            child_edges.push((edge.sink, scope.to_string()));
+        }
+        for (child_edge, scope) in child_edges {
+            self.traverse_node(db, child_edge, &scope);
+        }
+    }
+
+    /// The type a method returns, looked up on the type declaring it when that is known.
+    /// Otherwise it is only known when every type with a method of that name returns the
+    /// same type.
+    fn method_return(&self, class: Option<&str>, method: &str) -> Option<String> {
+        if let Some(class) = class {
+            return self
+                .method_returns
+                .get(&format!("{}.{}", class, method))
+                .cloned();
+        }
+        let mut returns = self
+            .method_returns
+            .iter()
+            .filter(|(key, _)| key.rsplit_once('.').is_some_and(|(_, m)| m == method))
+            .map(|(_, returns)| returns);
+        let first = returns.next()?;
+        returns.all(|other| other == first).then(|| first.clone())
+    }
+
+    fn insert_fqdn(&mut self, symbol: &str, fqdn: &str, kind: SymbolKind) {
+        self.fqdns.insert(symbol.to_string(), fqdn.to_string());
+        self.kind_fqdns
+            .insert((kind, symbol.to_string()), fqdn.to_string());
+        self.kinds.insert(fqdn.to_string(), kind);
+    }
+
+    /// The fully qualified name of a type or member declared in the namespace.
+    fn fqdn(&self, symbol: &str) -> Option<String> {
+        self.fqdns.get(symbol).cloned()
+    }
+
+    /// The fully qualified name of the type or member of the given kind declared with the
+    /// short name, any kind when none is given.
+    fn kind_fqdn(&self, symbol: &str, kind: Option<SymbolKind>) -> Option<String> {
+        match kind {
+            Some(kind) => self.kind_fqdns.get(&(kind, symbol.to_string())).cloned(),
+            None => self.fqdn(symbol),
+        }
+    }
+
+    /// The fully qualified name of a type or member named as written, `Call`, `Api.Call` or
+    /// `Demo.Lib.Api.Call`.
+    fn named_fqdn(&self, name: &str) -> Option<String> {
+        let short = name.rsplit('.').next().unwrap_or(name);
+        self.fqdn(short)
+            .filter(|fqdn| fqdn == name || fqdn.ends_with(&format!(".{}", name)))
+    }
+
+    /// The fully qualified name of the first type or member named in free text, a comment or
+    /// a string, where names are the dotted identifiers it contains.
+    fn text_fqdn(&self, text: &str) -> Option<String> {
+        text.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .flat_map(|name| name.split("..").map(|name| name.trim_matches('.')))
+            .filter(|name| !name.is_empty())
+            .find_map(|name| self.named_fqdn(name))
+    }
+
+    /// Whether a type or member of the given kind, any kind when none is given, is declared
+    /// with the short name.
+    /// Whether a `using` of `namespace` imports any of the types and members, or names one of
+    /// them itself.
+    fn imported_by(&self, namespace: &str) -> bool {
+        let prefix = format!("{}.", namespace);
+        self.kinds
+            .keys()
+            .any(|fqdn| fqdn == namespace || fqdn.starts_with(&prefix))
+    }
+
+    fn symbol_in_namespace(&self, symbol: &str, kind: Option<SymbolKind>) -> bool {
+        let class_match = self.classes.contains_key(symbol);
+        let method_match = self.class_methods.contains_key(symbol);
+        let field_match = self.class_fields.contains_key(symbol);
+
+        match kind {
+            Some(SymbolKind::Type) => class_match,
+            Some(SymbolKind::Method) => method_match,
+            Some(SymbolKind::Field) => field_match,
+            None => class_match || method_match || field_match,
+        }
+    }
+
+    /// The class an attribute usage refers to, `[Audited]` and `[Konveyor.Audited]` both
+    /// refer to `AuditedAttribute` unless a class named `Audited` is declared.
+    fn attribute_class<'a>(&'a self, name: &str) -> Option<&'a str> {
+        let name = name.rsplit('.').next().unwrap_or(name);
+        [name.to_string(), format!("{}Attribute", name)]
+            .into_iter()
+            .find_map(|class| self.classes.get_key_value(&class).map(|(k, _)| k.as_str()))
+    }
+
+    fn type_in_namespace(&self, type_name: &str) -> bool {
+        self.classes.contains_key(type_name)
+    }
+}
+
+#[derive(Debug)]
+struct SearchPart {
+    part: String,
+    regex: Option<Regex>,
+    // A namespace segment of a search that ignores namespace casing.
+    case_insensitive: bool,
+}
+
+#[derive(Debug)]
+struct Search {
+    parts: Vec<SearchPart>,
+}
+
+impl Search {
+    fn create_search(
+        query: String,
+        namespace_case_insensitive: bool,
+    ) -> anyhow::Result<Search, Error> {
+        let parts: Vec<String> = query.split(".").map(str::to_string).collect();
+        // A trailing `*` stands for everything in the namespace before it, otherwise the
+        // pattern ends with a type and a member of it.
+        let namespace_parts = match parts.last() {
+            Some(last) if last == "*" => parts.len() - 1,
+            _ => parts.len().saturating_sub(2),
+        };
+        Search::from_parts(parts, namespace_parts, namespace_case_insensitive)
+    }
+
+    /// The search for parts that are already split, each part is matched against one segment
+    /// of a name as it is. Only the first `namespace_parts` are namespace segments, the case
+    /// of the type and member after them always matters.
+    fn from_parts(
+        query_parts: Vec<String>,
+        namespace_parts: usize,
+        namespace_case_insensitive: bool,
+    ) -> anyhow::Result<Search, Error> {
+        let mut parts: Vec<SearchPart> = vec![];
+        for (i, part) in query_parts.into_iter().enumerate() {
+            let case_insensitive = namespace_case_insensitive && i < namespace_parts;
+            let regex = match part.contains("*") {
+                true => Some(wildcard_regex(&part, case_insensitive)?),
+                false => None,
+            };
+            parts.push(SearchPart {
+                part,
+                regex,
+                case_insensitive,
+            });
+        }
+
+        Ok(Search { parts })
+    }
+
+    /// The parts of the search without its trailing `*`, each with whether it ignores case.
+    /// Searches with the same prefix start from the same nodes.
+    fn namespace_prefix(&self) -> Vec<(String, bool)> {
+        let parts = match self.parts.split_last() {
+            Some((last, rest)) if last.part == "*" => rest,
+            _ => &self.parts,
+        };
+        parts
+            .iter()
+            .map(|p| (p.part.clone(), p.case_insensitive))
+            .collect()
+    }
+
+    fn all_references_search(&self) -> bool {
+        let last = self.parts.last();
+        match last {
+            None => false,
+            Some(part) => {
+                if part.part == "*" {
+                    return true;
+                }
+                false
+            }
+        }
+    }
+
+    /// Whether a fully qualified name is nested under the namespace the search names, that is
+    /// every part but the trailing `*`.
+    fn declared_under(&self, fqdn: &str) -> bool {
+        let namespace = match self.parts.split_last() {
+            Some((_, namespace)) => namespace,
+            None => return false,
+        };
+        let segments: Vec<&str> = fqdn.split('.').collect();
+        self.nested_under(namespace, &segments)
+    }
+
+    /// Whether `parts` match the start of `segments` with at least one segment left over.
+    fn nested_under(&self, parts: &[SearchPart], segments: &[&str]) -> bool {
+        match (parts.split_first(), segments.split_first()) {
+            (None, _) => !segments.is_empty(),
+            (Some((part, rest)), _) if part.spans_segments() => {
+                self.nested_under(rest, segments)
+                    || (!segments.is_empty() && self.nested_under(parts, &segments[1..]))
+            }
+            (Some(_), None) => false,
+            (Some((part, rest)), Some((segment, remaining))) => {
+                self.matches_segment(part, segment) && self.nested_under(rest, remaining)
+            }
+        }
+    }
+
+    /// Whether the search names exactly this fully qualified name, not something in it.
+    fn names(&self, fqdn: &str) -> bool {
+        let segments: Vec<&str> = fqdn.split('.').collect();
+        self.whole_matches(&self.parts, &segments)
+    }
+
+    fn whole_matches(&self, parts: &[SearchPart], segments: &[&str]) -> bool {
+        match (parts.split_first(), segments.split_first()) {
+            (None, None) => true,
+            (Some((part, rest)), _) if part.spans_segments() => {
+                self.whole_matches(rest, segments)
+                    || (!segments.is_empty() && self.whole_matches(parts, &segments[1..]))
+            }
+            (Some((part, rest)), Some((segment, remaining))) => {
+                self.matches_segment(part, segment) && self.whole_matches(rest, remaining)
+            }
+            _ => false,
+        }
+    }
+
+    fn partial_namespace(&self, symbol: &str) -> bool {
+        // We will need to break apart the symbol based on "." then looping through, look at the
+        // same index, and if it matches continue if it doesn't then return false.
+        let segments: Vec<&str> = symbol.split(".").collect();
+        self.prefix_matches(&self.parts, &segments)
+    }
+
+    fn match_namespace(&self, symbol: &str) -> bool {
+        // Because we can assume that the last part here is a '*' right now, anything past
+        // that should match.
+        let segments: Vec<&str> = symbol.split(".").collect();
+        self.prefix_matches(&self.parts, &segments)
+    }
+
+    /// Whether `parts` and `segments` agree for as long as both have one, a `**` part stands
+    /// for zero or more segments.
+    fn prefix_matches(&self, parts: &[SearchPart], segments: &[&str]) -> bool {
+        match (parts.split_first(), segments.split_first()) {
+            (None, _) | (_, None) => true,
+            (Some((part, rest)), Some((_, remaining))) if part.spans_segments() => {
+                self.prefix_matches(rest, segments) || self.prefix_matches(parts, remaining)
+            }
+            (Some((part, rest)), Some((segment, remaining))) => {
+                self.matches_segment(part, segment) && self.prefix_matches(rest, remaining)
+            }
+        }
+    }
+
+    fn matches_segment(&self, part: &SearchPart, segment: &str) -> bool {
+        if part.case_insensitive && part.regex.is_none() {
+            return part.part.to_lowercase() == segment.to_lowercase();
+        }
+        part.matches(segment.to_string())
+    }
+
+    // fn import_match
+    //Namespace Match
+    //Part Match
+    //Regex Match
+    //???
+}
+
+/// What `validate_pattern` learned about a pattern without touching a project.
+#[derive(Debug)]
+pub struct PatternReport {
+    pub parts: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+impl PatternReport {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl std::fmt::Display for PatternReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, part) in self.parts.iter().enumerate() {
+            let kind = if part.contains('*') {
+                "wildcard"
+            } else {
+                "literal"
+            };
+            writeln!(f, "part {}: {:?} ({})", i, part, kind)?;
+        }
+        if self.is_valid() {
+            return writeln!(f, "pattern is supported");
+        }
+        self.problems
+            .iter()
+            .try_for_each(|problem| writeln!(f, "error: {}", problem))
+    }
+}
+
+/// Parse a rule pattern the same way a query would and report the parts along with anything
+/// the query engine can not handle, so rule authors find out before running an analysis.
+pub fn validate_pattern(pattern: &str) -> PatternReport {
+    let mut problems = vec![];
+    let (name, signature) = split_signature(pattern).unwrap_or_else(|e| {
+        problems.push(e.to_string());
+        (pattern.to_string(), None)
+    });
+    let mut parts: Vec<String> = name.split(".").map(|p| p.to_string()).collect();
+    if parts.iter().any(|p| p.is_empty()) {
+        problems.push("pattern has an empty segment".to_string());
+    }
+    if signature.is_some() {
+        if parts.len() < 2 {
+            problems.push(
+                "method signatures need the type of the method, `Type.Method(...)`".to_string(),
+            );
+        }
+        if let Some(method) = parts.last_mut() {
+            method.push_str(&pattern[name.len()..]);
+        }
+    }
+    if pattern.contains('<') || pattern.contains('>') {
+        problems.push("generic type arguments are not supported".to_string());
+    }
+    match Search::create_search(name, false) {
+        Err(e) => problems.push(format!("unable to parse pattern: {}", e)),
+        Result::Ok(search) => {
+            if signature.is_none() && !search.all_references_search() {
+                problems.push(
+                    "only namespace searches ending in \".*\" are evaluated, this pattern will not match"
+                        .to_string(),
+                );
+            }
+        }
+    }
+    PatternReport { parts, problems }
+}
+
+/// The parameters a pattern like `Demo.Api.Send(string,int)` gives for the method it names.
+#[derive(Debug, PartialEq)]
+enum Signature {
+    /// `(*)`, any overload.
+    Any,
+    /// The parameter types, as written, of one overload. `()` takes none.
+    Parameters(Vec<String>),
+}
+
+/// Splits the signature off a pattern naming a method, the pattern is returned as is when it
+/// has none.
+fn split_signature(pattern: &str) -> Result<(String, Option<Signature>), Error> {
+    let malformed = || anyhow!("method signatures are written `Type.Method(type, ...)`");
+    let Some((name, parameters)) = pattern.split_once('(') else {
+        if pattern.contains(')') {
+            return Err(malformed());
+        }
+        return Ok((pattern.to_string(), None));
+    };
+    let parameters = parameters
+        .strip_suffix(')')
+        .filter(|parameters| !parameters.contains(['(', ')']))
+        .ok_or_else(malformed)?;
+    let signature = match parameters.trim() {
+        "*" => Signature::Any,
+        "" => Signature::Parameters(vec![]),
+        parameters => Signature::Parameters(
+            parameters
+                .split(',')
+                .map(|parameter| parameter.trim().to_string())
+                .collect(),
+        ),
+    };
+    Ok((name.to_string(), Some(signature)))
+}
+
+/// The regex for a part with wildcards. Only `*` is special, everything else is matched as
+/// written, and the whole name has to match so `Get*` matches `GetValue` but not
+/// `ForgetValue`.
+fn wildcard_regex(part: &str, case_insensitive: bool) -> Result<Regex, Error> {
+    let pattern = part
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<String>>()
+        .join("(.*)");
+    Ok(RegexBuilder::new(&format!("^{}$", pattern))
+        .case_insensitive(case_insensitive)
+        .build()?)
+}
+
+impl SearchPart {
+    /// A `**` part spans any number of dotted segments rather than a single one.
+    fn spans_segments(&self) -> bool {
+        self.part == "**"
+    }
+
+    fn matches(&self, match_string: String) -> bool {
         match &self.regex {
             None => self.part == match_string,
             Some(r) => r.is_match(match_string.as_str()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tree_sitter_stack_graphs::NoCancellation;
+
+    use super::*;
+    use crate::c_sharp_graph::loader::{add_dir_to_graph, init_stack_graph, IndexOptions};
+    use crate::test_support::{language_config, TestDir};
+
+    #[test]
+    fn namespace_index_selects_the_same_files_as_a_scan() {
+        let dir = TestDir::new("namespace-index");
+        dir.write(
+            "Api.cs",
+            "namespace Demo.Indexed { public class Api { public void Call() { } } }",
+        );
+        dir.write(
+            "User.cs",
+            "using Demo.Indexed;\nnamespace Demo.Users { class User { void M() { Api.Call(); } } }",
+        );
+        dir.write(
+            "Other.cs",
+            "using Other.Library;\nnamespace Other.Things { class Thing { void M() { } } }",
+        );
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let index = NamespaceIndex::new(&graph);
+
+        for pattern in ["Demo.Indexed.*", "Demo.*", "*.Things.*"] {
+            let search = Search::create_search(pattern.to_string(), false).unwrap();
+            let matches = |kind: NamespaceKind, symbol: &str| match kind {
+                NamespaceKind::Import => search.partial_namespace(symbol),
+                NamespaceKind::Declaration => search.match_namespace(symbol),
+            };
+
+            let mut inspected = 0;
+            let mut indexed: Vec<Handle<File>> = vec![];
+            for candidate in candidates(&index.by_root, &search) {
+                inspected += 1;
+                if matches(candidate.kind, &candidate.symbol) {
+                    indexed.push(candidate.file);
+                }
+            }
+
+            let mut scanned: Vec<Handle<File>> = vec![];
+            for node_handle in graph.iter_nodes() {
+                let node = &graph[node_handle];
+                let (Some(file), Some(symbol)) = (node.file(), node.symbol()) else {
+                    continue;
+                };
+                let kind = match graph
+                    .source_info(node_handle)
+                    .and_then(|s| s.syntax_type.into_option())
+                    .map(|t| &graph[t])
+                {
+                    Some("import") => NamespaceKind::Import,
+                    Some("namespace-declaration") => NamespaceKind::Declaration,
+                    _ => continue,
+                };
+                if matches(kind, &graph[symbol]) {
+                    scanned.push(file);
+                }
+            }
+
+            indexed.sort();
+            indexed.dedup();
+            scanned.sort();
+            scanned.dedup();
+            assert!(!indexed.is_empty(), "{}", pattern);
+            assert_eq!(indexed, scanned, "{}", pattern);
+            assert!(inspected < graph.iter_nodes().count(), "{}", pattern);
+        }
+        // A plain first segment only looks at the namespaces under it.
+        let search = Search::create_search("Demo.*".to_string(), false).unwrap();
+        assert_eq!(candidates(&index.by_root, &search).count(), 3);
+    }
+
+    #[test]
+    fn starting_nodes_are_computed_once_per_prefix() {
+        let dir = TestDir::new("starting-nodes");
+        dir.write(
+            "Page.cs",
+            "namespace System.Web.Demo { public class Page { public void Render() { } } }",
+        );
+        dir.write("Home.cs", "using System.Web.Demo;\nnamespace Site.Pages { class Home { void M() { Page.Render(); } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+
+        let first = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("System.Web.*".to_string())
+        .unwrap();
+        let system_web = vec![("System".to_string(), false), ("Web".to_string(), false)];
+        let cached = index.starting_nodes[&system_web].clone();
+        let second = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("System.Web.*".to_string())
+        .unwrap();
+
+        assert!(!first.is_empty());
+        assert_eq!(first.len(), second.len());
+        assert_eq!(index.starting_nodes.len(), 1);
+        assert!(Arc::ptr_eq(&cached, &index.starting_nodes[&system_web]));
+    }
+
+    #[test]
+    fn results_keep_their_location_when_the_database_is_missing_spans() {
+        let dir = TestDir::new("missing-spans");
+        dir.write(
+            "Page.cs",
+            "namespace System.Web.Demo { public class Page { public void Render() { } } }",
+        );
+        dir.write("Home.cs", "using System.Web.Demo;\nnamespace Site.Pages\n{\n    class Home { void M() { Page.Render(); } }\n}");
+        let lc = language_config();
+        let db_path = dir.join("test.db");
+        init_stack_graph(
+            &dir,
+            &db_path,
+            &lc.source_type_node_info,
+            &lc.language_config,
+            &IndexOptions::default(),
+            &NoCancellation,
+            None,
+        )
+        .unwrap();
+        let mut reader = stack_graphs::storage::SQLiteReader::open(&db_path).unwrap();
+        reader
+            .load_graphs_for_file_or_directory(&dir, &stack_graphs::NoCancellation)
+            .unwrap();
+        let mut graph = StackGraph::new();
+        reader
+            .get()
+            .0
+            .to_serializable()
+            .load_into(&mut graph)
+            .unwrap();
+
+        let locations = |graph: &mut StackGraph| {
+            let mut index = NamespaceIndex::new(graph);
+            let mut locations: Vec<(String, usize, usize, usize, usize)> = Querier::get_query(
+                graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions::default(),
+            )
+            .query("System.Web.*".to_string())
+            .unwrap()
+            .into_iter()
+            .map(|r| {
+                let start = &r.code_location.start_position;
+                let end = &r.code_location.end_position;
+                (
+                    r.file_uri,
+                    start.line,
+                    start.character,
+                    end.line,
+                    end.character,
+                )
+            })
+            .collect();
+            locations.sort();
+            locations
+        };
+        let expected = locations(&mut graph);
+        assert!(
+            expected
+                .iter()
+                .any(|(file, line, ..)| file.ends_with("Home.cs") && *line == 3),
+            "{:?}",
+            expected
+        );
+
+        let nodes: Vec<Handle<Node>> = graph.iter_nodes().collect();
+        for node in nodes {
+            graph.source_info_mut(node).span = Default::default();
+        }
+        assert_eq!(locations(&mut graph), expected);
+    }
+
+    #[test]
+    fn query_orders_results_the_same_across_runs() {
+        let dir = TestDir::new("query-order");
+        dir.write(
+            "Api.cs",
+            "namespace Demo.Ordering { public class Api { public void Call() { } } }",
+        );
+        for i in 0..6 {
+            fs::write(
+                dir.join(format!("User{}.cs", i)),
+                format!(
+                    "using Demo.Ordering;\nnamespace Demo.Users {{ class User{} {{ void M() {{ Api.Call(); }} }} }}",
+                    i
+                ),
+            )
+            .unwrap();
+        }
+        let lc = language_config();
+
+        let run = || {
+            let _ = fs::remove_file(dir.join("test.db"));
+            let graph = dir.index(&IndexOptions::default()).stack_graph;
+            let mut index = NamespaceIndex::new(&graph);
+            let mut query = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions::default(),
+            );
+            query
+                .query("Demo.Ordering.*".to_string())
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.file_uri, r.code_location.start_position.character))
+                .collect::<Vec<_>>()
+        };
+
+        let first = run();
+        assert!(first.len() > 6, "{:?}", first);
+        let mut files: Vec<&String> = first.iter().map(|(f, _)| f).collect();
+        files.dedup();
+        assert!(files.is_sorted(), "{:?}", files);
+        for _ in 0..5 {
+            assert_eq!(run(), first);
+        }
+    }
+
+    #[test]
+    fn query_reports_utf8_and_utf16_columns() {
+        let dir = TestDir::new("query-columns");
+        dir.write(
+            "Api.cs",
+            "namespace Demo.Columns { public class Api { public static void Call() { } } }",
+        );
+        let line =
+            "namespace Demo.Users { class Üser { void M() { /* naïve 😀 */ Api.Call(); } } }";
+        dir.write("User.cs", &format!("using Demo.Columns;\n{}", line));
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let mut query = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        );
+        let results = query.query("Demo.Columns.*".to_string()).unwrap();
+
+        let call = results
+            .iter()
+            .find(|r| r.file_uri.ends_with("User.cs") && r.line_number == 1)
+            .expect("the call is reported");
+        let start = &call.code_location.start_position;
+        assert_eq!(start.character, line.find("Api.Call").unwrap());
+        assert_eq!(
+            start.character_utf16,
+            line[..start.character].encode_utf16().count()
+        );
+        assert_eq!(start.character - start.character_utf16, 4);
+        assert_eq!(
+            call.variables.get("character_utf16"),
+            Some(&Value::from(start.character_utf16))
+        );
+    }
+
+    #[test]
+    fn dependency_definitions_are_reported_only_when_asked() {
+        let dir = TestDir::new("dependency-defs");
+        let source = dir.join("src");
+        let dependency = dir.join("packages/Demo.Lib/lib/Demo.Lib-decompiled");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&dependency).unwrap();
+        fs::write(
+            source.join("User.cs"),
+            "using Demo.Lib;\nnamespace Demo.Users { class User { void M() { Api.Call(); } } }",
+        )
+        .unwrap();
+        fs::write(
+            dependency.join("Api.cs"),
+            "namespace Demo.Lib\n{\n    public class Api\n    {\n        public static void Call() { }\n    }\n}\n",
+        )
+        .unwrap();
+        let lc = language_config();
+        let graph = init_stack_graph(
+            &source,
+            &dir.join("test.db"),
+            &lc.source_type_node_info,
+            &lc.language_config,
+            &IndexOptions::default(),
+            &NoCancellation,
+            None,
+        )
+        .unwrap()
+        .stack_graph;
+        let graph = add_dir_to_graph(
+            &dependency,
+            &lc.dependnecy_type_node_info,
+            &lc.language_config,
+            graph,
+            None,
+            &NoCancellation,
+        )
+        .unwrap()
+        .stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+
+        let mut run = |include_dependency_definitions| {
+            Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    include_dependency_definitions,
+                    ..Default::default()
+                },
+            )
+            .query("Demo.Lib.*".to_string())
+            .unwrap()
+        };
+
+        let without = run(false);
+        assert!(!without.is_empty());
+        assert!(without
+            .iter()
+            .all(|r| r.file_uri.ends_with("User.cs") && !r.is_dependency_incident));
+
+        let with = run(true);
+        let definitions: Vec<&ResultNode> =
+            with.iter().filter(|r| r.is_dependency_incident).collect();
+        assert_eq!(with.len(), without.len() + definitions.len());
+        assert_eq!(
+            definitions
+                .iter()
+                .map(|r| (r.fqdn.as_deref().unwrap(), r.line_number))
+                .collect::<Vec<_>>(),
+            vec![("Demo.Lib.Api", 2), ("Demo.Lib.Api.Call", 4)]
+        );
+        for definition in definitions {
+            assert!(definition.file_uri.ends_with("Demo.Lib-decompiled/Api.cs"));
+            assert_eq!(definition.effort, Some(0));
+            assert_eq!(
+                definition.variables.get("kind"),
+                Some(&Value::from("definition"))
+            );
+        }
+    }
+
+    #[test]
+    fn full_analysis_flags_the_results_in_dependencies() {
+        let dir = TestDir::new("dependency-incidents");
+        let source = dir.join("src");
+        let dependency = dir.join("packages/Demo.Lib/lib/Demo.Lib-decompiled");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(dependency.join("Internal")).unwrap();
+        fs::write(
+            source.join("User.cs"),
+            "using Demo.Lib;\nnamespace Demo.Users { class User { void M() { Client.Send(); } } }",
+        )
+        .unwrap();
+        // Only the decompiled dll defines and uses the internal helper.
+        fs::write(
+            dependency.join("Client.cs"),
+            "using Demo.Lib.Internal;\nnamespace Demo.Lib { public class Client { public static void Send() { Helper.Run(); } } }",
+        )
+        .unwrap();
+        fs::write(
+            dependency.join("Internal/Helper.cs"),
+            "namespace Demo.Lib.Internal { class Helper { public static void Run() { } } }",
+        )
+        .unwrap();
+        let lc = language_config();
+        let graph = init_stack_graph(
+            &source,
+            &dir.join("test.db"),
+            &lc.source_type_node_info,
+            &lc.language_config,
+            &IndexOptions::default(),
+            &NoCancellation,
+            None,
+        )
+        .unwrap()
+        .stack_graph;
+        let graph = add_dir_to_graph(
+            &dependency,
+            &lc.dependnecy_type_node_info,
+            &lc.language_config,
+            graph,
+            None,
+            &NoCancellation,
+        )
+        .unwrap()
+        .stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+
+        // Full analysis searches with the dependency source type.
+        let results = Querier::get_query(
+            &graph,
+            &lc.dependnecy_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("Demo.Lib.Internal.*".to_string())
+        .unwrap();
+
+        assert!(results
+            .iter()
+            .any(|r| r.file_uri.ends_with("Demo.Lib-decompiled/Client.cs")
+                && r.variables.get("access_kind") == Some(&Value::from("read"))));
+        for result in results.iter() {
+            assert_eq!(
+                result.is_dependency_incident,
+                result.file_uri.contains("Demo.Lib-decompiled/"),
+                "{:?}",
+                result
+            );
+            // Where the file is in the decompiled dll.
+            assert_eq!(
+                result
+                    .variables
+                    .get("dependency_relative_path")
+                    .and_then(Value::as_str),
+                result
+                    .file_uri
+                    .split_once("Demo.Lib-decompiled/")
+                    .map(|(_, path)| path),
+                "{:?}",
+                result
+            );
+        }
+        assert!(results
+            .iter()
+            .any(|r| r.variables.get("dependency_relative_path")
+                == Some(&Value::from("Internal/Helper.cs"))));
+    }
+
+    #[test]
+    fn comments_and_string_literals_are_matched_only_when_asked() {
+        let dir = TestDir::new("comment-matches");
+        dir.write(
+            "Api.cs",
+            "namespace Demo.Text { public class Api { public static void Call() { } } }",
+        );
+        dir.write(
+            "User.cs",
+            "using Demo.Text;
+namespace Demo.Users { class User { string M() {
+// Api.Call();
+return \"Api.Call\"; } } }",
+        );
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+
+        let mut run = |include_comments, include_string_literals| {
+            let results = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    include_comments,
+                    include_string_literals,
+                    ..Default::default()
+                },
+            )
+            .query("Demo.Text.*".to_string())
+            .unwrap();
+            results
+                .into_iter()
+                .filter(|r| r.file_uri.ends_with("User.cs"))
+                .map(|r| {
+                    (
+                        r.line_number,
+                        r.variables.get("kind").cloned(),
+                        r.fqdn.unwrap_or_default(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(false, false), vec![]);
+        assert_eq!(
+            run(true, false),
+            vec![(
+                2,
+                Some(Value::from("comment")),
+                "Demo.Text.Api.Call".to_string()
+            )]
+        );
+        assert_eq!(
+            run(false, true),
+            vec![(
+                3,
+                Some(Value::from("string_literal")),
+                "Demo.Text.Api.Call".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn object_initializer_members_are_matched_on_the_created_type() {
+        let dir = TestDir::new("initializers");
+        dir.write("Options.cs", "namespace Demo.Init { public class Options { public string Name { get; set; } } public class Client { public string Name { get; set; } } }");
+        dir.write("User.cs", "using Demo.Init;\nnamespace Demo.Users { class User { void M() {\nvar o = new Options { Name = \"a\" };\nvar c = new Demo.Init.Client { Name = \"b\" }; } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let results = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("Demo.Init.*".to_string())
+        .unwrap();
+
+        let members: Vec<(usize, &str, Option<&Value>)> = results
+            .iter()
+            .filter(|r| r.file_uri.ends_with("User.cs"))
+            .filter(|r| r.fqdn.as_deref().is_some_and(|f| f.ends_with(".Name")))
+            .map(|r| {
+                (
+                    r.line_number,
+                    r.fqdn.as_deref().unwrap(),
+                    r.variables.get("access_kind"),
+                )
+            })
+            .collect();
+        let write = Value::from("write");
+        assert_eq!(
+            members,
+            vec![
+                (2, "Demo.Init.Options.Name", Some(&write)),
+                (3, "Demo.Init.Client.Name", Some(&write))
+            ]
+        );
+    }
+
+    #[test]
+    fn receivers_of_called_and_assigned_members_are_read() {
+        let dir = TestDir::new("receiver-access");
+        dir.write("Settings.cs", "namespace Demo.Access { public class Settings { public static int Count; public int Value; public static Settings Open() { return null; } public void Reload() { } } }");
+        dir.write("User.cs", "using Demo.Access;\nnamespace Demo.Users { class User { void M() {\nSettings.Count = 1;\nSettings.Open().Value = 2;\nSettings.Open().Reload(); } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let results = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("Demo.Access.*".to_string())
+        .unwrap();
+
+        let mut accesses: Vec<(usize, &str, &str)> = results
+            .iter()
+            .filter(|r| r.file_uri.ends_with("User.cs"))
+            .map(|r| {
+                (
+                    r.line_number,
+                    r.fqdn.as_deref().unwrap_or_default(),
+                    r.variables["access_kind"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        accesses.sort();
+        accesses.dedup();
+        assert_eq!(
+            accesses,
+            vec![
+                (2, "Demo.Access.Settings", "read"),
+                (3, "Demo.Access.Settings", "read"),
+                (3, "Demo.Access.Settings.Value", "write"),
+                (4, "Demo.Access.Settings", "read"),
+                (4, "Demo.Access.Settings.Reload", "invocation"),
+            ]
+        );
+    }
+
+    #[test]
+    fn generic_constraints_match_without_their_type_arguments() {
+        let dir = TestDir::new("generic-constraints");
+        dir.write(
+            "Data.cs",
+            "namespace Demo.Data { public interface IRepo<T> { } public interface IEntity { } }",
+        );
+        dir.write("Store.cs", "using Demo.Data;\nnamespace Demo.Stores { class Store<T>\nwhere T : IRepo<T> { }\nclass Plain<T>\nwhere T : IEntity { } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let results = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions {
+                syntax_type: Some(SyntaxType::Constraint),
+                ..Default::default()
+            },
+        )
+        .query("Demo.Data.*".to_string())
+        .unwrap();
+
+        let mut constraints: Vec<(usize, &str)> = results
+            .iter()
+            .map(|r| (r.line_number, r.fqdn.as_deref().unwrap_or_default()))
+            .collect();
+        constraints.sort();
+        assert_eq!(
+            constraints,
+            vec![(2, "Demo.Data.IRepo"), (4, "Demo.Data.IEntity")]
+        );
+    }
+
+    #[test]
+    fn chained_calls_use_the_return_type_of_the_receivers_method() {
+        let dir = TestDir::new("chained-calls");
+        dir.write("Config.cs", "namespace Demo.Chain { public class Section { public string Value; } public class Region { public string Value; } public class Config { public Section GetSection(string k) { return null; } } public class Cache { public Region GetSection(string k) { return null; } } }");
+        dir.write("User.cs", "using Demo.Chain;\nnamespace Demo.Users { class User { void M() {\nvar a = new Config().GetSection(\"x\").Value;\nvar b = new Cache().GetSection(\"y\").Value; } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let results = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("Demo.Chain.*".to_string())
+        .unwrap();
+
+        let members: Vec<(usize, &str)> = results
+            .iter()
+            .filter(|r| r.file_uri.ends_with("User.cs"))
+            .filter(|r| r.fqdn.as_deref().is_some_and(|f| f.ends_with(".Value")))
+            .map(|r| (r.line_number, r.fqdn.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            members,
+            vec![
+                (2, "Demo.Chain.Section.Value"),
+                (3, "Demo.Chain.Region.Value")
+            ]
+        );
+    }
+
+    #[test]
+    fn members_accessed_through_null_conditionals_are_matched_on_their_receiver() {
+        let dir = TestDir::new("null-conditional");
+        dir.write("Repo.cs", "namespace Demo.Store { public class Repo { public static Repo Open() { return null; } public void Save() { } } }");
+        dir.write("User.cs", "using Demo.Store;\nnamespace Demo.Users { class User { void M() {\nRepo.Open()?.Save();\nvar r = Repo.Open() ?? null; } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let results = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("Demo.Store.*".to_string())
+        .unwrap();
+
+        let saves: Vec<(usize, usize, Option<&Value>)> = results
+            .iter()
+            .filter(|r| r.file_uri.ends_with("User.cs"))
+            .filter(|r| r.fqdn.as_deref() == Some("Demo.Store.Repo.Save"))
+            .map(|r| {
+                (
+                    r.line_number,
+                    r.code_location.start_position.character,
+                    r.variables.get("access_kind"),
+                )
+            })
+            .collect();
+        assert_eq!(saves, vec![(2, 13, Some(&Value::from("invocation")))]);
+    }
+
+    #[test]
+    fn aliased_generic_types_are_matched_by_their_canonical_name() {
+        let dir = TestDir::new("aliased-generics");
+        dir.write(
+            "Box.cs",
+            "namespace Demo.Generic { public class Box<T> { public T Value { get; set; } } }",
+        );
+        dir.write("User.cs", "using B = Demo.Generic.Box<int>;\nnamespace Demo.Users { class User { void M() {\nvar b = new B();\nvar v = new B().Value; } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        for pattern in ["Demo.Generic.*", "Demo.Generic.Box.*"] {
+            let results = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions::default(),
+            )
+            .query(pattern.to_string())
+            .unwrap();
+            let mut used: Vec<(usize, &str)> = results
+                .iter()
+                .filter(|r| r.file_uri.ends_with("User.cs"))
+                .map(|r| (r.line_number, r.fqdn.as_deref().unwrap_or_default()))
+                .collect();
+            used.sort();
+            assert_eq!(
+                used,
+                vec![
+                    (0, "Demo.Generic.Box"),
+                    (2, "Demo.Generic.Box"),
+                    (3, "Demo.Generic.Box"),
+                    (3, "Demo.Generic.Box.Value")
+                ],
+                "{}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn location_only_matches_names_declared_or_written_as_asked() {
+        let dir = TestDir::new("symbol-kinds");
+        dir.write("Printer.cs", "namespace Demo.Kinds { public class Report { } public class Printer { public void Report() { } } }");
+        dir.write("User.cs", "using Demo.Kinds;\nnamespace Demo.Users { class User : Printer { void M() {\nReport();\nvar r = new Report(); } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        for (location, expected) in [
+            (
+                Some(SymbolKind::Method),
+                vec![(2, "Demo.Kinds.Printer.Report")],
+            ),
+            (Some(SymbolKind::Type), vec![(3, "Demo.Kinds.Report")]),
+            (Some(SymbolKind::Field), vec![]),
+        ] {
+            let results = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    location,
+                    ..Default::default()
+                },
+            )
+            .query("Demo.Kinds.*".to_string())
+            .unwrap();
+            let used: Vec<(usize, &str)> = results
+                .iter()
+                .filter(|r| r.file_uri.ends_with("User.cs"))
+                .map(|r| (r.line_number, r.fqdn.as_deref().unwrap_or_default()))
+                .collect();
+            assert_eq!(used, expected, "{:?}", location);
+        }
+        assert!("Method".parse::<SymbolKind>().is_ok());
+        assert!("constructor".parse::<SymbolKind>().is_err());
+
+        for (syntax_type, expected) in [
+            (SyntaxType::Import, vec![("User.cs", 0, "Demo.Kinds")]),
+            (
+                SyntaxType::ClassDef,
+                vec![
+                    ("Printer.cs", 0, "Demo.Kinds.Printer"),
+                    ("Printer.cs", 0, "Demo.Kinds.Report"),
+                    ("User.cs", 1, "Demo.Users.User"),
+                ],
+            ),
+            (
+                SyntaxType::MethodName,
+                vec![
+                    ("Printer.cs", 0, "Demo.Kinds.Printer.Report"),
+                    ("User.cs", 1, "Demo.Users.User.M"),
+                ],
+            ),
+        ] {
+            let results = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    syntax_type: Some(syntax_type),
+                    ..Default::default()
+                },
+            )
+            .query("Demo.*".to_string())
+            .unwrap();
+            let mut matched: Vec<(&str, usize, &str)> = results
+                .iter()
+                .map(|r| {
+                    (
+                        r.file_uri.rsplit('/').next().unwrap(),
+                        r.line_number,
+                        r.fqdn.as_deref().unwrap_or_default(),
+                    )
+                })
+                .collect();
+            matched.sort();
+            assert_eq!(matched, expected, "{:?}", syntax_type);
+        }
+        assert_eq!(
+            "class-def".parse::<MatchLocation>().unwrap(),
+            MatchLocation::Syntax(SyntaxType::ClassDef)
+        );
+        assert_eq!(
+            "field".parse::<MatchLocation>().unwrap(),
+            MatchLocation::Kind(SymbolKind::Field)
+        );
+        assert!("using".parse::<MatchLocation>().is_err());
+    }
+
+    #[test]
+    fn file_paths_limit_the_files_searched() {
+        let dir = TestDir::new("file-paths");
+        fs::create_dir_all(dir.join("Controllers")).unwrap();
+        fs::create_dir_all(dir.join("Models")).unwrap();
+        dir.write(
+            "Api.cs",
+            "namespace Demo.Paths { public class Api { public static void Call() { } } }",
+        );
+        let user =
+            "using Demo.Paths;\nnamespace Demo.Users { class User { void M() { Api.Call(); } } }";
+        dir.write("Controllers/HomeController.cs", user);
+        dir.write("Models/Order.cs", user);
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let mut searched = |file_paths: Option<&[&str]>| {
+            let file_paths = file_paths.map(|paths| {
+                FilePaths::new(&paths.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap()
+            });
+            let mut files: Vec<String> = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    file_paths,
+                    ..Default::default()
+                },
+            )
+            .query("Demo.Paths.*".to_string())
+            .unwrap()
+            .into_iter()
+            .map(|r| r.file_uri.rsplit('/').next().unwrap().to_string())
+            .collect();
+            files.dedup();
+            files
+        };
+
+        assert_eq!(
+            searched(None),
+            vec!["Api.cs", "HomeController.cs", "Order.cs"]
+        );
+        assert_eq!(
+            searched(Some(&["Controllers/*.cs"])),
+            vec!["HomeController.cs"]
+        );
+        assert_eq!(
+            searched(Some(&["Models/Order.cs", "Api.cs"])),
+            vec!["Api.cs", "Order.cs"]
+        );
+    }
+
+    #[test]
+    fn file_paths_match_path_suffixes() {
+        let file_paths = |filters: &[&str]| {
+            FilePaths::new(&filters.iter().map(|f| f.to_string()).collect::<Vec<_>>()).unwrap()
+        };
+        let demo = file_paths(&["demo/File.cs"]);
+        assert!(demo.matches("/src/demo/File.cs"));
+        assert!(!demo.matches("/src/demo/Other.cs"));
+        let glob = file_paths(&["src/*/File.cs", "*.Designer.cs"]);
+        assert!(glob.matches("/repo/src/demo/File.cs"));
+        assert!(!glob.matches("/repo/src/demo/nested/File.cs"));
+        assert!(glob.matches("/repo/Forms/Main.Designer.cs"));
+        assert!(!glob.matches("/repo/Forms/MainDesigner.cs"));
+        // Dots and other regex characters are matched literally.
+        assert!(!file_paths(&["File.cs"]).matches("/src/File_cs"));
+        // The path starts at a separator, not within the name of a file or directory.
+        let api = file_paths(&["Api.cs"]);
+        assert!(api.matches("/src/Api.cs"));
+        assert!(api.matches("Api.cs"));
+        assert!(!api.matches("/src/MyApi.cs"));
+        let controllers = file_paths(&["Controllers/*.cs"]);
+        assert!(controllers.matches("/src/Controllers/HomeController.cs"));
+        assert!(!controllers.matches("/src/AdminControllers/HomeController.cs"));
+        assert!(file_paths(&["/src/Api.cs"]).matches("/src/Api.cs"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn file_paths_are_case_sensitive() {
+        let file_paths = FilePaths::new(&["/Src/Demo/File.cs".to_string()]).unwrap();
+        assert!(!file_paths.matches("/src/demo/File.cs"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn file_paths_ignore_case_on_windows() {
+        let file_paths = FilePaths::new(&["C:/Src/Demo/File.cs".to_string()]).unwrap();
+        assert!(file_paths.matches("c:\\src\\demo\\File.cs"));
+        let file_paths = FilePaths::new(&["c:\\src\\demo\\file.cs".to_string()]).unwrap();
+        assert!(file_paths.matches("C:/Src/Demo/File.cs"));
+    }
+
+    #[test]
+    fn files_of_a_namespace_in_different_directories_resolve_each_other() {
+        let dir = TestDir::new("root-path");
+        fs::create_dir_all(dir.join("Core/Api")).unwrap();
+        fs::create_dir_all(dir.join("Web")).unwrap();
+        dir.write(
+            "Core/Api/Api.cs",
+            "namespace Demo.Shared { public class Api { public static void Call() { } } }",
+        );
+        dir.write(
+            "Web/User.cs",
+            "namespace Demo.Shared { class User : Api { void M() {\nCall(); } } }",
+        );
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let results = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions::default(),
+        )
+        .query("Demo.Shared.*".to_string())
+        .unwrap();
+        let used: Vec<(usize, &str)> = results
+            .iter()
+            .filter(|r| r.file_uri.ends_with("Web/User.cs"))
+            .map(|r| (r.line_number, r.fqdn.as_deref().unwrap_or_default()))
+            .collect();
+        assert!(used.contains(&(1, "Demo.Shared.Api.Call")), "{:?}", used);
+    }
+
+    #[test]
+    fn namespace_case_insensitive_ignores_namespace_casing_only() {
+        let dir = TestDir::new("namespace-casing");
+        dir.write(
+            "Api.cs",
+            "namespace Legacy.WEB { public class Api { public static void Send() { } } }",
+        );
+        dir.write("User.cs", "using legacy.Web;\nnamespace Demo.Users { class User : Api { void M() {\nSend();\nsend(); } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        for (namespace_case_insensitive, expected) in
+            [(false, vec![]), (true, vec![(2, "Legacy.WEB.Api.Send")])]
+        {
+            let results = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    namespace_case_insensitive,
+                    ..Default::default()
+                },
+            )
+            .query("Legacy.Web.*".to_string())
+            .unwrap();
+            let used: Vec<(usize, &str)> = results
+                .iter()
+                .filter(|r| r.file_uri.ends_with("User.cs"))
+                .map(|r| (r.line_number, r.fqdn.as_deref().unwrap_or_default()))
+                .collect();
+            assert_eq!(used, expected, "{}", namespace_case_insensitive);
+        }
+        // The type and member after the namespace are still matched exactly.
+        for (pattern, expected) in [
+            ("legacy.web.Api.Send(*)", vec![(2, "Legacy.WEB.Api.Send")]),
+            ("Legacy.Web.Api.SEND(*)", vec![]),
+            ("Legacy.Web.API.Send(*)", vec![]),
+        ] {
+            let results = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    namespace_case_insensitive: true,
+                    ..Default::default()
+                },
+            )
+            .query(pattern.to_string())
+            .unwrap();
+            let used: Vec<(usize, &str)> = results
+                .iter()
+                .filter(|r| r.file_uri.ends_with("User.cs"))
+                .map(|r| (r.line_number, r.fqdn.as_deref().unwrap_or_default()))
+                .collect();
+            assert_eq!(used, expected, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn starting_nodes_are_not_shared_by_searches_ignoring_case_differently() {
+        let dir = TestDir::new("prefix-casing");
+        dir.write(
+            "Page.cs",
+            "namespace system.WEB { public class Page { public static void Go() { } } }",
+        );
+        dir.write(
+            "User.cs",
+            "using system.WEB;\nnamespace Demo.Users { class User { void M() {\nPage.Go(); } } }",
+        );
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        // `Web` is a namespace segment of the pattern, but the class of the symbol, so only
+        // the pattern ignores its case.
+        let pattern = QueryOptions {
+            namespace_case_insensitive: true,
+            ..Default::default()
+        };
+        let symbol = QueryOptions {
+            symbol: Some(SymbolPattern {
+                namespace: "System".to_string(),
+                class: Some("Web".to_string()),
+                method: None,
+            }),
+            ..pattern.clone()
+        };
+        let used = |index: &mut NamespaceIndex, options: &QueryOptions| -> Vec<String> {
+            Querier::get_query(&graph, &lc.source_type_node_info, index, options.clone())
+                .query("System.Web.*".to_string())
+                .unwrap()
+                .iter()
+                .filter(|r| r.file_uri.ends_with("User.cs"))
+                .filter_map(|r| r.fqdn.clone())
+                .collect()
+        };
+        let alone = |options: &QueryOptions| used(&mut NamespaceIndex::new(&graph), options);
+        assert!(!alone(&pattern).is_empty());
+        assert!(alone(&symbol).is_empty());
+        for order in [[&pattern, &symbol], [&symbol, &pattern]] {
+            let mut index = NamespaceIndex::new(&graph);
+            for options in order {
+                assert_eq!(used(&mut index, options), alone(options), "{:?}", options);
+            }
+        }
+    }
+
+    #[test]
+    fn query_timeout_returns_flagged_partial_results() {
+        let dir = TestDir::new("query-timeout");
+        dir.write(
+            "Api.cs",
+            "namespace Demo.Slow { public class Api { public static void Call() { } } }",
+        );
+        for i in 0..300 {
+            fs::write(
+                dir.join(format!("User{}.cs", i)),
+                format!(
+                    "using Demo.Slow;\nnamespace Demo.Users {{ class User{} {{ void M() {{ Api.Call(); Api.Call(); Api.Call(); }} }} }}",
+                    i
+                ),
+            )
+            .unwrap();
+        }
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let mut query = |timeout: Option<Duration>| {
+            Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    timeout,
+                    ..Default::default()
+                },
+            )
+            .query("Demo.Slow.*".to_string())
+            .unwrap()
+        };
+        let truncated = |r: &ResultNode| r.variables.get("truncated") == Some(&Value::from(true));
+
+        let start = std::time::Instant::now();
+        let all = query(None);
+        let elapsed = start.elapsed();
+        assert!(!all.iter().any(truncated));
+
+        // Somewhere short of the time the whole search takes it is cut off part way.
+        let partial = (1..10)
+            .map(|tenths| query(Some(elapsed * tenths / 10)))
+            .find(|results| !results.is_empty() && results.len() < all.len())
+            .expect("no timeout returned partial results");
+        assert!(partial.iter().all(truncated));
+        assert!(query(Some(Duration::ZERO)).is_empty());
+    }
+
+    #[test]
+    fn validate_pattern_reports_supported_patterns() {
+        for pattern in ["System.Web.Mvc.*", "*.Web.*", "System.Config*.*"] {
+            let report = validate_pattern(pattern);
+            assert!(report.is_valid(), "{}: {:?}", pattern, report.problems);
+        }
+        assert_eq!(
+            validate_pattern("System.Web.*").parts,
+            vec!["System", "Web", "*"]
+        );
+    }
+
+    #[test]
+    fn validate_pattern_reports_unsupported_patterns() {
+        let cases = [
+            ("System.Web.Mvc.Controller", "only namespace searches"),
+            ("System..Web.*", "empty segment"),
+            ("System.Web.Controller.View(*", "method signatures"),
+            ("View(*)", "method signatures need the type"),
+            (
+                "System.Collections.Generic.List<int>.*",
+                "generic type arguments",
+            ),
+        ];
+        for (pattern, expected) in cases {
+            let report = validate_pattern(pattern);
+            assert!(
+                report.problems.iter().any(|p| p.contains(expected)),
+                "{}: {:?}",
+                pattern,
+                report.problems
+            );
+        }
+    }
+
+    #[test]
+    fn wildcard_parts_match_the_whole_name() {
+        let part = |pattern: &str| {
+            Search::create_search(pattern.to_string(), false)
+                .unwrap()
+                .parts
+                .remove(0)
+        };
+        let prefix = part("Get*");
+        assert!(prefix.matches("Get".to_string()));
+        assert!(prefix.matches("GetThing".to_string()));
+        assert!(!prefix.matches("ForgetThing".to_string()));
+
+        let suffix = part("*Thing");
+        assert!(suffix.matches("GetThing".to_string()));
+        assert!(!suffix.matches("GetThings".to_string()));
+
+        let infix = part("Get*Thing");
+        assert!(infix.matches("GetOldThing".to_string()));
+        assert!(!infix.matches("ForgetOldThing".to_string()));
+        assert!(!infix.matches("GetOldThingX".to_string()));
+
+        let any = part("*");
+        assert!(any.matches(String::new()));
+        assert!(any.matches("ForgetThing".to_string()));
+    }
+
+    #[test]
+    fn wildcard_parts_match_other_characters_literally() {
+        for (part, name, matches) in [
+            ("Get+Set*", "Get+SetValue", true),
+            ("Get+Set*", "GetttSetValue", false),
+            ("Get(*)", "Get(int)", true),
+            ("Get(*)", "Getint", false),
+            ("*$", "Value$", true),
+            ("*$", "Value", false),
+            ("a.b*", "a.bc", true),
+            ("a.b*", "axbc", false),
+        ] {
+            let regex = wildcard_regex(part, false).unwrap();
+            assert_eq!(regex.is_match(name), matches, "{} {}", part, name);
+        }
+        assert!(Search::create_search("Demo.Get+Set*".to_string(), false).is_ok());
+    }
+
+    #[test]
+    fn declared_under_requires_a_nested_name() {
+        let search = Search::create_search("System.Security.Crypto*.*".to_string(), false).unwrap();
+        assert!(search.declared_under("System.Security.Cryptography.Aes"));
+        assert!(search.declared_under("System.Security.Cryptography.X509.Store.Open"));
+        assert!(!search.declared_under("System.Security.Cryptography"));
+        assert!(!search.declared_under("System.Security.Policy"));
+    }
+
+    #[test]
+    fn spanning_wildcard_matches_any_number_of_segments() {
+        let search = Search::create_search("a.**.C.m".to_string(), false).unwrap();
+        assert!(search.match_namespace("a.b.C"));
+        assert!(search.match_namespace("a.b.c.d.C"));
+        assert!(search.partial_namespace("a.b.c.d.C"));
+        assert!(!search.match_namespace("x.b.C"));
+        assert!(!search.partial_namespace("x.b.C"));
+
+        // A single `*` still stands for exactly one segment.
+        let search = Search::create_search("a.*.C.m".to_string(), false).unwrap();
+        assert!(search.match_namespace("a.b.C"));
+        assert!(!search.match_namespace("a.b.c.d.C"));
+
+        let search = Search::create_search("a.**.C.*".to_string(), false).unwrap();
+        assert!(search.declared_under("a.C.Run"));
+        assert!(search.declared_under("a.b.c.d.C.Run"));
+        assert!(!search.declared_under("a.b.c.d.C"));
+        assert!(!search.declared_under("x.b.C.Run"));
+    }
+
+    #[test]
+    fn parameter_types_pick_the_overload() {
+        let dir = TestDir::new("overloads");
+        dir.write("Api.cs", "namespace Demo.Overloads { public class Api {\npublic void Send(string text) { }\npublic void Send(string text, int retries) { }\npublic void Send(string text, Options options) { } }\npublic class Options { public static Options None; } }");
+        dir.write("User.cs", "using Demo.Overloads;\nnamespace Demo.Users { class User : Api { void M(string text) {\nSend(text);\nSend(\"a\", 3);\nnew Api().Send(\"a\", new Options());\nOptions options = null;\nSend(text, options);\nSend(text, Options.None); } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        for (parameter_types, expected) in [
+            (None, vec![2, 3, 4, 6, 7]),
+            (Some(vec!["string"]), vec![2]),
+            (
+                Some(vec!["string", "Demo.Overloads.Options"]),
+                vec![4, 6, 7],
+            ),
+            (Some(vec!["String", "Int32"]), vec![3, 7]),
+        ] {
+            let results = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions {
+                    location: Some(SymbolKind::Method),
+                    parameter_types: parameter_types
+                        .clone()
+                        .map(|types| types.into_iter().map(str::to_string).collect()),
+                    ..Default::default()
+                },
+            )
+            .query("Demo.Overloads.*".to_string())
+            .unwrap();
+            let lines: Vec<usize> = results
+                .iter()
+                .filter(|r| r.file_uri.ends_with("User.cs"))
+                .map(|r| r.line_number)
+                .collect();
+            assert_eq!(lines, expected, "{:?}", parameter_types);
+        }
+
+        // The same overloads named by the signature in the pattern.
+        for (pattern, expected) in [
+            ("Demo.Overloads.Api.Send(*)", vec![2, 3, 4, 6, 7]),
+            ("Demo.Overloads.Api.Send(string)", vec![2]),
+            ("Demo.Overloads.Api.Send(string, Options)", vec![4, 6, 7]),
+            ("Demo.Overloads.Api.Send()", vec![]),
+            ("Demo.Overloads.Api.Other(*)", vec![]),
+            // Constructors are named after their class and called by `new`.
+            ("Demo.Overloads.Api.Api(*)", vec![4]),
+            ("Demo.Overloads.Options.Options()", vec![4]),
+            ("Demo.Overloads.Options.Options(string)", vec![]),
+        ] {
+            let results = Querier::get_query(
+                &graph,
+                &lc.source_type_node_info,
+                &mut index,
+                QueryOptions::default(),
+            )
+            .query(pattern.to_string())
+            .unwrap();
+            let lines: Vec<usize> = results
+                .iter()
+                .filter(|r| r.file_uri.ends_with("User.cs"))
+                .map(|r| r.line_number)
+                .collect();
+            assert_eq!(lines, expected, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn symbol_patterns_find_what_the_dotted_patterns_find() {
+        let dir = TestDir::new("symbol-patterns");
+        dir.write("Api.cs", "namespace Demo.Symbols { public class Api {\npublic void Send(string text) { }\npublic void Send(string text, int retries) { }\npublic void Receive() { } } }");
+        dir.write("User.cs", "using Demo.Symbols;\nnamespace Demo.Users { class User : Api { void M(string text) {\nSend(text);\nSend(\"a\", 3);\nReceive();\nnew Api().Receive(); } } }");
+        let lc = language_config();
+        let graph = dir.index(&IndexOptions::default()).stack_graph;
+        let mut index = NamespaceIndex::new(&graph);
+        let symbol = |namespace: &str, class: Option<&str>, method: Option<&str>| SymbolPattern {
+            namespace: namespace.to_string(),
+            class: class.map(str::to_string),
+            method: method.map(str::to_string),
+        };
+        for (pattern, symbol) in [
+            ("Demo.Symbols.*", symbol("Demo.Symbols", None, None)),
+            (
+                "Demo.Symbols.Api.*",
+                symbol("Demo.Symbols", Some("Api"), None),
+            ),
+            (
+                "Demo.Symbols.Api.Send(*)",
+                symbol("Demo.Symbols", Some("Api"), Some("Send")),
+            ),
+            (
+                "Demo.Sym*.A*.Rec*(*)",
+                symbol("Demo.Sym*", Some("A*"), Some("Rec*")),
+            ),
+        ] {
+            let mut found = vec![];
+            for options in [
+                QueryOptions::default(),
+                QueryOptions {
+                    symbol: Some(symbol.clone()),
+                    ..Default::default()
+                },
+            ] {
+                let results =
+                    Querier::get_query(&graph, &lc.source_type_node_info, &mut index, options)
+                        .query(pattern.to_string())
+                        .unwrap();
+                let results: Vec<(String, usize, Option<String>)> = results
+                    .into_iter()
+                    .map(|r| (r.file_uri, r.line_number, r.fqdn))
+                    .collect();
+                found.push(results);
+            }
+            assert!(!found[0].is_empty(), "{}", pattern);
+            assert_eq!(found[0], found[1], "{}", pattern);
+        }
+
+        // The method is one part, a dot in it is not a namespace separator.
+        let results = Querier::get_query(
+            &graph,
+            &lc.source_type_node_info,
+            &mut index,
+            QueryOptions {
+                symbol: Some(symbol("Demo", Some("Symbols"), Some("Api.Send"))),
+                ..Default::default()
+            },
+        )
+        .query(String::new())
+        .unwrap();
+        assert!(results.is_empty(), "{:?}", results);
+    }
+
+    #[test]
+    fn split_signature_separates_the_parameters() {
+        assert_eq!(
+            split_signature("Demo.Api.Send(*)").unwrap(),
+            ("Demo.Api.Send".to_string(), Some(Signature::Any))
+        );
+        assert_eq!(
+            split_signature("Demo.Api.Send(int, System.String)").unwrap(),
+            (
+                "Demo.Api.Send".to_string(),
+                Some(Signature::Parameters(vec![
+                    "int".to_string(),
+                    "System.String".to_string()
+                ]))
+            )
+        );
+        assert_eq!(
+            split_signature("Demo.Api.Send()").unwrap().1,
+            Some(Signature::Parameters(vec![]))
+        );
+        assert_eq!(split_signature("Demo.Api.*").unwrap().1, None);
+        for malformed in [
+            "Demo.Api.Send(int",
+            "Demo.Api.Send)",
+            "Demo.Api.Send((int))",
+        ] {
+            assert!(split_signature(malformed).is_err(), "{}", malformed);
+        }
+        assert!(validate_pattern("Demo.Api.Send(string, int)").is_valid());
+    }
+}
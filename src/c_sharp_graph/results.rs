@@ -17,6 +17,14 @@ pub struct ResultNode {
     pub variables: BTreeMap<std::string::String, serde_json::Value>,
     #[serde(rename = "codeLocation")]
     pub code_location: Location,
+    /// Fully qualified name of what matched, when it is known.
+    #[serde(skip)]
+    pub fqdn: Option<String>,
+    #[serde(default)]
+    pub effort: Option<i64>,
+    /// The match is in a file that was indexed as a dependency rather than the source.
+    #[serde(default, rename = "isDependencyIncident")]
+    pub is_dependency_incident: bool,
 }
 
 fn string_to_usize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
@@ -38,7 +46,8 @@ where
     }
 }
 
-fn serde_json_to_prost(json: serde_json::Value) -> prost_types::Value {
+/// The protobuf value of a JSON value, for the variables sent back to the analyzer.
+pub fn serde_json_to_prost(json: serde_json::Value) -> prost_types::Value {
     use prost_types::value::Kind::*;
     use serde_json::Value::*;
     prost_types::Value {
@@ -67,22 +76,22 @@ impl From<ResultNode> for IncidentContext {
         if let Some(prost_types::value::Kind::StructValue(x)) = x.kind {
             IncidentContext {
                 file_uri: val.file_uri.clone(),
-                effort: None,
+                effort: val.effort,
                 code_location: Some(val.code_location.into()),
                 line_number: Some(val.line_number as i64),
                 variables: Some(x),
                 links: vec![],
-                is_dependency_incident: false,
+                is_dependency_incident: val.is_dependency_incident,
             }
         } else {
             IncidentContext {
                 file_uri: val.file_uri.clone(),
-                effort: None,
+                effort: val.effort,
                 code_location: Some(val.code_location.into()),
                 line_number: Some(val.line_number as i64),
                 variables: None,
                 links: vec![],
-                is_dependency_incident: false,
+                is_dependency_incident: val.is_dependency_incident,
             }
         }
     }
@@ -91,8 +100,13 @@ impl From<ResultNode> for IncidentContext {
 #[derive(Debug, Deserialize, Clone)]
 pub struct Position {
     pub line: usize,
+    /// Column in UTF-8 bytes.
     #[serde(default)]
     pub character: usize,
+    /// Column in UTF-16 code units, as LSP based clients count it. Only the start position's is
+    /// sent, as the `character_utf16` variable, the protocol's position has no room for it.
+    #[serde(default)]
+    pub character_utf16: usize,
 }
 
 impl From<Position> for ProtoPosition {
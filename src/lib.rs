@@ -1,3 +1,6 @@
 pub mod analyzer_service;
 pub mod c_sharp_graph;
 pub mod provider;
+
+#[cfg(test)]
+mod test_support;
@@ -1,33 +1,62 @@
-mod analyzer_service;
-mod c_sharp_graph;
 mod pipe_stream;
-mod provider;
 
 use std::{
+    collections::BTreeMap,
     env::temp_dir,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
     path::PathBuf,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use clap::{command, Parser};
+use clap::{Parser, Subcommand};
 use tokio::runtime;
-use tonic::transport::Server;
-use tracing::{debug, info, instrument::WithSubscriber};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tracing::{debug, error, info, instrument::WithSubscriber};
 use tracing_log::LogTracer;
 use tracing_subscriber::EnvFilter;
 
-use crate::analyzer_service::proto;
-use crate::analyzer_service::provider_service_server::ProviderServiceServer;
-use crate::provider::CSharpProvider;
+use c_sharp_analyzer_provider_cli::analyzer_service::proto;
+use c_sharp_analyzer_provider_cli::c_sharp_graph::{database::checkpoint, query::validate_pattern};
+use c_sharp_analyzer_provider_cli::provider::{
+    capabilities_manifest, run_self_test, AnalysisMode, CSharpProvider, Project, Tools,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// TCP port to serve on. Given with `--socket` both are served.
     #[arg(long)]
-    port: Option<usize>,
+    port: Option<u16>,
+    /// Address `--port` is bound to, `0.0.0.0` or `::` to accept connections on every
+    /// interface. IPv6 addresses may be written in brackets, `[::1]`.
+    #[arg(
+        long,
+        visible_alias = "host",
+        value_parser = parse_address,
+        default_value_t = IpAddr::V6(Ipv6Addr::LOCALHOST)
+    )]
+    address: IpAddr,
 
+    /// Unix socket, or named pipe on Windows, to serve on.
     #[arg(long)]
     socket: Option<String>,
+    /// PEM certificate the server presents, along with `--tls-key`. Connections are plaintext
+    /// when not given.
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key of `--tls-cert`.
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// PEM certificate of the authority client certificates have to be signed by. Clients
+    /// without one are refused, for mutual TLS.
+    #[arg(long, requires = "tls_cert")]
+    tls_ca: Option<PathBuf>,
+    /// Permissions of the Unix socket, in octal, e.g. `660` to let the group connect. The
+    /// umask decides when not given.
+    #[arg(long, value_parser = parse_socket_mode)]
+    socket_mode: Option<u32>,
 
     #[arg(long)]
     name: Option<String>,
@@ -35,13 +64,85 @@ struct Args {
     log_file: Option<String>,
     #[command(flatten)]
     verbosity: clap_verbosity_flag::Verbosity,
+    /// Where the index is written. Repeat to add databases indexed ahead of time that are
+    /// merged into the project graph, directories stand for every `.db` file they contain.
+    /// When the first path is a directory the index is written inside it.
+    #[arg(long)]
+    db_path: Vec<PathBuf>,
+    /// Only load the graph from the database `--db-path` points to, indexed ahead of time,
+    /// rather than resolving and decompiling the dependencies. `ilspycmd` and `paket` are not
+    /// needed.
+    #[arg(long)]
+    use_existing_db: bool,
+    /// Do not register the gRPC reflection service.
     #[arg(long)]
-    db_path: Option<PathBuf>,
+    no_reflection: bool,
+    /// Largest gRPC message, in bytes, that is accepted or sent.
+    #[arg(long, default_value_t = CSharpProvider::DEFAULT_MAX_MESSAGE_SIZE)]
+    max_message_size: usize,
+    /// Print the capabilities and the schema of their conditions as JSON and exit.
+    #[arg(long)]
+    dump_capabilities: bool,
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Check a rule pattern against the supported query syntax without loading a project.
+    ValidatePattern { pattern: String },
+    /// Print the resolved name, version and location of every dependency of a project as JSON.
+    DumpDependencies { location: PathBuf },
+    /// Index a small bundled C# sample and run known queries against it to check the install.
+    SelfTest,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    if args.dump_capabilities {
+        println!("{}", capabilities_manifest()?);
+        return Ok(());
+    }
+    if let Some(Commands::ValidatePattern { pattern }) = &args.command {
+        let report = validate_pattern(pattern);
+        print!("{}", report);
+        if !report.is_valid() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Commands::DumpDependencies { location }) = &args.command {
+        // Only reads what a previous resolution locked, so the tools are never run.
+        let project = Project::new(
+            location.clone(),
+            temp_dir().join("c_sharp_provider.db"),
+            AnalysisMode::Full,
+            Tools {
+                ilspy_cmd: PathBuf::new(),
+                paket_cmd: PathBuf::new(),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        );
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        println!("{}", rt.block_on(project.dependencies_json())?);
+        return Ok(());
+    }
+    if let Some(Commands::SelfTest) = &args.command {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let report = rt.block_on(run_self_test());
+        print!("{}", report);
+        if !report.is_success() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let filter = EnvFilter::from_default_env();
     // construct a subscriber that prints formatted traces to stdout
     LogTracer::init_with_filter(tracing_log::log::LevelFilter::Trace)?;
@@ -61,65 +162,200 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .enable_all()
         .build()?;
 
-    let provider = CSharpProvider::new(
-        args.db_path
-            .map_or(temp_dir().join("c_sharp_provider.db"), |x| x),
-    );
-    let service = tonic_reflection::server::Builder::configure()
-        .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
-        .build_v1alpha()
-        .unwrap();
-
-    if args.port.is_some() {
-        let s = format!("[::1]:{}", args.port.unwrap());
-        info!("Using gRPC over HTTP/2 on port {}", s);
-
-        let addr = s.parse()?;
-
-        rt.block_on(async {
-            let _ = Server::builder()
-                .add_service(ProviderServiceServer::new(provider))
-                .add_service(service)
-                .serve(addr)
-                .with_current_subscriber()
-                .await;
-        });
+    let (db_path, extra_db_paths) = expand_db_paths(&args.db_path)?;
+    let mut provider = CSharpProvider::new(db_path);
+    provider.extra_db_paths = extra_db_paths;
+    provider.use_existing_db = args.use_existing_db;
+    let db_path = provider.db_path.clone();
+    let shutdown = provider.shutdown.clone();
+    let provider = provider.into_service(args.max_message_size);
+    let mut server = Server::builder();
+    if let Some(tls) = tls_config(&args)? {
+        server = server.tls_config(tls)?;
+    }
+    let service = if args.no_reflection {
+        info!("reflection service disabled");
+        None
     } else {
-        info!("using uds");
-        #[cfg(not(windows))]
+        match tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
+            .build_v1alpha()
         {
-            debug!("Running on Unix-like OS");
-
-            use tokio::net::UnixListener;
-            use tokio_stream::wrappers::UnixListenerStream;
-            use tracing::debug;
-
-            let uds = UnixListener::bind(args.socket.unwrap())?;
-            let uds_stream = UnixListenerStream::new(uds);
-
-            rt.block_on(async {
-                let _ = Server::builder()
-                    .add_service(ProviderServiceServer::new(provider))
-                    .add_service(service)
-                    .serve_with_incoming(uds_stream)
-                    .with_current_subscriber()
-                    .await;
-            });
-        }
-        #[cfg(target_os = "windows")]
-        {
-            debug!("Using Windows OS");
-            use crate::pipe_stream::get_named_pipe_connection_stream;
-            rt.block_on(async {
-                let _ = Server::builder()
-                    .add_service(ProviderServiceServer::new(provider))
-                    .add_service(service)
-                    .serve_with_incoming(get_named_pipe_connection_stream(args.socket.unwrap()))
-                    .with_current_subscriber()
-                    .await;
-            });
+            Ok(service) => Some(
+                service
+                    .max_decoding_message_size(args.max_message_size)
+                    .max_encoding_message_size(args.max_message_size),
+            ),
+            Err(e) => {
+                error!(
+                    "unable to build reflection service, serving without it: {}",
+                    e
+                );
+                None
+            }
         }
+    };
+
+    if args.port.is_none() && args.socket.is_none() {
+        return Err("give --port, --socket or both to listen on".into());
     }
+    // One router per listener, all serving the same provider.
+    let router = || {
+        server
+            .clone()
+            .add_service(provider.clone())
+            .add_optional_service(service.clone())
+    };
+    rt.block_on(async {
+        // Every listener is told once `stop` is called, they then answer the requests in
+        // flight and return.
+        let (stop, stopping) = watch::channel(());
+        tokio::spawn(async move {
+            shutdown.notified().await;
+            info!("stopping the server");
+            let _ = stop.send(());
+        });
+        let stopped = |mut stopping: watch::Receiver<()>| async move {
+            let _ = stopping.changed().await;
+        };
+        let mut listeners = JoinSet::new();
+        if let Some(port) = args.port {
+            let addr = SocketAddr::new(args.address, port);
+            // Bound up front so an address that can not be used is an error rather than a
+            // server that never listens.
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!("Using gRPC over HTTP/2 on {}", listener.local_addr()?);
+            listeners.spawn(
+                router()
+                    .serve_with_incoming_shutdown(
+                        tokio_stream::wrappers::TcpListenerStream::new(listener),
+                        stopped(stopping.clone()),
+                    )
+                    .with_current_subscriber(),
+            );
+        }
+        if let Some(socket) = args.socket {
+            info!("using uds");
+            #[cfg(not(windows))]
+            {
+                use tokio::net::UnixListener;
+                use tokio_stream::wrappers::UnixListenerStream;
 
+                debug!("Running on Unix-like OS");
+                // Binding registers the socket with the runtime, so it has to happen inside it.
+                let uds = UnixListener::bind(&socket)?;
+                if let Some(mode) = args.socket_mode {
+                    use std::os::unix::fs::PermissionsExt;
+
+                    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(mode))?;
+                    info!("socket {} has mode {:o}", socket, mode);
+                }
+                listeners.spawn(
+                    router()
+                        .serve_with_incoming_shutdown(
+                            UnixListenerStream::new(uds),
+                            stopped(stopping.clone()),
+                        )
+                        .with_current_subscriber(),
+                );
+            }
+            #[cfg(target_os = "windows")]
+            {
+                use crate::pipe_stream::get_named_pipe_connection_stream;
+
+                debug!("Using Windows OS");
+                listeners.spawn(
+                    router()
+                        .serve_with_incoming_shutdown(
+                            get_named_pipe_connection_stream(socket),
+                            stopped(stopping.clone()),
+                        )
+                        .with_current_subscriber(),
+                );
+            }
+        }
+        while let Some(served) = listeners.join_next().await {
+            match served {
+                Ok(Err(e)) => error!("server stopped: {}", e),
+                Err(e) => error!("server stopped: {}", e),
+                Ok(Ok(())) => (),
+            }
+        }
+        Ok::<(), std::io::Error>(())
+    })?;
+
+    if db_path.exists() {
+        if let Err(e) = checkpoint(&db_path) {
+            error!("unable to checkpoint {:?}: {}", db_path, e);
+        }
+    }
     Ok(())
 }
+
+/// The TLS the server is configured with, `None` for plaintext.
+fn tls_config(args: &Args) -> Result<Option<ServerTlsConfig>, String> {
+    let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) else {
+        return Ok(None);
+    };
+    let read = |path: &PathBuf| {
+        std::fs::read(path).map_err(|e| format!("unable to read {}: {}", path.display(), e))
+    };
+    let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(read(cert)?, read(key)?));
+    if let Some(ca) = &args.tls_ca {
+        tls = tls.client_ca_root(Certificate::from_pem(read(ca)?));
+    }
+    info!(
+        "serving over TLS{}",
+        if args.tls_ca.is_some() {
+            " with client certificates"
+        } else {
+            ""
+        }
+    );
+    Ok(Some(tls))
+}
+
+/// Reads an `--address` value, an IPv4 or IPv6 address with or without the brackets of a URL.
+fn parse_address(address: &str) -> Result<IpAddr, String> {
+    let unbracketed = address
+        .strip_prefix('[')
+        .and_then(|a| a.strip_suffix(']'))
+        .unwrap_or(address);
+    unbracketed
+        .parse()
+        .map_err(|_| format!("{} is not an IP address", address))
+}
+
+/// Reads a `--socket-mode` value, always taken as octal like `chmod` does.
+fn parse_socket_mode(mode: &str) -> Result<u32, String> {
+    match u32::from_str_radix(mode.trim_start_matches("0o"), 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("{} is not an octal file mode", mode)),
+    }
+}
+
+/// Splits the `--db-path` values into the database the provider writes to and the extra ones
+/// it only reads.
+fn expand_db_paths(paths: &[PathBuf]) -> std::io::Result<(PathBuf, Vec<PathBuf>)> {
+    const DB_NAME: &str = "c_sharp_provider.db";
+    let db_path = match paths.first() {
+        None => return Ok((temp_dir().join(DB_NAME), vec![])),
+        Some(dir) if dir.is_dir() => dir.join(DB_NAME),
+        Some(path) => path.clone(),
+    };
+    let mut extra_db_paths = vec![];
+    for path in paths.iter() {
+        if !path.is_dir() {
+            extra_db_paths.push(path.clone());
+            continue;
+        }
+        let mut dbs: Vec<PathBuf> = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        dbs.retain(|db| db.extension().is_some_and(|ext| ext == "db"));
+        dbs.sort();
+        extra_db_paths.extend(dbs);
+    }
+    extra_db_paths.retain(|db| *db != db_path);
+    Ok((db_path, extra_db_paths))
+}
@@ -0,0 +1,63 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use tree_sitter_stack_graphs::{CancellationError, CancellationFlag};
+
+/// Cancellation flag that can be cloned into spawned work and tripped from elsewhere, for
+/// example when the gRPC client that started the work goes away.
+#[derive(Clone, Default, Debug)]
+pub struct AtomicCancellationFlag {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl AtomicCancellationFlag {
+    pub fn new() -> AtomicCancellationFlag {
+        AtomicCancellationFlag::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns a guard that cancels the flag when it is dropped. tonic drops the handler
+    /// future when the client disconnects, so holding this in the handler ties the spawned
+    /// work to the lifetime of the request.
+    pub fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop { flag: self.clone() }
+    }
+}
+
+impl CancellationFlag for AtomicCancellationFlag {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        if self.is_cancelled() {
+            return Err(CancellationError(at));
+        }
+        Ok(())
+    }
+}
+
+// The stack graph database and stitcher use their own flavor of the trait.
+impl stack_graphs::CancellationFlag for AtomicCancellationFlag {
+    fn check(&self, at: &'static str) -> Result<(), stack_graphs::CancellationError> {
+        if self.is_cancelled() {
+            return Err(stack_graphs::CancellationError(at));
+        }
+        Ok(())
+    }
+}
+
+pub struct CancelOnDrop {
+    flag: AtomicCancellationFlag,
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.flag.cancel();
+    }
+}
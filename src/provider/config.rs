@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+use prost_types::{value::Kind, Struct, Value};
+use serde::Deserialize;
+use which::which;
+
+use crate::provider::{QueryMode, Tools};
+
+/// The `providerSpecificConfig` of an init request. A key that is not known or a value of the
+/// wrong type is an error rather than silently ignored.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderConfig {
+    /// Path to `ilspycmd`, looked up on the `PATH` and then the `tool_search_paths` when not
+    /// given.
+    pub ilspy_cmd: Option<PathBuf>,
+    /// Path to `paket`, looked up the same way as `ilspy_cmd`.
+    pub paket_cmd: Option<PathBuf>,
+    /// The C# version decompiled code is written in, as ilspycmd's `-lv` takes it, such as
+    /// `CSharp10_0`. Defaults to `CSharp7_3`.
+    pub ilspy_language_version: Option<String>,
+    /// Directories searched for a tool that is not on the `PATH`, in order. Defaults to where
+    /// `dotnet tool install --global` and container images usually put them.
+    pub tool_search_paths: Option<Vec<PathBuf>>,
+    /// Set on every external command, see `Tools::env`.
+    #[serde(default)]
+    pub tool_env: BTreeMap<String, String>,
+    /// See `IndexOptions::include_generated`.
+    #[serde(default)]
+    pub include_generated: bool,
+    /// See `IndexOptions::respect_gitignore`.
+    #[serde(default)]
+    pub respect_gitignore: bool,
+    /// See `IndexOptions::include_namespaces`.
+    #[serde(default)]
+    pub include_namespaces: Vec<String>,
+    /// See `IndexOptions::file_list`, read with `ProviderConfig::file_list`.
+    pub file_list: Option<FileList>,
+    /// See `Project::default_framework`.
+    pub default_framework: Option<String>,
+    /// See `Project::max_decompiled_file_bytes`.
+    pub max_decompiled_file_bytes: Option<u64>,
+    /// See `Project::prefer_source_packages`.
+    #[serde(default)]
+    pub prefer_source_packages: bool,
+    /// See `Project::index_reference_assemblies`.
+    #[serde(default)]
+    pub index_reference_assemblies: bool,
+    /// See `Project::lazy_index`.
+    #[serde(default)]
+    pub lazy_index: bool,
+    /// See `Project::warm_cache`.
+    #[serde(default)]
+    pub warm_cache: bool,
+    /// See `Project::query_mode`, `in_memory` or `db_backed`.
+    #[serde(default)]
+    pub query_mode: QueryMode,
+    /// See `Project::cache_size`, in bytes.
+    pub cache_size: Option<u64>,
+    /// See `Project::mmap_size`, in bytes.
+    pub mmap_size: Option<u64>,
+}
+
+/// The files to index, listed in the config or in a file with one path per line.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum FileList {
+    Paths(Vec<PathBuf>),
+    File(PathBuf),
+}
+
+impl ProviderConfig {
+    pub(crate) const ILSPY_CMD: &str = "ilspy";
+    pub(crate) const PAKET_CMD: &str = "paket";
+
+    pub fn from_struct(config: &Option<Struct>) -> Result<ProviderConfig, Error> {
+        let Some(config) = config else {
+            return Ok(ProviderConfig::default());
+        };
+        serde_json::from_value(struct_to_json(config))
+            .map_err(|e| anyhow!("invalid provider specific config: {}", e))
+    }
+
+    /// The external tools, checking that the configured paths exist.
+    pub fn tools(&self) -> Result<Tools, Error> {
+        let tool = |configured: &Option<PathBuf>, name: &str, key: &str| match configured {
+            Some(path) if path.exists() => Ok(path.clone()),
+            Some(path) => Err(anyhow!("{} {:?} does not exist", key, path)),
+            None => self.find_tool(name),
+        };
+        let ilspy_language_version = match &self.ilspy_language_version {
+            None => Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            Some(version) => Tools::ILSPY_LANGUAGE_VERSIONS
+                .iter()
+                .find(|known| known.eq_ignore_ascii_case(version))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "ilspy_language_version {} is not one of {}",
+                        version,
+                        Tools::ILSPY_LANGUAGE_VERSIONS.join(", ")
+                    )
+                })?,
+        };
+        Ok(Tools {
+            ilspy_cmd: tool(&self.ilspy_cmd, Self::ILSPY_CMD, "ilspy_cmd")?,
+            paket_cmd: tool(&self.paket_cmd, Self::PAKET_CMD, "paket_cmd")?,
+            env: self.tool_env.clone(),
+            ilspy_language_version,
+        })
+    }
+
+    /// Looks the tool `name` up on the `PATH`, falling back to the first of the
+    /// `tool_search_paths` that has it.
+    pub(crate) fn find_tool(&self, name: &str) -> Result<PathBuf, Error> {
+        if let Ok(path) = which(name) {
+            return Ok(path);
+        }
+        let search_paths = self
+            .tool_search_paths
+            .clone()
+            .unwrap_or_else(default_tool_search_paths);
+        search_paths
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| anyhow!("{} not found on PATH or in {:?}", name, search_paths))
+    }
+
+    /// The files of `file_list`, relative paths, and the list file itself, are relative to
+    /// the project `location`. Every listed file has to exist, blank lines are skipped.
+    pub fn file_list(&self, location: &Path) -> Result<Option<Vec<PathBuf>>, Error> {
+        let paths = match &self.file_list {
+            None => return Ok(None),
+            Some(FileList::Paths(paths)) => paths.clone(),
+            Some(FileList::File(list)) => {
+                let list = location.join(list);
+                std::fs::read_to_string(&list)
+                    .map_err(|e| anyhow!("unable to read file_list {:?}: {}", list, e))?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from)
+                    .collect()
+            }
+        };
+        paths
+            .into_iter()
+            .map(|path| {
+                let path = location.join(path);
+                match path.is_file() {
+                    true => Ok(path),
+                    false => Err(anyhow!("file_list: {:?} does not exist", path)),
+                }
+            })
+            .collect::<Result<Vec<PathBuf>, Error>>()
+            .map(Some)
+    }
+}
+
+fn default_tool_search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+    if let Some(home) = std::env::var_os("HOME") {
+        paths.push(Path::new(&home).join(".dotnet/tools"));
+    }
+    paths.push(PathBuf::from("/usr/local/bin"));
+    paths
+}
+
+fn struct_to_json(config: &Struct) -> serde_json::Value {
+    serde_json::Value::Object(
+        config
+            .fields
+            .iter()
+            .map(|(k, v)| (k.clone(), value_to_json(v)))
+            .collect(),
+    )
+}
+
+/// Protobuf only has doubles, whole numbers are turned back into integers so they can be read
+/// into integer fields.
+fn value_to_json(value: &Value) -> serde_json::Value {
+    match &value.kind {
+        None | Some(Kind::NullValue(_)) => serde_json::Value::Null,
+        Some(Kind::BoolValue(b)) => serde_json::Value::from(*b),
+        Some(Kind::NumberValue(n)) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+            serde_json::Value::from(*n as i64)
+        }
+        Some(Kind::NumberValue(n)) => serde_json::Value::from(*n),
+        Some(Kind::StringValue(s)) => serde_json::Value::from(s.clone()),
+        Some(Kind::ListValue(l)) => l.values.iter().map(value_to_json).collect(),
+        Some(Kind::StructValue(s)) => struct_to_json(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    fn config(fields: Vec<(&str, Kind)>) -> Option<Struct> {
+        Some(Struct {
+            fields: fields
+                .into_iter()
+                .map(|(k, kind)| (k.to_string(), Value { kind: Some(kind) }))
+                .collect(),
+        })
+    }
+
+    #[test]
+    fn valid_keys_are_read() {
+        let parsed = ProviderConfig::from_struct(&config(vec![
+            ("ilspy_cmd", Kind::StringValue("/bin/sh".to_string())),
+            ("lazy_index", Kind::BoolValue(true)),
+            ("max_decompiled_file_bytes", Kind::NumberValue(1024.0)),
+            (
+                "tool_env",
+                Kind::StructValue(Struct {
+                    fields: BTreeMap::from([(
+                        "NUGET_PACKAGES".to_string(),
+                        Value {
+                            kind: Some(Kind::StringValue("/cache".to_string())),
+                        },
+                    )]),
+                }),
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(
+            parsed,
+            ProviderConfig {
+                ilspy_cmd: Some(PathBuf::from("/bin/sh")),
+                lazy_index: true,
+                max_decompiled_file_bytes: Some(1024),
+                tool_env: BTreeMap::from([("NUGET_PACKAGES".to_string(), "/cache".to_string())]),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            ProviderConfig::from_struct(&None).unwrap(),
+            ProviderConfig::default()
+        );
+    }
+
+    #[test]
+    fn mistyped_values_are_rejected() {
+        for fields in [
+            vec![("lazy_index", Kind::StringValue("yes".to_string()))],
+            vec![("max_decompiled_file_bytes", Kind::NumberValue(-1.0))],
+            vec![("max_decompiled_file_bytes", Kind::NumberValue(1.5))],
+            vec![("tool_env", Kind::StringValue("A=1".to_string()))],
+        ] {
+            let key = fields[0].0;
+            let err = ProviderConfig::from_struct(&config(fields)).unwrap_err();
+            let err = err.to_string();
+            assert!(
+                err.contains("invalid type") || err.contains("invalid value"),
+                "{}: {}",
+                key,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let err = ProviderConfig::from_struct(&config(vec![("lazy_indx", Kind::BoolValue(true))]))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("unknown field `lazy_indx`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn file_list_is_read_inline_or_from_a_file() {
+        let dir = TestDir::new("file-list-config");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        dir.write("src/A.cs", "");
+        dir.write("B.cs", "");
+        dir.write("changed.txt", "src/A.cs\n\nB.cs\n");
+
+        let listed = vec![dir.join("src/A.cs"), dir.join("B.cs")];
+        let inline = ProviderConfig::from_struct(&config(vec![(
+            "file_list",
+            Kind::ListValue(prost_types::ListValue {
+                values: ["src/A.cs", "B.cs"]
+                    .iter()
+                    .map(|path| Value {
+                        kind: Some(Kind::StringValue(path.to_string())),
+                    })
+                    .collect(),
+            }),
+        )]))
+        .unwrap();
+        assert_eq!(inline.file_list(&dir).unwrap(), Some(listed.clone()));
+        let from_file = ProviderConfig::from_struct(&config(vec![(
+            "file_list",
+            Kind::StringValue("changed.txt".to_string()),
+        )]))
+        .unwrap();
+        assert_eq!(from_file.file_list(&dir).unwrap(), Some(listed));
+        assert_eq!(ProviderConfig::default().file_list(&dir).unwrap(), None);
+
+        let missing = ProviderConfig {
+            file_list: Some(FileList::Paths(vec![PathBuf::from("Gone.cs")])),
+            ..Default::default()
+        };
+        let err = missing.file_list(&dir).unwrap_err();
+        assert!(err.to_string().contains("Gone.cs"), "{}", err);
+    }
+
+    #[test]
+    fn missing_tools_are_reported() {
+        let parsed = ProviderConfig {
+            ilspy_cmd: Some(PathBuf::from("/does/not/exist/ilspycmd")),
+            paket_cmd: Some(PathBuf::from("/bin/sh")),
+            ..Default::default()
+        };
+        let Err(err) = parsed.tools() else {
+            panic!("a missing ilspy_cmd is accepted");
+        };
+        assert!(err.to_string().contains("ilspy_cmd"), "{}", err);
+    }
+
+    #[test]
+    fn ilspy_language_version_is_checked_against_what_ilspy_accepts() {
+        let tools = |version: Option<&str>| {
+            ProviderConfig {
+                ilspy_cmd: Some(PathBuf::from("/bin/sh")),
+                paket_cmd: Some(PathBuf::from("/bin/sh")),
+                ilspy_language_version: version.map(ToString::to_string),
+                ..Default::default()
+            }
+            .tools()
+        };
+        assert_eq!(tools(None).unwrap().ilspy_language_version, "CSharp7_3");
+        assert_eq!(
+            tools(Some("csharp10_0")).unwrap().ilspy_language_version,
+            "CSharp10_0"
+        );
+        let Err(err) = tools(Some("CSharp10")) else {
+            panic!("an unknown language version is accepted");
+        };
+        assert!(err.to_string().contains("CSharp10_0"), "{}", err);
+    }
+
+    #[test]
+    fn tools_not_on_path_are_found_in_the_search_paths() {
+        let dir = TestDir::new("tool-search");
+        let fallback = dir.join("fallback");
+        std::fs::create_dir_all(&fallback).unwrap();
+        let name = "ilspycmd-only-in-fallback";
+        std::fs::write(fallback.join(name), "").unwrap();
+
+        let config = |search_paths: Vec<PathBuf>| ProviderConfig {
+            tool_search_paths: Some(search_paths),
+            ..Default::default()
+        };
+        assert_eq!(
+            config(vec![dir.join("empty"), fallback.clone()])
+                .find_tool(name)
+                .unwrap(),
+            fallback.join(name)
+        );
+        let err = config(vec![dir.join("empty")]).find_tool(name).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+    }
+}
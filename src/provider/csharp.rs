@@ -1,75 +1,816 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use prost_types::{value::Kind, Struct, Value};
+use regex::Regex;
 use serde::Deserialize;
-use tokio::sync::Mutex;
+use serde_json::json;
+use tokio::sync::{Mutex, Notify};
 use tonic::{Request, Response, Status};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+use url::Url;
 use utoipa::{OpenApi, ToSchema};
 
 use crate::c_sharp_graph::find_node::FindNode;
-use crate::provider::AnalysisMode;
+use crate::c_sharp_graph::loader::FileDiagnostic;
+use crate::c_sharp_graph::query::{
+    ArgumentValue, FilePaths, MatchLocation, QueryOptions, SymbolPattern, SyntaxType,
+};
+use crate::c_sharp_graph::results::{serde_json_to_prost, ResultNode};
+use crate::provider::{AnalysisMode, AtomicCancellationFlag, ProviderConfig, Tools};
 use crate::{
     analyzer_service::{
-        provider_service_server::ProviderService, CapabilitiesResponse, Capability, Config,
-        DependencyDagResponse, DependencyResponse, EvaluateRequest, EvaluateResponse,
-        IncidentContext, InitResponse, NotifyFileChangesRequest, NotifyFileChangesResponse,
-        ProviderEvaluateResponse, ServiceRequest,
+        provider_service_server::{ProviderService, ProviderServiceServer},
+        CapabilitiesResponse, Capability, Config, DependencyDagResponse, DependencyResponse,
+        EvaluateRequest, EvaluateResponse, FileDagDep, IncidentContext, InitResponse,
+        NotifyFileChangesRequest, NotifyFileChangesResponse, Position, ProviderEvaluateResponse,
+        ServiceRequest,
     },
     provider::Project,
 };
 
 #[derive(ToSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
 struct ReferenceCondition {
-    pattern: String,
+    /// Dotted pattern to search for, `System.Web.*`. Give either this or `symbol`.
+    pattern: Option<String>,
+    /// The symbol to search for given field by field, for names that are awkward to write as
+    /// a dotted pattern.
+    symbol: Option<SymbolCondition>,
+    /// Only match names declared as this kind, `type`, `method` or `field`, or written as this
+    /// syntax, such as `import`, `class_def`, `method_name` or `member_access`. Any other value
+    /// is logged and does not narrow down the matches.
     location: Option<String>,
-    #[allow(dead_code)]
+    /// Only search the files whose path ends with one of these, whole segments of it, a `*`
+    /// matches within one segment of the path, `Controllers/*.cs`.
+    #[serde(alias = "file_paths")]
+    file_paths: Option<Vec<String>>,
+    /// Skip this many incidents, in the sorted order, before returning any.
+    offset: Option<usize>,
+    /// Return at most this many incidents.
+    limit: Option<usize>,
+    /// Add the types the enclosing class derives from to each incident as `base_types`.
+    #[serde(default)]
+    include_base_types: bool,
+    /// Only keep incidents whose fully qualified name matched at least this many times.
+    #[serde(default)]
+    min_occurrences: Option<usize>,
+    /// Return no incidents, only the number of distinct files matched as the `file_count` of
+    /// the template context, for scans that only ask how many files use an API.
+    #[serde(default)]
+    return_file_count: bool,
+    /// Return a single incident, at the first match, with where every match is as its
+    /// `locations` variable, for rules reported once rather than once per match. `offset` and
+    /// `limit` do not apply.
+    #[serde(default)]
+    aggregate: bool,
+    /// Treat the pattern as a namespace and match every symbol declared anywhere under it.
+    #[serde(default)]
+    subtree: bool,
+    /// Give up after this many milliseconds and return the incidents found so far, marked
+    /// with a `truncated` variable.
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+    /// Add the fully qualified name of the method each incident is in as `enclosing_method`.
+    #[serde(default)]
+    include_enclosing_method: bool,
+    /// Keep only the first incident, by location, of each enclosing method, for rules that
+    /// flag a method using an API rather than every call. Incidents outside of a method are
+    /// all kept. Implies `includeEnclosingMethod`.
+    #[serde(default)]
+    dedupe_per_enclosing_method: bool,
+    /// Also report where the matched types and members are defined in the decompiled
+    /// dependencies, as zero effort dependency incidents with a `kind` of `definition`.
+    #[serde(default)]
+    include_dependency_definitions: bool,
+    /// Also match names written in comments, such as commented out code, with a `kind` of
+    /// `comment`.
+    #[serde(default)]
+    include_comments: bool,
+    /// Also match names written in string literals, such as a provider named in a connection
+    /// string, with a `kind` of `string_literal`.
+    #[serde(default)]
+    include_string_literals: bool,
+    /// Match the namespace segments of the pattern ignoring case, `System.Web` also matches
+    /// `System.WEB`. Type and member names are still matched exactly.
+    #[serde(default)]
+    namespace_case_insensitive: bool,
+    /// Order of the incidents, and so of the pages.
+    #[serde(default)]
+    sort_by: SortBy,
+    /// Only match the calls of a method overload taking these parameter types, such as
+    /// `["string", "IFormatProvider"]`. Arguments whose type can not be told from the call
+    /// match any type, so at least the number of arguments has to agree.
+    #[serde(default)]
+    parameter_types: Option<Vec<String>>,
+}
+
+/// A symbol named by its namespace, class and method rather than a dotted pattern, each field
+/// may use `*` wildcards. `{namespace: System.Web.Mvc, class: Controller}` searches the same
+/// as `System.Web.Mvc.Controller.*`.
+#[derive(ToSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct SymbolCondition {
+    /// Dotted namespace, `System.Web.Mvc`.
+    namespace: String,
+    /// Type declared in the namespace, every type when not given.
+    class: Option<String>,
+    /// Member of the class, every member when not given. It is matched as a whole even when
+    /// it contains a `.`.
+    method: Option<String>,
+}
+
+impl From<SymbolCondition> for SymbolPattern {
+    fn from(symbol: SymbolCondition) -> SymbolPattern {
+        SymbolPattern {
+            namespace: symbol.namespace,
+            class: symbol.class,
+            method: symbol.method,
+        }
+    }
+}
+
+/// How incidents are ordered before they are paged.
+#[derive(ToSchema, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum SortBy {
+    /// By file and then line.
+    #[default]
+    Location,
+    /// Highest effort first, incidents of the same effort by location.
+    Effort,
+}
+
+/// Matches the calls to a method that pass a literal matching `value` as one of the arguments.
+#[derive(ToSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct ValueCondition {
+    /// Fully qualified name of the method, `System.Text.Encoding.GetEncoding`.
+    method: String,
+    /// Zero based position of the argument, as written in the call.
+    #[serde(default)]
+    position: usize,
+    /// Regex the literal must match, strings and characters are matched without their quotes.
+    value: String,
+    /// Only search the files whose path ends with one of these, whole segments of it, a `*`
+    /// matches within one segment of the path, `Controllers/*.cs`.
+    #[serde(alias = "file_paths")]
+    file_paths: Option<Vec<String>>,
+}
+
+/// Lists the types declared under a namespace, whether or not anything uses them.
+#[derive(ToSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct DeclarationsCondition {
+    /// Namespace the types are declared under, `Konveyor.Demos` or `Konveyor.Demos.*`.
+    pattern: String,
+    /// Only search the files whose path ends with one of these, whole segments of it, a `*`
+    /// matches within one segment of the path, `Controllers/*.cs`.
+    #[serde(alias = "file_paths")]
+    file_paths: Option<Vec<String>>,
+    /// Skip this many incidents, in the sorted order, before returning any.
+    offset: Option<usize>,
+    /// Return at most this many incidents.
+    limit: Option<usize>,
+}
+
+/// Lists the methods declared to return a type, such as the data access code returning a
+/// `DataSet`.
+#[derive(ToSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct ReturnsTypeCondition {
+    /// Fully qualified name of the returned type, `System.Data.DataSet`. Segments may use `*`
+    /// wildcards.
+    pattern: String,
+    /// Only search the files whose path ends with one of these, whole segments of it, a `*`
+    /// matches within one segment of the path, `Controllers/*.cs`.
+    #[serde(alias = "file_paths")]
+    file_paths: Option<Vec<String>>,
+    /// Skip this many incidents, in the sorted order, before returning any.
+    offset: Option<usize>,
+    /// Return at most this many incidents.
+    limit: Option<usize>,
+}
+
+/// Finds where an attribute is applied, such as the `[WebMethod]`s of an ASMX service.
+#[derive(ToSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct AttributeCondition {
+    /// Fully qualified name of the attribute class, with or without its `Attribute` suffix,
+    /// `System.Web.Services.WebMethod`. Segments may use `*` wildcards.
+    pattern: String,
+    /// Only search the files whose path ends with one of these, whole segments of it, a `*`
+    /// matches within one segment of the path, `Controllers/*.cs`.
+    #[serde(alias = "file_paths")]
     file_paths: Option<Vec<String>>,
+    /// Skip this many incidents, in the sorted order, before returning any.
+    offset: Option<usize>,
+    /// Return at most this many incidents.
+    limit: Option<usize>,
+}
+
+/// How the results of a search are narrowed down before they are returned.
+#[derive(Debug, Default)]
+struct ResultFilters {
+    min_occurrences: Option<usize>,
+    return_file_count: bool,
+    aggregate: bool,
+    dedupe_per_enclosing_method: bool,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    sort_by: SortBy,
 }
 
 #[derive(ToSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
 struct CSharpCondition {
-    referenced: ReferenceCondition,
+    referenced: Option<ReferenceCondition>,
+    value: Option<ValueCondition>,
+    declarations: Option<DeclarationsCondition>,
+    returns_type: Option<ReturnsTypeCondition>,
+    attribute: Option<AttributeCondition>,
+    // The analyzer sends the rule's context inline with every condition, it is accepted so
+    // that the condition is not refused for it but not used here.
+    #[serde(default, rename = "ruleID")]
+    #[schema(ignore)]
+    #[allow(dead_code)]
+    rule_id: Option<serde_yml::Value>,
+    #[serde(default)]
+    #[schema(ignore)]
+    #[allow(dead_code)]
+    tags: Option<serde_yml::Value>,
+    #[serde(default)]
+    #[schema(ignore)]
+    #[allow(dead_code)]
+    template: Option<serde_yml::Value>,
+}
+
+/// Parses the condition of an evaluate request, the error names the field when there is one
+/// that is not understood rather than silently ignoring it.
+fn parse_condition(condition_info: &str) -> Result<CSharpCondition, serde_yml::Error> {
+    serde_yml::from_str(condition_info)
+}
+
+fn invalid_condition(err: serde_yml::Error) -> Status {
+    error!("{:?}", err);
+    Status::invalid_argument(format!("invalid condition: {}", err))
+}
+
+/// The files a condition is limited to, an empty list searches every file like no list.
+fn file_paths(file_paths: Option<Vec<String>>) -> Result<Option<FilePaths>, String> {
+    file_paths
+        .filter(|file_paths| !file_paths.is_empty())
+        .map(|file_paths| FilePaths::new(&file_paths))
+        .transpose()
+        .map_err(|e| format!("invalid condition: `filePaths`: {}", e))
+}
+
+/// The search a condition asks for and how its results are filtered, or why the condition
+/// is not valid for the capability.
+fn condition_search(
+    cap: &str,
+    condition: CSharpCondition,
+) -> Result<(FindNode, ResultFilters), String> {
+    let missing = || format!("invalid condition: missing `{}`", cap);
+    match cap {
+        "referenced" => {
+            let referenced = condition.referenced.ok_or_else(missing)?;
+            // Rules written for other providers name locations this one does not have, the
+            // matches are not narrowed down for those rather than failing the rule.
+            let location = referenced.location.and_then(|location| {
+                location
+                    .parse::<MatchLocation>()
+                    .inspect_err(|e| warn!("{}, matching every location", e))
+                    .ok()
+            });
+            let (location, syntax_type) = match location {
+                Some(MatchLocation::Kind(kind)) => (Some(kind), None),
+                Some(MatchLocation::Syntax(syntax_type)) => (None, Some(syntax_type)),
+                None => (None, None),
+            };
+            let (regex, symbol) = match (referenced.pattern, referenced.symbol) {
+                (Some(pattern), None) => (pattern, None),
+                (None, Some(symbol)) => {
+                    if symbol.method.is_some() && symbol.class.is_none() {
+                        return Err(
+                            "invalid condition: `symbol` needs the `class` of its `method`"
+                                .to_string(),
+                        );
+                    }
+                    // Only logged, the query searches for the symbol itself.
+                    let regex = std::iter::once(&symbol.namespace)
+                        .chain(&symbol.class)
+                        .chain(&symbol.method)
+                        .cloned()
+                        .collect::<Vec<String>>()
+                        .join(".");
+                    (regex, Some(symbol.into()))
+                }
+                _ => return Err("invalid condition: give either `pattern` or `symbol`".to_string()),
+            };
+            let search = FindNode {
+                regex,
+                options: QueryOptions {
+                    location,
+                    syntax_type,
+                    include_base_types: referenced.include_base_types,
+                    subtree: referenced.subtree,
+                    timeout: referenced.timeout_ms.map(Duration::from_millis),
+                    include_enclosing_method: referenced.include_enclosing_method
+                        || referenced.dedupe_per_enclosing_method,
+                    include_dependency_definitions: referenced.include_dependency_definitions,
+                    include_comments: referenced.include_comments,
+                    include_string_literals: referenced.include_string_literals,
+                    namespace_case_insensitive: referenced.namespace_case_insensitive,
+                    parameter_types: referenced.parameter_types,
+                    symbol,
+                    file_paths: file_paths(referenced.file_paths)?,
+                    ..Default::default()
+                },
+            };
+            let filters = ResultFilters {
+                min_occurrences: referenced.min_occurrences,
+                return_file_count: referenced.return_file_count,
+                aggregate: referenced.aggregate,
+                dedupe_per_enclosing_method: referenced.dedupe_per_enclosing_method,
+                offset: referenced.offset,
+                limit: referenced.limit,
+                sort_by: referenced.sort_by,
+            };
+            Ok((search, filters))
+        }
+        "value" => {
+            let value = condition.value.ok_or_else(missing)?;
+            let Some((declaring_type, _)) = value.method.rsplit_once('.') else {
+                return Err("invalid condition: `method` must be fully qualified".to_string());
+            };
+            let regex = Regex::new(&value.value)
+                .map_err(|e| format!("invalid condition: `value`: {}", e))?;
+            let search = FindNode {
+                // The files that can call the method are the ones that use its type.
+                regex: format!("{}.*", declaring_type),
+                options: QueryOptions {
+                    argument_value: Some(ArgumentValue {
+                        method: value.method,
+                        position: value.position,
+                        value: regex,
+                    }),
+                    file_paths: file_paths(value.file_paths)?,
+                    ..Default::default()
+                },
+            };
+            let filters = ResultFilters::default();
+            Ok((search, filters))
+        }
+        "declarations" => {
+            let declarations = condition.declarations.ok_or_else(missing)?;
+            let search = FindNode {
+                regex: format!("{}.*", declarations.pattern.trim_end_matches(".*")),
+                options: QueryOptions {
+                    declarations: true,
+                    file_paths: file_paths(declarations.file_paths)?,
+                    ..Default::default()
+                },
+            };
+            let filters = ResultFilters {
+                offset: declarations.offset,
+                limit: declarations.limit,
+                ..Default::default()
+            };
+            Ok((search, filters))
+        }
+        "returnsType" => {
+            let returns_type = condition.returns_type.ok_or_else(missing)?;
+            let Some((namespace, _)) = returns_type.pattern.rsplit_once('.') else {
+                return Err("invalid condition: `pattern` must be fully qualified".to_string());
+            };
+            let search = FindNode {
+                // The files that can name the type are the ones that use its namespace.
+                regex: format!("{}.*", namespace),
+                options: QueryOptions {
+                    returns_type: Some(returns_type.pattern),
+                    file_paths: file_paths(returns_type.file_paths)?,
+                    ..Default::default()
+                },
+            };
+            let filters = ResultFilters {
+                offset: returns_type.offset,
+                limit: returns_type.limit,
+                ..Default::default()
+            };
+            Ok((search, filters))
+        }
+        "attribute" => {
+            let attribute = condition.attribute.ok_or_else(missing)?;
+            let Some((namespace, _)) = attribute.pattern.rsplit_once('.') else {
+                return Err("invalid condition: `pattern` must be fully qualified".to_string());
+            };
+            let search = FindNode {
+                // The files that can apply the attribute are the ones that use its namespace.
+                regex: format!("{}.*", namespace),
+                options: QueryOptions {
+                    syntax_type: Some(SyntaxType::Attribute),
+                    attribute: Some(attribute.pattern),
+                    file_paths: file_paths(attribute.file_paths)?,
+                    ..Default::default()
+                },
+            };
+            let filters = ResultFilters {
+                offset: attribute.offset,
+                limit: attribute.limit,
+                ..Default::default()
+            };
+            Ok((search, filters))
+        }
+        _ => Err("unknown capabilities".to_string()),
+    }
 }
 
 pub struct CSharpProvider {
     pub db_path: PathBuf,
+    /// Read only databases merged into every project graph, see `Project::extra_db_paths`.
+    pub extra_db_paths: Vec<PathBuf>,
+    /// See `Project::use_existing_db`.
+    pub use_existing_db: bool,
     pub config: Arc<Mutex<Option<Config>>>,
     pub project: Arc<Mutex<Option<Arc<Project>>>>,
+    /// Notified by `stop`, the server stops taking requests and shuts down once the ones in
+    /// flight are answered.
+    pub shutdown: Arc<Notify>,
 }
 
 impl CSharpProvider {
+    /// The incidents of a broad query easily exceed tonic's default limit of 4MB.
+    pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
     pub fn new(db_path: PathBuf) -> CSharpProvider {
         CSharpProvider {
             db_path,
+            extra_db_paths: vec![],
+            use_existing_db: false,
             config: Arc::new(Mutex::new(None)),
             project: Arc::new(Mutex::new(None)),
+            shutdown: Arc::new(Notify::new()),
+        }
+    }
+
+    /// The gRPC service for the provider, decoding requests and encoding responses of up to
+    /// `max_message_size` bytes.
+    pub fn into_service(self, max_message_size: usize) -> ProviderServiceServer<CSharpProvider> {
+        ProviderServiceServer::new(self)
+            .max_decoding_message_size(max_message_size)
+            .max_encoding_message_size(max_message_size)
+    }
+
+    async fn init_project(
+        project: Arc<Project>,
+        cancellation_flag: AtomicCancellationFlag,
+    ) -> Result<(), Status> {
+        info!(
+            "starting to load project for location: {:?}",
+            project.location
+        );
+        if let Err(e) = project.validate_language_configuration().await {
+            error!("unable to create language configuration: {}", e);
+            return Err(Status::internal(
+                "unable to create language configuration for project",
+            ));
+        }
+        if project.lazy_index {
+            info!("deferring indexing to the first query");
+            return Ok(());
         }
+        Self::index_project(&project, &cancellation_flag).await
     }
+
+    /// Builds the project graph and loads the dependencies, the first call does the work and
+    /// the others wait on it. A failed or cancelled attempt is retried by the next call, with
+    /// the flag of the request that makes it.
+    async fn index_project(
+        project: &Arc<Project>,
+        cancellation_flag: &AtomicCancellationFlag,
+    ) -> Result<(), Status> {
+        project
+            .indexed
+            .get_or_try_init(|| Self::load_project(project.clone(), cancellation_flag))
+            .await
+            .map(|_| ())
+    }
+
+    async fn load_project(
+        project: Arc<Project>,
+        cancellation_flag: &AtomicCancellationFlag,
+    ) -> Result<(), Status> {
+        let cancelled = |phase: &str| {
+            cancellation_flag.is_cancelled().then(|| {
+                info!("init cancelled {}", phase);
+                Status::cancelled(format!("init cancelled {}", phase))
+            })
+        };
+
+        if let Some(status) = cancelled("before loading project graph") {
+            return Err(status);
+        }
+        if project.use_existing_db {
+            let graph = project
+                .load_graph_from_databases(cancellation_flag)
+                .map_err(|err| {
+                    error!("{:?}", err);
+                    cancelled("while loading project graph").unwrap_or_else(|| {
+                        Status::internal(format!("unable to load existing database: {}", err))
+                    })
+                })?;
+            if graph.iter_files().next().is_none() {
+                return Err(Status::failed_precondition(format!(
+                    "existing database {:?} has nothing indexed for {:?}",
+                    project.db_path, project.location
+                )));
+            }
+            info!(
+                "loaded {} files from existing database",
+                graph.iter_files().count()
+            );
+            project.set_graph(graph);
+            return Ok(());
+        }
+        let stats = project
+            .get_project_graph(cancellation_flag)
+            .await
+            .map_err(|err| {
+                error!("{:?}", err);
+                cancelled("while loading project graph")
+                    .unwrap_or_else(|| Status::new(tonic::Code::Internal, "failed"))
+            })?;
+        debug!("loaded files: {:?}", stats);
+        if let Some(status) = cancelled("before resolving dependencies") {
+            return Err(status);
+        }
+        if let Err(e) = project.resolve(cancellation_flag).await {
+            debug!("unable to get deps: {}", e);
+            return Err(Status::internal(format!(
+                "unable to resolve dependenies: {}",
+                e
+            )));
+        }
+        debug!("resolved dependencies -- project: {:?}", project);
+        if let Some(status) = cancelled("before loading dependencies") {
+            return Err(status);
+        }
+        info!("adding depdencies to stack graph database");
+        let res = project.load_to_database(cancellation_flag).await;
+        debug!(
+            "loading project to database: {:?} -- project: {:?}",
+            res, project
+        );
+        if let Some(status) = cancelled("while loading dependencies") {
+            return Err(status);
+        }
+        if project.warm_cache {
+            let start = Instant::now();
+            // Only there to speed up the first query, it working is not required.
+            match project.warm_up() {
+                Ok(read) => info!(
+                    "warmed up cache, read {} bytes in {:?}",
+                    read,
+                    start.elapsed()
+                ),
+                Err(e) => error!("unable to warm up cache: {}", e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The files that could not be indexed, for the error of an init that still succeeded. Only
+/// the first few are named, the log has all of them.
+fn diagnostics_summary(diagnostics: &[FileDiagnostic]) -> String {
+    const NAMED: usize = 5;
+    if diagnostics.is_empty() {
+        return String::new();
+    }
+    let mut summary = format!(
+        "{} file(s) could not be indexed: {}",
+        diagnostics.len(),
+        diagnostics
+            .iter()
+            .take(NAMED)
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join("; ")
+    );
+    if diagnostics.len() > NAMED {
+        summary.push_str(&format!("; and {} more", diagnostics.len() - NAMED));
+    }
+    summary
+}
+
+/// Drops the results whose fully qualified name matched fewer than `min` times, adding how
+/// many times it matched to the ones that are kept as `count`. Results without a known name
+/// count on their own.
+fn filter_min_occurrences(results: Vec<ResultNode>, min: usize) -> Vec<ResultNode> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for r in results.iter() {
+        if let Some(fqdn) = &r.fqdn {
+            *counts.entry(fqdn.clone()).or_default() += 1;
+        }
+    }
+    results
+        .into_iter()
+        .filter_map(|mut r| {
+            let count = r.fqdn.as_ref().map_or(1, |fqdn| counts[fqdn]);
+            if count < min {
+                return None;
+            }
+            r.variables
+                .insert("count".to_string(), serde_json::Value::from(count));
+            Some(r)
+        })
+        .collect()
+}
+
+/// Keeps the first result, by location, of each enclosing method. Results that are not in a
+/// method are all kept.
+fn dedupe_per_enclosing_method(mut results: Vec<ResultNode>) -> Vec<ResultNode> {
+    results.sort_by_key(|r| {
+        let start = &r.code_location.start_position;
+        (r.file_uri.clone(), start.line, start.character)
+    });
+    let mut seen: HashSet<String> = HashSet::new();
+    results.retain(|r| match r.variables.get("enclosing_method") {
+        Some(serde_json::Value::String(method)) => seen.insert(method.clone()),
+        _ => true,
+    });
+    results
+}
+
+fn sort_incidents(incidents: &mut [IncidentContext], sort_by: SortBy) {
+    incidents.sort_by_key(|i| format!("{}-{:?}", i.file_uri, i.line_number()));
+    if sort_by == SortBy::Effort {
+        // Stable, so the location order is kept for the same effort.
+        incidents.sort_by_key(|i| std::cmp::Reverse(i.effort));
+    }
+}
+
+/// Cut the sorted incidents down to the requested page. When the condition asks for a page
+/// the total and whether more incidents remain are returned as template context, so the
+/// caller knows to ask for the next one.
+fn paginate(
+    incidents: Vec<IncidentContext>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> (Vec<IncidentContext>, Option<Struct>) {
+    if offset.is_none() && limit.is_none() {
+        return (incidents, None);
+    }
+    let total = incidents.len();
+    let offset = offset.unwrap_or_default();
+    let page: Vec<IncidentContext> = incidents
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    let truncated = offset + page.len() < total;
+    let template_context = Struct {
+        fields: BTreeMap::from([
+            (
+                "total".to_string(),
+                Value {
+                    kind: Some(Kind::NumberValue(total as f64)),
+                },
+            ),
+            (
+                "truncated".to_string(),
+                Value {
+                    kind: Some(Kind::BoolValue(truncated)),
+                },
+            ),
+        ]),
+    };
+    (page, Some(template_context))
+}
+
+/// The number of distinct files the incidents are in, as the `file_count` of a template
+/// context.
+fn file_count(incidents: &[IncidentContext]) -> Struct {
+    let files: HashSet<&str> = incidents.iter().map(|i| i.file_uri.as_str()).collect();
+    Struct {
+        fields: BTreeMap::from([(
+            "file_count".to_string(),
+            Value {
+                kind: Some(Kind::NumberValue(files.len() as f64)),
+            },
+        )]),
+    }
+}
+
+/// Collapses the sorted incidents into one at the first of them, listing the file, line and
+/// location of each of them, in order, as the `locations` variable.
+fn aggregate(incidents: Vec<IncidentContext>) -> Vec<IncidentContext> {
+    let Some(first) = incidents.first() else {
+        return vec![];
+    };
+    let position = |position: Option<Position>| {
+        let position = position.unwrap_or_default();
+        json!({"line": position.line, "character": position.character})
+    };
+    let locations: Vec<serde_json::Value> = incidents
+        .iter()
+        .map(|i| {
+            let location = i.code_location.unwrap_or_default();
+            json!({
+                "file": i.file_uri,
+                "line": i.line_number,
+                "location": {
+                    "startPosition": position(location.start_position),
+                    "endPosition": position(location.end_position),
+                },
+            })
+        })
+        .collect();
+    // The file and the text of the line stay those of the first incident, for display.
+    let mut variables = Struct {
+        fields: first
+            .variables
+            .iter()
+            .flat_map(|variables| &variables.fields)
+            .filter(|(name, _)| matches!(name.as_str(), "file" | "line"))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect(),
+    };
+    variables.fields.insert(
+        "locations".to_string(),
+        serde_json_to_prost(serde_json::Value::from(locations)),
+    );
+    vec![IncidentContext {
+        file_uri: first.file_uri.clone(),
+        effort: incidents.iter().filter_map(|i| i.effort).max(),
+        code_location: first.code_location,
+        line_number: first.line_number,
+        variables: Some(variables),
+        links: vec![],
+        is_dependency_incident: incidents.iter().all(|i| i.is_dependency_incident),
+    }]
+}
+
+/// The conditions of the capabilities, see `capabilities_manifest`.
+#[derive(OpenApi)]
+#[openapi(components(schemas(
+    CSharpCondition,
+    ReferenceCondition,
+    SymbolCondition,
+    SortBy,
+    ValueCondition,
+    DeclarationsCondition,
+    ReturnsTypeCondition,
+    AttributeCondition
+)))]
+struct ApiDoc;
+
+fn capabilities_response() -> CapabilitiesResponse {
+    CapabilitiesResponse {
+        capabilities: [
+            "referenced",
+            "value",
+            "declarations",
+            "returnsType",
+            "attribute",
+        ]
+        .into_iter()
+        .map(|name| Capability {
+            name: name.to_string(),
+            template_context: None,
+        })
+        .collect(),
+    }
+}
+
+/// The capabilities the provider answers and the OpenAPI schema of their conditions as JSON,
+/// for tooling that generates rule schemas without starting a server.
+pub fn capabilities_manifest() -> Result<String, serde_json::Error> {
+    let capabilities: Vec<serde_json::Value> = capabilities_response()
+        .capabilities
+        .into_iter()
+        .map(|capability| serde_json::json!({ "name": capability.name }))
+        .collect();
+    serde_json::to_string_pretty(&serde_json::json!({
+        "capabilities": capabilities,
+        "schema": ApiDoc::openapi(),
+    }))
 }
 
 #[tonic::async_trait]
 impl ProviderService for CSharpProvider {
     async fn capabilities(&self, _: Request<()>) -> Result<Response<CapabilitiesResponse>, Status> {
-        // Add Referenced
-
-        #[derive(OpenApi)]
-        struct ApiDoc;
-
-        let openapi = ApiDoc::openapi();
-        let json = openapi.to_pretty_json();
+        let json = ApiDoc::openapi().to_pretty_json();
         if json.is_err() {
             return Err(Status::from_error(Box::new(json.err().unwrap())));
         }
 
         debug!("returning refernced capability: {:?}", json.ok());
 
-        return Ok(Response::new(CapabilitiesResponse {
-            capabilities: vec![Capability {
-                name: "referenced".to_string(),
-                template_context: None,
-            }],
-        }));
+        return Ok(Response::new(capabilities_response()));
     }
 
     async fn init(&self, r: Request<Config>) -> Result<Response<InitResponse>, Status> {
@@ -78,14 +819,46 @@ impl ProviderService for CSharpProvider {
 
         let analysis_mode = AnalysisMode::from(saved_config.analysis_mode.clone());
         let location = PathBuf::from(saved_config.location.clone());
-        let tools = Project::get_tools(&saved_config.provider_specific_config)
-            .map_err(|e| Status::invalid_argument(format!("unalble to find tools: {}", e)))?;
-        let project = Arc::new(Project::new(
-            location,
-            self.db_path.clone(),
-            analysis_mode,
-            tools,
-        ));
+        let provider_config = ProviderConfig::from_struct(&saved_config.provider_specific_config)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let tools = if self.use_existing_db {
+            if !self.db_path.exists() {
+                return Err(Status::failed_precondition(format!(
+                    "existing database {:?} not found",
+                    self.db_path
+                )));
+            }
+            // Nothing is resolved or decompiled, the tools are never run.
+            Tools {
+                ilspy_cmd: PathBuf::new(),
+                paket_cmd: PathBuf::new(),
+                env: provider_config.tool_env.clone(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            }
+        } else {
+            provider_config
+                .tools()
+                .map_err(|e| Status::invalid_argument(format!("unalble to find tools: {}", e)))?
+        };
+        let mut project = Project::new(location, self.db_path.clone(), analysis_mode, tools);
+        project.index_options.include_generated = provider_config.include_generated;
+        project.index_options.respect_gitignore = provider_config.respect_gitignore;
+        project.index_options.include_namespaces = provider_config.include_namespaces.clone();
+        project.default_framework = provider_config.default_framework.clone();
+        project.index_options.file_list = provider_config
+            .file_list(&project.location)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        project.max_decompiled_file_bytes = provider_config.max_decompiled_file_bytes;
+        project.prefer_source_packages = provider_config.prefer_source_packages;
+        project.index_reference_assemblies = provider_config.index_reference_assemblies;
+        project.lazy_index = provider_config.lazy_index;
+        project.warm_cache = provider_config.warm_cache;
+        project.query_mode = provider_config.query_mode;
+        project.cache_size = provider_config.cache_size;
+        project.mmap_size = provider_config.mmap_size;
+        project.extra_db_paths = self.extra_db_paths.clone();
+        project.use_existing_db = self.use_existing_db;
+        let project = Arc::new(project);
         let project_lock = self.project.clone();
         let mut project_guard = project_lock.lock().await;
         let _ = project_guard.replace(project.clone());
@@ -94,7 +867,7 @@ impl ProviderService for CSharpProvider {
 
         let project_guard = project_lock.lock().await;
         let project = match project_guard.as_ref() {
-            Some(x) => x,
+            Some(x) => x.clone(),
             None => {
                 return Err(Status::internal(
                     "unable to create language configuration for project",
@@ -102,40 +875,24 @@ impl ProviderService for CSharpProvider {
             }
         };
 
-        info!(
-            "starting to load project for location: {:?}",
-            project.location
-        );
-        if let Err(e) = project.validate_language_configuration().await {
-            error!("unable to create language configuration: {}", e);
-            return Err(Status::internal(
-                "unable to create language configuration for project",
-            ));
-        }
-        let stats = project.get_project_graph().await.map_err(|err| {
-            error!("{:?}", err);
-            Status::new(tonic::Code::Internal, "failed")
-        })?;
-        debug!("loaded files: {:?}", stats);
-        let get_deps_handle = project.resolve();
-
-        let res = match get_deps_handle.await {
-            Ok(res) => res,
+        // Run the pipeline on its own task, if the client goes away tonic drops this future
+        // and the guard trips the flag of this request so the pipeline stops at its next
+        // check. Later requests on the project each have their own flag.
+        let cancellation_flag = AtomicCancellationFlag::new();
+        let _cancel_on_drop = cancellation_flag.cancel_on_drop();
+        let pipeline = tokio::spawn(Self::init_project(project.clone(), cancellation_flag));
+        match pipeline.await {
+            Ok(res) => res?,
             Err(e) => {
-                debug!("unable to get deps: {}", e);
-                return Err(Status::internal("unable to resolve dependenies"));
+                error!("init task failed: {}", e);
+                return Err(Status::internal("init task failed"));
             }
-        };
-        debug!("got task result: {:?} -- project: {:?}", res, project);
-        info!("adding depdencies to stack graph database");
-        let res = project.load_to_database().await;
-        debug!(
-            "loading project to database: {:?} -- project: {:?}",
-            res, project
-        );
+        }
+        let error = diagnostics_summary(&project.diagnostics.lock().unwrap());
+        drop(project_guard);
 
         return Ok(Response::new(InitResponse {
-            error: String::new(),
+            error,
             successful: true,
             id: 4,
             builtin_config: None,
@@ -150,20 +907,18 @@ impl ProviderService for CSharpProvider {
         let evaluate_request = r.get_ref();
         debug!("evaluate request: {:?}", evaluate_request.condition_info);
 
-        if evaluate_request.cap != "referenced" {
+        if !matches!(
+            evaluate_request.cap.as_str(),
+            "referenced" | "value" | "declarations" | "returnsType" | "attribute"
+        ) {
             return Err(Status::invalid_argument("unknown capabilities"));
         }
-        let condition: CSharpCondition =
-            serde_yml::from_str(evaluate_request.condition_info.as_str()).map_err(|err| {
-                error!("{:?}", err);
-                Status::new(tonic::Code::Internal, "failed")
-            })?;
+        let condition =
+            parse_condition(&evaluate_request.condition_info).map_err(invalid_condition)?;
 
         debug!("condition: {:?}", condition);
-        let search = FindNode {
-            node_type: condition.referenced.location.clone(),
-            regex: condition.referenced.pattern.clone(),
-        };
+        let (search, filters) =
+            condition_search(&evaluate_request.cap, condition).map_err(Status::invalid_argument)?;
 
         let project_guard = self.project.lock().await;
         let project = match project_guard.as_ref() {
@@ -172,6 +927,12 @@ impl ProviderService for CSharpProvider {
                 return Err(Status::internal("project may not be initialized"));
             }
         };
+        if project.lazy_index {
+            // Indexing stops if the client goes away, the next request starts it over.
+            let cancellation_flag = AtomicCancellationFlag::new();
+            let _cancel_on_drop = cancellation_flag.cancel_on_drop();
+            Self::index_project(project, &cancellation_flag).await?;
+        }
         let results = search.run(project).await.map_or_else(
             |err| EvaluateResponse {
                 error: err.to_string(),
@@ -179,16 +940,30 @@ impl ProviderService for CSharpProvider {
                 response: None,
             },
             |res| {
-                info!("found {} results for search: {:?}", res.len(), &condition);
+                info!("found {} results for search: {:?}", res.len(), &filters);
+                let res = match filters.min_occurrences {
+                    Some(min) => filter_min_occurrences(res, min),
+                    None => res,
+                };
+                let res = match filters.dedupe_per_enclosing_method {
+                    true => dedupe_per_enclosing_method(res),
+                    false => res,
+                };
                 let mut i: Vec<IncidentContext> = res.into_iter().map(Into::into).collect();
-                i.sort_by_key(|i| format!("{}-{:?}", i.file_uri, i.line_number()));
+                sort_incidents(&mut i, filters.sort_by);
+                let matched = !i.is_empty();
+                let (i, template_context) = match (filters.return_file_count, filters.aggregate) {
+                    (true, _) => (vec![], Some(file_count(&i))),
+                    (false, true) => (aggregate(i), None),
+                    (false, false) => paginate(i, filters.offset, filters.limit),
+                };
                 EvaluateResponse {
                     error: String::new(),
                     successful: true,
                     response: Some(ProviderEvaluateResponse {
-                        matched: !i.is_empty(),
+                        matched,
                         incident_contexts: i,
-                        template_context: None,
+                        template_context,
                     }),
                 }
             },
@@ -198,6 +973,9 @@ impl ProviderService for CSharpProvider {
     }
 
     async fn stop(&self, _: Request<ServiceRequest>) -> Result<Response<()>, Status> {
+        info!("stop requested, shutting down once the requests in flight are answered");
+        // Kept as a permit when the server is not waiting on it yet.
+        self.shutdown.notify_one();
         return Ok(Response::new(()));
     }
 
@@ -216,19 +994,692 @@ impl ProviderService for CSharpProvider {
         &self,
         _: Request<ServiceRequest>,
     ) -> Result<Response<DependencyDagResponse>, Status> {
-        return Ok(Response::new(DependencyDagResponse {
-            successful: true,
-            error: String::new(),
-            file_dag_dep: vec![],
-        }));
+        let project_guard = self.project.lock().await;
+        let project = match project_guard.as_ref() {
+            Some(x) => x.clone(),
+            None => {
+                return Err(Status::internal("project may not be initialized"));
+            }
+        };
+        drop(project_guard);
+        let file_uri = Url::from_file_path(project.location.join("paket.dependencies"))
+            .map_err(|_| Status::internal("unable to build the uri of paket.dependencies"))?;
+        let response = match project.dependency_dag().await {
+            Ok(list) => DependencyDagResponse {
+                successful: true,
+                error: String::new(),
+                file_dag_dep: vec![FileDagDep {
+                    file_uri: file_uri.to_string(),
+                    list,
+                }],
+            },
+            Err(e) => {
+                error!("unable to build dependency dag: {:?}", e);
+                DependencyDagResponse {
+                    successful: false,
+                    error: e.to_string(),
+                    file_dag_dep: vec![],
+                }
+            }
+        };
+        Ok(Response::new(response))
     }
 
     async fn notify_file_changes(
         &self,
-        _: Request<NotifyFileChangesRequest>,
+        r: Request<NotifyFileChangesRequest>,
     ) -> Result<Response<NotifyFileChangesResponse>, Status> {
-        return Ok(Response::new(NotifyFileChangesResponse {
-            error: String::new(),
-        }));
+        let project_guard = self.project.lock().await;
+        let project = match project_guard.as_ref() {
+            Some(x) => x.clone(),
+            None => {
+                return Err(Status::internal("project may not be initialized"));
+            }
+        };
+        drop(project_guard);
+        let mut paths = vec![];
+        for change in r.into_inner().changes {
+            // Unsaved edits are not on disk, the file is indexed again once it is saved.
+            if !change.saved {
+                continue;
+            }
+            match Url::parse(&change.uri).map(|uri| uri.to_file_path()) {
+                Ok(Ok(path)) => paths.push(path),
+                _ => {
+                    return Ok(Response::new(NotifyFileChangesResponse {
+                        error: format!("invalid file uri: {}", change.uri),
+                    }))
+                }
+            }
+        }
+        // Indexing the files stops if the client goes away, they are indexed again with the
+        // next change reported for them.
+        let cancellation_flag = AtomicCancellationFlag::new();
+        let _cancel_on_drop = cancellation_flag.cancel_on_drop();
+        let error = match project.reindex_files(&paths, &cancellation_flag).await {
+            Ok(changes) => {
+                info!(
+                    "indexed file changes, added: {}, modified: {}, deleted: {}",
+                    changes.added.len(),
+                    changes.modified.len(),
+                    changes.deleted.len()
+                );
+                String::new()
+            }
+            Err(e) => {
+                error!("unable to index file changes: {:?}", e);
+                format!("unable to index file changes: {}", e)
+            }
+        };
+        Ok(Response::new(NotifyFileChangesResponse { error }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::c_sharp_graph::query::{SymbolKind, SyntaxType};
+    use crate::test_support::TestDir;
+
+    fn incidents(n: i64) -> Vec<IncidentContext> {
+        (0..n)
+            .map(|line| IncidentContext {
+                file_uri: "file:///demo/File.cs".to_string(),
+                line_number: Some(line),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn field(context: &Struct, name: &str) -> Kind {
+        context.fields[name].kind.clone().unwrap()
+    }
+
+    #[test]
+    fn effort_sort_puts_the_highest_effort_first_and_keeps_location_for_ties() {
+        let incident = |file: &str, line: i64, effort: Option<i64>| IncidentContext {
+            file_uri: format!("file:///demo/{}", file),
+            line_number: Some(line),
+            effort,
+            ..Default::default()
+        };
+        let unsorted = vec![
+            incident("B.cs", 1, Some(3)),
+            incident("A.cs", 2, None),
+            incident("A.cs", 1, Some(3)),
+            incident("C.cs", 1, Some(7)),
+            incident("A.cs", 3, Some(1)),
+        ];
+        let order = |sort_by: SortBy| {
+            let mut incidents = unsorted.clone();
+            sort_incidents(&mut incidents, sort_by);
+            incidents
+                .iter()
+                .map(|i| {
+                    (
+                        i.file_uri.trim_start_matches("file:///demo/").to_string(),
+                        i.line_number(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let at = |file: &str, line: i64| (file.to_string(), line);
+        assert_eq!(
+            order(SortBy::Effort),
+            vec![
+                at("C.cs", 1),
+                at("A.cs", 1),
+                at("B.cs", 1),
+                at("A.cs", 3),
+                at("A.cs", 2)
+            ]
+        );
+        assert_eq!(
+            order(SortBy::Location),
+            vec![
+                at("A.cs", 1),
+                at("A.cs", 2),
+                at("A.cs", 3),
+                at("B.cs", 1),
+                at("C.cs", 1)
+            ]
+        );
+
+        let condition: ReferenceCondition =
+            serde_json::from_str(r#"{"pattern": "A.*", "sortBy": "effort"}"#).unwrap();
+        assert_eq!(condition.sort_by, SortBy::Effort);
+        assert!(serde_json::from_str::<ReferenceCondition>(
+            r#"{"pattern": "A.*", "sortBy": "size"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn paginate_reports_truncation_when_limit_cuts_results() {
+        let (page, context) = paginate(incidents(5), None, Some(2));
+        let context = context.expect("page metadata");
+        assert_eq!(page.len(), 2);
+        assert_eq!(field(&context, "total"), Kind::NumberValue(5.0));
+        assert_eq!(field(&context, "truncated"), Kind::BoolValue(true));
+
+        let (page, context) = paginate(incidents(5), Some(4), Some(2));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].line_number, Some(4));
+        assert_eq!(
+            field(&context.unwrap(), "truncated"),
+            Kind::BoolValue(false)
+        );
+    }
+
+    #[test]
+    fn file_paths_of_a_condition_match_whole_file_names() {
+        let filters = file_paths(Some(vec!["demo/File.cs".to_string()]))
+            .unwrap()
+            .unwrap();
+        assert!(filters.matches("/src/demo/File.cs"));
+        assert!(!filters.matches("/src/demo/MyFile.cs"));
+        assert!(!filters.matches("/src/mydemo/File.cs"));
+        assert!(file_paths(Some(vec![])).unwrap().is_none());
+    }
+
+    #[test]
+    fn paginate_without_page_returns_everything() {
+        let (page, context) = paginate(incidents(5), None, None);
+        assert_eq!(page.len(), 5);
+        assert!(context.is_none());
+    }
+
+    #[test]
+    fn aggregate_collapses_the_incidents_into_one_at_the_first() {
+        let aggregated = aggregate(incidents(3));
+        assert_eq!(aggregated.len(), 1);
+        assert_eq!(aggregated[0].line_number, Some(0));
+        let Kind::ListValue(locations) =
+            field(aggregated[0].variables.as_ref().unwrap(), "locations")
+        else {
+            panic!("locations is not a list");
+        };
+        let lines: Vec<Kind> = locations
+            .values
+            .iter()
+            .map(|location| match &location.kind {
+                Some(Kind::StructValue(location)) => field(location, "line"),
+                other => panic!("unexpected location {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                Kind::NumberValue(0.0),
+                Kind::NumberValue(1.0),
+                Kind::NumberValue(2.0)
+            ]
+        );
+
+        assert!(aggregate(vec![]).is_empty());
+    }
+
+    #[test]
+    fn value_conditions_are_checked_for_the_capability() {
+        let search_for = |cap: &str, condition: &str| {
+            condition_search(cap, parse_condition(condition).unwrap()).map(|(search, _)| search)
+        };
+        let search = search_for(
+            "value",
+            r#"{"value": {"method": "System.Text.Encoding.GetEncoding", "value": "^ascii$"}}"#,
+        )
+        .unwrap();
+        assert_eq!(search.regex, "System.Text.Encoding.*");
+        let argument_value = search.options.argument_value.unwrap();
+        assert_eq!(argument_value.method, "System.Text.Encoding.GetEncoding");
+        assert_eq!(argument_value.position, 0);
+
+        let err =
+            search_for("value", r#"{"referenced": {"pattern": "System.Web.*"}}"#).unwrap_err();
+        assert_eq!(err, "invalid condition: missing `value`");
+        let err = search_for(
+            "value",
+            r#"{"value": {"method": "GetEncoding", "value": "ascii"}}"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("fully qualified"), "{}", err);
+        let err = search_for(
+            "value",
+            r#"{"value": {"method": "System.Text.Encoding.GetEncoding", "value": "("}}"#,
+        )
+        .unwrap_err();
+        assert!(err.starts_with("invalid condition: `value`"), "{}", err);
+    }
+
+    #[test]
+    fn returns_type_conditions_search_the_namespace_of_the_type() {
+        let search_for = |condition: &str| {
+            condition_search("returnsType", parse_condition(condition).unwrap())
+                .map(|(search, _)| search)
+        };
+        let search = search_for(r#"{"returnsType": {"pattern": "System.Data.DataSet"}}"#).unwrap();
+        assert_eq!(search.regex, "System.Data.*");
+        assert_eq!(
+            search.options.returns_type.as_deref(),
+            Some("System.Data.DataSet")
+        );
+
+        let err = search_for(r#"{"returnsType": {"pattern": "DataSet"}}"#).unwrap_err();
+        assert!(err.contains("fully qualified"), "{}", err);
+        let err = search_for(r#"{"referenced": {"pattern": "System.Data.*"}}"#).unwrap_err();
+        assert_eq!(err, "invalid condition: missing `returnsType`");
+    }
+
+    #[test]
+    fn attribute_conditions_search_the_applications_of_the_attribute() {
+        let search_for = |condition: &str| {
+            condition_search("attribute", parse_condition(condition).unwrap())
+                .map(|(search, _)| search)
+        };
+        let search =
+            search_for(r#"{"attribute": {"pattern": "System.Web.Services.WebMethod"}}"#).unwrap();
+        assert_eq!(search.regex, "System.Web.Services.*");
+        assert_eq!(search.options.syntax_type, Some(SyntaxType::Attribute));
+        assert_eq!(
+            search.options.attribute.as_deref(),
+            Some("System.Web.Services.WebMethod")
+        );
+
+        let err = search_for(r#"{"attribute": {"pattern": "WebMethod"}}"#).unwrap_err();
+        assert!(err.contains("fully qualified"), "{}", err);
+        let err = search_for(r#"{"referenced": {"pattern": "System.Web.*"}}"#).unwrap_err();
+        assert_eq!(err, "invalid condition: missing `attribute`");
+    }
+
+    #[test]
+    fn condition_fields_are_camel_case_and_file_paths_keeps_its_released_name() {
+        for condition in [
+            r#"{"declarations": {"pattern": "Demo", "filePaths": ["Api.cs"]}}"#,
+            r#"{"declarations": {"pattern": "Demo", "file_paths": ["Api.cs"]}}"#,
+        ] {
+            let condition = parse_condition(condition).unwrap();
+            assert_eq!(
+                condition.declarations.unwrap().file_paths,
+                Some(vec!["Api.cs".to_string()])
+            );
+        }
+        let condition =
+            parse_condition(r#"{"returnsType": {"pattern": "System.Data.DataSet"}}"#).unwrap();
+        assert!(condition.returns_type.is_some());
+        let err = parse_condition(r#"{"referenced": {"pattern": "Demo", "sort_by": "effort"}}"#)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("unknown field `sort_by`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn referenced_locations_are_parsed_into_a_symbol_kind_or_syntax_type() {
+        let search_for = |condition: &str| {
+            condition_search("referenced", parse_condition(condition).unwrap())
+                .map(|(search, _)| search)
+        };
+        let search =
+            search_for(r#"{"referenced": {"pattern": "System.Web.*", "location": "method"}}"#)
+                .unwrap();
+        assert_eq!(search.options.location, Some(SymbolKind::Method));
+        let search = search_for(r#"{"referenced": {"pattern": "System.Web.*"}}"#).unwrap();
+        assert_eq!(search.options.location, None);
+
+        for (location, syntax_type) in [
+            ("import", SyntaxType::Import),
+            ("class_def", SyntaxType::ClassDef),
+            ("method_name", SyntaxType::MethodName),
+            ("member-access", SyntaxType::MemberAccess),
+        ] {
+            let condition = format!(
+                r#"{{"referenced": {{"pattern": "System.Web.*", "location": "{}"}}}}"#,
+                location
+            );
+            let search = search_for(&condition).unwrap();
+            assert_eq!(search.options.location, None);
+            assert_eq!(search.options.syntax_type, Some(syntax_type));
+        }
+
+        // Not narrowed down by a location that is not known.
+        let search =
+            search_for(r#"{"referenced": {"pattern": "System.Web.*", "location": "using"}}"#)
+                .unwrap();
+        assert_eq!(search.options.location, None);
+        assert_eq!(search.options.syntax_type, None);
+        let err = "using".parse::<MatchLocation>().unwrap_err().to_string();
+        assert!(err.contains("unknown location `using`"), "{}", err);
+        assert!(err.contains("`class_def`"), "{}", err);
+    }
+
+    #[test]
+    fn referenced_conditions_take_either_a_pattern_or_a_symbol() {
+        let search_for = |condition: &str| {
+            condition_search("referenced", parse_condition(condition).unwrap())
+                .map(|(search, _)| search)
+        };
+        let search = search_for(
+            r#"{"referenced": {"symbol": {"namespace": "System.Web", "class": "Http*", "method": "Get"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(search.regex, "System.Web.Http*.Get");
+        let symbol = search.options.symbol.unwrap();
+        assert_eq!(symbol.class.as_deref(), Some("Http*"));
+        assert_eq!(symbol.method.as_deref(), Some("Get"));
+
+        let err = search_for(r#"{"referenced": {"location": "method"}}"#).unwrap_err();
+        assert!(err.contains("either `pattern` or `symbol`"), "{}", err);
+        let err = search_for(
+            r#"{"referenced": {"pattern": "System.Web.*", "symbol": {"namespace": "System.Web"}}}"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("either `pattern` or `symbol`"), "{}", err);
+        let err = search_for(
+            r#"{"referenced": {"symbol": {"namespace": "System.Web", "method": "Get"}}}"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("needs the `class`"), "{}", err);
+    }
+
+    #[test]
+    fn conditions_with_unknown_fields_are_rejected() {
+        let status = invalid_condition(
+            parse_condition(r#"{"referenced": {"patern": "System.Web.*"}}"#).unwrap_err(),
+        );
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(
+            status.message().contains("unknown field `patern`"),
+            "{}",
+            status.message()
+        );
+
+        let status = invalid_condition(
+            parse_condition(r#"{"referenced": {"pattern": "System.Web.*"}, "referenced2": {}}"#)
+                .unwrap_err(),
+        );
+        assert!(
+            status.message().contains("unknown field `referenced2`"),
+            "{}",
+            status.message()
+        );
+
+        let condition = parse_condition(
+            r#"{"referenced": {"pattern": "System.Web.*"}, "ruleID": "rule-1", "tags": {}, "template": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            condition.referenced.unwrap().pattern.as_deref(),
+            Some("System.Web.*")
+        );
+    }
+
+    /// A source-only project in `dir` with a mock standing in for paket and ilspy, and the
+    /// config to init it with the given extra flags set.
+    #[cfg(unix)]
+    fn mock_tool_project(dir: &TestDir, flags: &[&str]) -> Config {
+        use std::os::unix::fs::PermissionsExt;
+
+        dir.write(
+            "Program.cs",
+            "namespace Demo.Mocked { public class Program { void Run() { new Program(); } } }",
+        );
+        dir.write(
+            "paket.dependencies",
+            "source https://www.nuget.org/api/v2\n",
+        );
+        // Stands in for paket and ilspy, `paket add` installs an empty reference assembly
+        // package.
+        let tool = dir.join("mock-tool");
+        std::fs::write(
+            &tool,
+            "#!/bin/sh\nmkdir -p \"packages/$2\"\necho 'D: /build/.NETFramework/v4.0' > \"packages/$2/paket-installmodel.cache\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let string = |s: &str| Value {
+            kind: Some(Kind::StringValue(s.to_string())),
+        };
+        // Without dependencies nothing says which reference assemblies to install.
+        let mut fields = BTreeMap::from([
+            ("ilspy_cmd".to_string(), string(tool.to_str().unwrap())),
+            ("paket_cmd".to_string(), string(tool.to_str().unwrap())),
+            ("default_framework".to_string(), string("net45")),
+        ]);
+        for flag in flags {
+            fields.insert(
+                flag.to_string(),
+                Value {
+                    kind: Some(Kind::BoolValue(true)),
+                },
+            );
+        }
+        Config {
+            location: dir.to_string_lossy().to_string(),
+            analysis_mode: "source-only".to_string(),
+            provider_specific_config: Some(Struct { fields }),
+            ..Default::default()
+        }
+    }
+
+    fn evaluate_request(pattern: &str) -> Request<EvaluateRequest> {
+        Request::new(EvaluateRequest {
+            id: 1,
+            cap: "referenced".to_string(),
+            condition_info: format!(r#"{{"referenced": {{"pattern": "{}"}}}}"#, pattern),
+        })
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn lazy_index_builds_the_graph_on_the_first_query() {
+        let dir = TestDir::new("lazy-index");
+        let provider = CSharpProvider::new(dir.join("test.db"));
+        provider
+            .init(Request::new(mock_tool_project(&dir, &["lazy_index"])))
+            .await
+            .unwrap();
+        let project = provider.project.lock().await.clone().unwrap();
+        assert!(project.graph.lock().unwrap().is_none());
+        assert!(project.indexed.get().is_none());
+
+        let evaluate = || provider.evaluate(evaluate_request("Demo.Mocked.*"));
+        let response = evaluate().await.unwrap().into_inner();
+        assert!(response.successful, "{}", response.error);
+        assert!(response.response.unwrap().matched);
+        assert!(project.indexed.get().is_some());
+
+        // Later queries search the same graph instead of indexing again.
+        let packages = dir.join("packages");
+        std::fs::remove_dir_all(&packages).unwrap();
+        let response = evaluate().await.unwrap().into_inner();
+        assert!(response.response.unwrap().matched);
+        assert!(!packages.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn init_reports_files_that_could_not_be_indexed() {
+        let dir = TestDir::new("init-diagnostics");
+        let config = mock_tool_project(&dir, &[]);
+        dir.write("Broken.cs", "namespace Demo.Mocked { class Broken {");
+        let provider = CSharpProvider::new(dir.join("test.db"));
+        let response = provider
+            .init(Request::new(config))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.successful);
+        assert!(response.error.contains("Broken.cs"), "{}", response.error);
+        let project = provider.project.lock().await.clone().unwrap();
+        let diagnostics = project.diagnostics.lock().unwrap().clone();
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0].path, dir.join("Broken.cs"));
+
+        // The files that parse are still searched.
+        let response = provider
+            .evaluate(evaluate_request("Demo.Mocked.*"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.response.unwrap().matched);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn use_existing_db_queries_a_prebuilt_database_without_tools() {
+        let dir = TestDir::new("existing-db");
+        let db_path = dir.join("test.db");
+        let indexer = CSharpProvider::new(db_path.clone());
+        indexer
+            .init(Request::new(mock_tool_project(&dir, &[])))
+            .await
+            .unwrap();
+        drop(indexer);
+
+        // Neither the tools nor the packages they installed are there any more.
+        std::fs::remove_file(dir.join("mock-tool")).unwrap();
+        std::fs::remove_dir_all(dir.join("packages")).unwrap();
+        let mut provider = CSharpProvider::new(db_path.clone());
+        provider.use_existing_db = true;
+        let response = provider
+            .init(Request::new(Config {
+                location: dir.to_string_lossy().to_string(),
+                analysis_mode: "source-only".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.successful, "{}", response.error);
+        assert!(!dir.join("packages").exists());
+
+        let response = provider
+            .evaluate(evaluate_request("Demo.Mocked.*"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.successful, "{}", response.error);
+        assert!(response.response.unwrap().matched);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn warm_cache_builds_the_index_before_the_first_query() {
+        for warm_cache in [false, true] {
+            let dir = TestDir::new(&format!("warm-cache-{}", warm_cache));
+            let flags: &[&str] = if warm_cache { &["warm_cache"] } else { &[] };
+            let provider = CSharpProvider::new(dir.join("test.db"));
+            provider
+                .init(Request::new(mock_tool_project(&dir, flags)))
+                .await
+                .unwrap();
+            let project = provider.project.lock().await.clone().unwrap();
+            assert_eq!(
+                project.namespace_index.lock().unwrap().is_some(),
+                warm_cache
+            );
+
+            let response = provider
+                .evaluate(evaluate_request("Demo.Mocked.*"))
+                .await
+                .unwrap()
+                .into_inner();
+            assert!(response.successful, "{}", response.error);
+            assert!(response.response.unwrap().matched);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn queries_after_a_failed_init_are_not_cancelled() {
+        let dir = TestDir::new("failed-init");
+        let mut config = mock_tool_project(&dir, &[]);
+        config
+            .provider_specific_config
+            .as_mut()
+            .unwrap()
+            .fields
+            .insert(
+                "query_mode".to_string(),
+                Value {
+                    kind: Some(Kind::StringValue("db_backed".to_string())),
+                },
+            );
+        // The project is indexed, then resolving its dependencies fails.
+        dir.write("mock-tool", "#!/bin/sh\nexit 1\n");
+        let provider = CSharpProvider::new(dir.join("test.db"));
+        assert!(provider.init(Request::new(config)).await.is_err());
+
+        // Every query loads the graph from the database, which a cancelled flag would stop.
+        let response = provider
+            .evaluate(evaluate_request("Demo.Mocked.*"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.successful, "{}", response.error);
+        assert!(response.response.unwrap().matched);
+    }
+
+    #[cfg(unix)]
+    // paket runs on a worker of its own, the other one notices the client is gone.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn init_stops_when_the_client_goes_away() {
+        use tokio_stream::wrappers::TcpListenerStream;
+        use tonic::transport::{Channel, Server};
+
+        use crate::analyzer_service::provider_service_client::ProviderServiceClient;
+
+        let dir = TestDir::new("client-gone");
+        let config = mock_tool_project(&dir, &[]);
+        // paket converts the project slowly, the client goes away while it does.
+        std::fs::remove_file(dir.join("paket.dependencies")).unwrap();
+        dir.write("mock-tool", "#!/bin/sh\necho \"$@\" >> tool-args.out\nif [ \"$1\" = convert-from-nuget ]; then\n  sleep 1\n  echo 'source https://www.nuget.org/api/v2' > paket.dependencies\nfi\n");
+        let provider = CSharpProvider::new(dir.join("test.db"));
+        let project_slot = provider.project.clone();
+        let listener = tokio::net::TcpListener::bind("[::1]:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(provider.into_service(CSharpProvider::DEFAULT_MAX_MESSAGE_SIZE))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let mut client = ProviderServiceClient::new(
+            Channel::from_shared(format!("http://{}", addr))
+                .unwrap()
+                .connect()
+                .await
+                .unwrap(),
+        );
+
+        let init = tokio::spawn({
+            let mut client = client.clone();
+            async move { client.init(config).await }
+        });
+        let tool_args = dir.join("tool-args.out");
+        while !tool_args.exists() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        init.abort();
+        // Long enough for the conversion to finish, after which resolving would go on to
+        // install the reference assemblies.
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert_eq!(
+            std::fs::read_to_string(&tool_args).unwrap(),
+            "convert-from-nuget -f\n"
+        );
+        let project = project_slot.lock().await.clone().unwrap();
+        assert!(project.indexed.get().is_none());
+
+        // The project graph built before the client went away is still searched.
+        let response = client
+            .evaluate(evaluate_request("Demo.Mocked.*").into_inner())
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.successful, "{}", response.error);
+        assert!(response.response.unwrap().matched);
     }
 }
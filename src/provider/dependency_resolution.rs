@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::path::Path;
 use std::path::PathBuf;
@@ -8,33 +8,46 @@ use std::sync::Mutex;
 
 use anyhow::{anyhow, Error};
 use fs_extra::dir::get_size;
+use serde::Serialize;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPaths;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::StitcherConfig;
-use stack_graphs::storage::SQLiteReader;
 use stack_graphs::storage::SQLiteWriter;
-use stack_graphs::NoCancellation;
 use tokio::fs::{self, File};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::task::JoinSet;
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
+use walkdir::WalkDir;
 
+use crate::analyzer_service::{Dependency, DependencyDagItem};
+use crate::c_sharp_graph::database::{open_writer, with_busy_retry};
 use crate::c_sharp_graph::loader::add_dir_to_graph;
 use crate::c_sharp_graph::loader::SourceType;
 use crate::provider::project::Tools;
+use crate::provider::web_config::{
+    gac_dirs, locate_assembly, parse_web_config, web_config_files, AssemblyReference,
+};
+use crate::provider::AtomicCancellationFlag;
 use crate::provider::Project;
 
 const REFERNCE_ASSEMBLIES_NAME: &str = "Microsoft.NETFramework.ReferenceAssemblies";
+#[derive(Serialize)]
 pub struct Dependencies {
     pub location: PathBuf,
-    #[allow(dead_code)]
     pub name: String,
-    #[allow(dead_code)]
     pub version: String,
+    #[serde(skip)]
     pub decompiled_size: Mutex<Option<u64>>,
+    /// Where the source of the dependency is indexed from, the directories it was decompiled
+    /// into or the source the package ships with.
+    #[serde(skip)]
     pub decompiled_location: Arc<Mutex<HashSet<PathBuf>>>,
+    /// Set for assemblies paket does not know about, the DLL at `location` is decompiled into
+    /// this directory rather than the DLLs paket installed for the package.
+    #[serde(skip)]
+    pub decompile_to: Option<PathBuf>,
 }
 
 impl Debug for Dependencies {
@@ -47,7 +60,136 @@ impl Debug for Dependencies {
     }
 }
 
+/// The lowest .NET Framework a paket `restriction:` expression allows, which is the one its
+/// reference assemblies are installed for. Paket writes the expression either in prefix form,
+/// `&& (>= net45) (< net48)` or `|| (>= net40) (>= netstandard2.0)`, or in infix form,
+/// `>= net45 && < net48`, where `&&` binds tighter than `||`. An `&&` allows the highest of the
+/// lower bounds of its terms and an `||` the lowest. Upper bounds and other frameworks, such as
+/// `netstandard2.0`, are no lower bound and are left out.
+fn restriction_framework(restriction: &str) -> Option<String> {
+    // Options after the restriction are separated from it by a comma.
+    let restriction = restriction.split(',').next()?;
+    let spaced = restriction.replace('(', " ( ").replace(')', " ) ");
+    Restriction::or(&mut spaced.split_whitespace().peekable())
+}
+
+type Tokens<'a> = std::iter::Peekable<std::str::SplitWhitespace<'a>>;
+
+/// The recursive descent over the tokens of a restriction, each rule returning the lowest
+/// framework what it parsed allows.
+struct Restriction;
+
+impl Restriction {
+    fn or(tokens: &mut Tokens) -> Option<String> {
+        let mut lowest = Self::and(tokens);
+        while tokens.next_if_eq(&"||").is_some() {
+            lowest = Self::lowest(lowest, Self::and(tokens));
+        }
+        lowest
+    }
+
+    fn and(tokens: &mut Tokens) -> Option<String> {
+        let mut highest = Self::term(tokens);
+        while tokens.next_if_eq(&"&&").is_some() {
+            highest = highest.max(Self::term(tokens));
+        }
+        highest
+    }
+
+    fn term(tokens: &mut Tokens) -> Option<String> {
+        match tokens.next()? {
+            "(" => {
+                let framework = Self::or(tokens);
+                tokens.next_if_eq(&")");
+                framework
+            }
+            op @ ("&&" | "||") => {
+                let mut operands = vec![];
+                while tokens.next_if_eq(&"(").is_some() {
+                    operands.push(Self::or(tokens));
+                    tokens.next_if_eq(&")");
+                }
+                let operands = operands.into_iter().flatten();
+                if op == "&&" {
+                    operands.max()
+                } else {
+                    operands.min()
+                }
+            }
+            ">=" | "==" | "=" => tokens.next().and_then(Self::lower_bound),
+            "<" | "<=" | ">" => tokens.next().and(None),
+            framework => Self::lower_bound(framework),
+        }
+    }
+
+    fn lowest(a: Option<String>, b: Option<String>) -> Option<String> {
+        a.into_iter().chain(b).min()
+    }
+
+    /// `framework` if it names a .NET Framework version, `net45`, the only ones with reference
+    /// assemblies to install.
+    fn lower_bound(framework: &str) -> Option<String> {
+        let framework = framework.to_lowercase();
+        let version = framework.strip_prefix("net")?;
+        if version.is_empty() || !version.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        Some(framework)
+    }
+}
+
 impl Dependencies {
+    /// Parses a `nuget` line of paket.dependencies, returning the dependency and the framework
+    /// it is restricted to.
+    fn from_paket_line(location: &Path, line: &str) -> Option<(Dependencies, Option<String>)> {
+        if !line.contains("restriction") {
+            return None;
+        }
+        let parts: Vec<&str> = line.split("restriction:").collect();
+        if parts.len() != 2 {
+            return None;
+        }
+        let white_space_split: Vec<&str> = parts.first()?.split_whitespace().collect();
+        if white_space_split.len() < 4 {
+            return None;
+        }
+        let name = white_space_split.get(1)?;
+        let version = white_space_split.get(2)?;
+        let dep = Dependencies::package(location, name, version);
+        let framework = parts.get(1).and_then(|r| restriction_framework(r));
+        Some((dep, framework))
+    }
+
+    /// A nuget package paket installs under the project's packages.
+    fn package(location: &Path, name: &str, version: &str) -> Dependencies {
+        Dependencies {
+            location: location.join("packages").join(name),
+            name: name.to_string(),
+            version: version.to_string(),
+            decompiled_location: Arc::new(Mutex::new(HashSet::new())),
+            decompiled_size: Mutex::new(None),
+            decompile_to: None,
+        }
+    }
+
+    /// An assembly a `web.config` references, or a framework reference assembly, that was found
+    /// at `dll`, decompiled under the project's packages so it is indexed with the rest of the
+    /// dependencies.
+    fn from_assembly(location: &Path, reference: AssemblyReference, dll: PathBuf) -> Dependencies {
+        let decompile_to = location
+            .join("packages")
+            .join(&reference.name)
+            .join(format!("{}-decompiled", reference.name));
+        Dependencies {
+            location: dll,
+            name: reference.name,
+            version: reference.version.unwrap_or_default(),
+            decompiled_location: Arc::new(Mutex::new(HashSet::new())),
+            decompiled_size: Mutex::new(None),
+            decompile_to: Some(decompile_to),
+        }
+    }
+
     pub async fn decompile(
         &self,
         reference_assmblies: PathBuf,
@@ -55,6 +197,78 @@ impl Dependencies {
         tools: &Tools,
     ) -> Result<(), Error> {
         info!("decompiling dependency: {:?}", self);
+        let to_decompile_locations = match self.decompile_to {
+            Some(_) => vec![self.location.clone()],
+            None => self.paket_dlls(restriction).await?,
+        };
+        if to_decompile_locations.is_empty() {
+            trace!("no dll's found for dependnecy: {:?}", self);
+        }
+        let mut decompiled_files: HashSet<PathBuf> = HashSet::new();
+        for file_to_decompile in to_decompile_locations {
+            let decompiled_file = self
+                .decompile_file(&reference_assmblies, file_to_decompile, tools)
+                .await?;
+            decompiled_files.insert(decompiled_file);
+        }
+
+        info!(
+            "deompiled {} files for dependnecy: {:?}",
+            decompiled_files.len(),
+            self
+        );
+        let mut dir_size: u64 = 0;
+        for dir_path in decompiled_files.iter() {
+            dir_size += get_size(dir_path).unwrap_or_default();
+        }
+        let mut size_guard = self.decompiled_size.lock().unwrap();
+        let _ = size_guard.insert(dir_size);
+        drop(size_guard);
+
+        self.add_decompiled_locations(decompiled_files);
+
+        Ok(())
+    }
+
+    /// Indexes the source the package ships with instead of decompiling it, when it has any.
+    fn use_package_sources(&self) -> bool {
+        let sources: HashSet<PathBuf> = self.package_sources().into_iter().collect();
+        if sources.is_empty() {
+            return false;
+        }
+        info!("using the source shipped with dependency: {:?}", self);
+        let size = sources
+            .iter()
+            .map(|dir| get_size(dir).unwrap_or_default())
+            .sum();
+        let _ = self.decompiled_size.lock().unwrap().insert(size);
+        self.add_decompiled_locations(sources);
+        true
+    }
+
+    /// The C# source a package ships, under `src/` as source and symbol packages lay it out,
+    /// or under `contentFiles/cs/` for source-only packages. Assemblies found through a
+    /// `web.config` have no package to look in.
+    fn package_sources(&self) -> Vec<PathBuf> {
+        if self.decompile_to.is_some() {
+            return vec![];
+        }
+        [
+            self.location.join("src"),
+            self.location.join("contentFiles").join("cs"),
+        ]
+        .into_iter()
+        .filter(|dir| {
+            WalkDir::new(dir)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .any(|e| e.file_type().is_file() && e.path().extension().is_some_and(|x| x == "cs"))
+        })
+        .collect()
+    }
+
+    /// The DLLs paket installed for the package that best match the restriction.
+    async fn paket_dlls(&self, restriction: String) -> Result<Vec<PathBuf>, Error> {
         let dep_package_dir = self.location.to_owned();
         if !dep_package_dir.is_dir() || !dep_package_dir.exists() {
             return Err(anyhow!("invalid package path: {:?}", dep_package_dir));
@@ -81,39 +295,14 @@ impl Dependencies {
                 return Err(anyhow!("did not find a cache file for dep: {:?}", self));
             }
         };
-        if to_decompile_locations.is_empty() {
-            trace!("no dll's found for dependnecy: {:?}", self);
-        }
-        let mut decompiled_files: HashSet<PathBuf> = HashSet::new();
-        for file_to_decompile in to_decompile_locations {
-            let decompiled_file = self
-                .decompile_file(
-                    &reference_assmblies,
-                    file_to_decompile,
-                    tools.ilspy_cmd.clone(),
-                )
-                .await?;
-            decompiled_files.insert(decompiled_file);
-        }
-
-        info!(
-            "deompiled {} files for dependnecy: {:?}",
-            decompiled_files.len(),
-            self
-        );
-        let mut dir_size: u64 = 0;
-        for dir_path in decompiled_files.iter() {
-            dir_size += get_size(dir_path).unwrap_or_default();
-        }
-        let mut size_guard = self.decompiled_size.lock().unwrap();
-        let _ = size_guard.insert(dir_size);
-        drop(size_guard);
+        Ok(to_decompile_locations)
+    }
 
+    /// Adds to the directories this dependency was decompiled into, keeping the ones already
+    /// recorded by an earlier decompile.
+    fn add_decompiled_locations(&self, locations: HashSet<PathBuf>) {
         let mut guard = self.decompiled_location.lock().unwrap();
-        *guard = decompiled_files;
-        drop(guard);
-
-        Ok(())
+        guard.extend(locations);
     }
 
     async fn read_packet_cache_file(
@@ -167,23 +356,34 @@ impl Dependencies {
         &self,
         reference_assmblies: &PathBuf,
         file_to_decompile: PathBuf,
-        ilspycmd: PathBuf,
+        tools: &Tools,
     ) -> Result<PathBuf, Error> {
-        let decompile_name = match self.location.as_path().file_name() {
-            Some(n) => {
-                let mut x = n.to_owned().to_string_lossy().into_owned();
-                x.push_str("-decompiled");
-                x
-            }
-            None => return Err(anyhow!("unable to dependency name")),
-        };
-        let decompile_out_name = match file_to_decompile.parent() {
-            Some(p) => p.join(decompile_name),
+        let decompile_out_name = match &self.decompile_to {
+            Some(dir) => dir.clone(),
             None => {
-                return Err(anyhow!("unable to get path"));
+                let decompile_name = match self.location.as_path().file_name() {
+                    Some(n) => {
+                        let mut x = n.to_owned().to_string_lossy().into_owned();
+                        x.push_str("-decompiled");
+                        x
+                    }
+                    None => return Err(anyhow!("unable to dependency name")),
+                };
+                match file_to_decompile.parent() {
+                    Some(p) => p.join(decompile_name),
+                    None => {
+                        return Err(anyhow!("unable to get path"));
+                    }
+                }
             }
         };
-        let decompile_output = Command::new(ilspycmd)
+        // An assembly's location is the DLL itself, which cannot be the working directory.
+        let working_dir = match self.decompile_to {
+            Some(_) => file_to_decompile.parent().unwrap_or(&self.location),
+            None => &self.location,
+        };
+        let decompile_output = Command::new(&tools.ilspy_cmd)
+            .envs(&tools.env)
             .arg("-o")
             .arg(&decompile_out_name)
             .arg("-r")
@@ -191,10 +391,10 @@ impl Dependencies {
             .arg("--no-dead-code")
             .arg("--no-dead-stores")
             .arg("-lv")
-            .arg("CSharp7_3")
+            .arg(tools.ilspy_language_version)
             .arg("-p")
             .arg(&file_to_decompile)
-            .current_dir(&self.location)
+            .current_dir(working_dir)
             .output()?;
 
         trace!("decompile output: {:?}", decompile_output);
@@ -203,9 +403,141 @@ impl Dependencies {
     }
 }
 
+/// Pairs every directory the dependencies were decompiled into with the first dependency, in
+/// order, that produced it. Dependencies that share an output directory would otherwise index
+/// it once each, writing the same files to the database concurrently.
+fn decompiled_locations_by_dependency(deps: &[Dependencies]) -> Vec<(PathBuf, &Dependencies)> {
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let mut locations = vec![];
+    for d in deps {
+        let mut decompiled: Vec<PathBuf> = d
+            .decompiled_location
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        decompiled.sort();
+        for location in decompiled {
+            if seen.insert(location.clone()) {
+                locations.push((location, d));
+            } else {
+                debug!(
+                    "{:?} already indexed for another dependency of {}",
+                    location, d.name
+                );
+            }
+        }
+    }
+    locations
+}
+
+/// A package paket.lock resolved, with the names of the packages it depends on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LockedPackage {
+    version: String,
+    dependencies: Vec<String>,
+}
+
+/// The nuget packages of each group of a paket.lock, by name. A package is listed at four
+/// spaces with the version it resolved to, the packages it depends on follow it at six with
+/// their version constraint.
+fn parse_paket_lock(contents: &str) -> HashMap<String, HashMap<String, LockedPackage>> {
+    let mut groups: HashMap<String, HashMap<String, LockedPackage>> = HashMap::new();
+    let mut group = "Main".to_string();
+    let mut in_nuget = false;
+    let mut package: Option<String> = None;
+    for line in contents.lines() {
+        if let Some(name) = line.strip_prefix("GROUP ") {
+            group = name.trim().to_string();
+            continue;
+        }
+        if !line.starts_with(' ') {
+            in_nuget = line.trim() == "NUGET";
+            package = None;
+            continue;
+        }
+        let Some(name) = line.split_whitespace().next().filter(|_| in_nuget) else {
+            continue;
+        };
+        let packages = groups.entry(group.clone()).or_default();
+        if line.starts_with("      ") {
+            if let Some(locked) = package.as_ref().and_then(|p| packages.get_mut(p)) {
+                locked.dependencies.push(name.to_string());
+            }
+        } else if line.starts_with("    ") {
+            let version = line
+                .split_once('(')
+                .and_then(|(_, rest)| rest.split_once(')'))
+                .map_or("", |(version, _)| version);
+            // A package is resolved once per group, should it be listed again the first
+            // version stays and what it depends on is not mixed into it.
+            if packages.contains_key(name) {
+                debug!("{} is listed twice in group {}", name, group);
+                package = None;
+                continue;
+            }
+            packages.insert(
+                name.to_string(),
+                LockedPackage {
+                    version: version.to_string(),
+                    dependencies: vec![],
+                },
+            );
+            package = Some(name.to_string());
+        }
+    }
+    groups
+}
+
+/// The nuget packages paket.dependencies asks for directly, with the group they are in.
+fn paket_direct_dependencies(contents: &str) -> Vec<(String, String)> {
+    let mut group = "Main".to_string();
+    let mut direct = vec![];
+    for line in contents.lines() {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("group"), Some(name)) => group = name.to_string(),
+            (Some("nuget"), Some(name)) => direct.push((group.clone(), name.to_string())),
+            _ => {}
+        }
+    }
+    direct
+}
+
+/// The tree of packages `name` pulls in. A package already on the way down from the direct
+/// dependency is not followed again, so a cycle in the lock file ends rather than recursing.
+fn dependency_dag_item(
+    packages: &HashMap<String, LockedPackage>,
+    name: &str,
+    indirect: bool,
+    path: &mut Vec<String>,
+) -> DependencyDagItem {
+    let locked = packages.get(name);
+    let mut added_deps = vec![];
+    path.push(name.to_string());
+    for dependency in locked.iter().flat_map(|l| l.dependencies.iter()) {
+        if path.contains(dependency) {
+            debug!("dependency cycle through {} at {}", dependency, name);
+            continue;
+        }
+        added_deps.push(dependency_dag_item(packages, dependency, true, path));
+    }
+    path.pop();
+    DependencyDagItem {
+        key: Some(Dependency {
+            name: name.to_string(),
+            version: locked.map(|l| l.version.clone()).unwrap_or_default(),
+            indirect,
+            ..Default::default()
+        }),
+        added_deps,
+    }
+}
+
 impl Project {
     #[tracing::instrument]
-    pub async fn resolve(&self) -> Result<(), Error> {
+    pub async fn resolve(&self, cancellation_flag: &AtomicCancellationFlag) -> Result<(), Error> {
         // determine if the paket.dependencies already exists, if it does then we don't need to
         // convert.
         let paket_deps_file = self.location.clone().join("paket.dependencies");
@@ -215,6 +547,7 @@ impl Project {
             // Need to convert and download all DLL's
             //TODO: Add paket location as a provider specific config.
             let paket_output = Command::new(&self.tools.paket_cmd)
+                .envs(&self.tools.env)
                 .args(["convert-from-nuget", "-f"])
                 .current_dir(&self.location)
                 .output()?;
@@ -224,10 +557,20 @@ impl Project {
                 return Err(Error::msg("paket command did not succeed"));
             }
         }
+        if cancellation_flag.is_cancelled() {
+            return Err(anyhow!("dependency resolution cancelled"));
+        }
 
-        let (reference_assembly_path, highest_restriction, deps) = self
+        let (reference_assembly_path, highest_restriction, mut deps) = self
             .read_packet_dependency_file(paket_deps_file.as_path())
             .await?;
+        let web_config_deps = self.web_config_dependencies(&deps, &gac_dirs());
+        deps.extend(web_config_deps);
+        if self.index_reference_assemblies {
+            let reference_deps =
+                self.reference_assembly_dependencies(&reference_assembly_path, &deps);
+            deps.extend(reference_deps);
+        }
         debug!(
             "got: {:?} -- {:?}",
             reference_assembly_path, highest_restriction
@@ -237,7 +580,11 @@ impl Project {
             let reference_assmblies = reference_assembly_path.clone();
             let restriction = highest_restriction.clone();
             let tools = self.tools.clone();
+            let prefer_source_packages = self.prefer_source_packages;
             set.spawn(async move {
+                if prefer_source_packages && d.use_package_sources() {
+                    return d;
+                }
                 let decomp = d.decompile(reference_assmblies, restriction, &tools).await;
                 if let Err(e) = decomp {
                     error!("could not decompile - {:?}", e);
@@ -248,6 +595,10 @@ impl Project {
         // reset deps, as all the deps should be moved into the threads.
         let mut deps = vec![];
         while let Some(res) = set.join_next().await {
+            if cancellation_flag.is_cancelled() {
+                set.abort_all();
+                return Err(anyhow!("dependency resolution cancelled"));
+            }
             match res {
                 Ok(d) => {
                     deps.push(d);
@@ -269,86 +620,90 @@ impl Project {
         Ok(())
     }
 
-    pub async fn load_to_database(&self) -> Result<(), Error> {
+    pub async fn load_to_database(
+        &self,
+        cancellation_flag: &AtomicCancellationFlag,
+    ) -> Result<(), Error> {
         let shared_deps = Arc::clone(&self.dependencies);
         let mut x = shared_deps.lock().await;
         let mut set = JoinSet::new();
         if let Some(ref mut vec) = *x {
             // For each dependnecy in the list we will try and load the decompiled files
             // Into the stack graph database.
-            for d in vec {
+            for (file, d) in decompiled_locations_by_dependency(vec) {
                 let size = d.decompiled_size.lock().unwrap().unwrap_or_default();
-                let decompiled_locations: Arc<Mutex<HashSet<PathBuf>>> =
-                    Arc::clone(&d.decompiled_location);
-                let decompiled_locations = decompiled_locations.lock().unwrap();
-                let decompiled_files = &(*decompiled_locations);
-                for decompiled_file in decompiled_files {
-                    let file = decompiled_file.clone();
-                    let lc = self.source_language_config.clone();
-                    let db_path = self.db_path.clone();
-                    let dep_name = d.name.clone();
-                    set.spawn(async move {
-                        info!(
-                            "indexing dep: {} with size: {} into a graph",
-                            dep_name, &size
-                        );
-                        let mut graph = StackGraph::new();
-                        // We need to make sure that the symols for source type are the first
-                        // symbols, so that they match what is in the builtins.
-                        let (_, _) = SourceType::load_symbols_into_graph(&mut graph);
-                        // remove mutability
-                        let graph = graph;
-                        let lc_guard = lc.read().await;
-                        let lc = match lc_guard.as_ref() {
-                            Some(x) => x,
-                            None => {
-                                return Err(anyhow!("unable to get source language config"));
-                            }
-                        };
-
-                        let graph = add_dir_to_graph(
-                            &file,
-                            &lc.dependnecy_type_node_info,
-                            &lc.language_config,
-                            graph,
-                        )?;
-                        drop(lc_guard);
-                        let mut db: SQLiteWriter = SQLiteWriter::open(db_path)?;
-                        for (file_path, tag) in graph.file_to_tag.clone() {
-                            let file_str = file_path.to_string_lossy();
-                            let file_handle = graph
-                                .stack_graph
-                                .get_file(&file_str)
-                                .ok_or(anyhow!("unable to get file"))?;
-                            let mut partials = PartialPaths::new();
-                            let mut paths: Vec<PartialPath> = vec![];
-                            let stats =
-                                ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
-                                    &graph.stack_graph,
-                                    &mut partials,
-                                    file_handle,
-                                    StitcherConfig::default().with_collect_stats(true),
-                                    &NoCancellation,
-                                    |_, _, p| paths.push(p.clone()),
-                                )?;
+                let lc = self.source_language_config.clone();
+                let db_path = self.db_path.clone();
+                let dep_name = d.name.clone();
+                let max_file_bytes = self.max_decompiled_file_bytes;
+                let cancellation_flag = cancellation_flag.clone();
+                set.spawn(async move {
+                    info!(
+                        "indexing dep: {} with size: {} into a graph",
+                        dep_name, &size
+                    );
+                    let mut graph = StackGraph::new();
+                    // We need to make sure that the symols for source type are the first
+                    // symbols, so that they match what is in the builtins.
+                    let (_, _) = SourceType::load_symbols_into_graph(&mut graph);
+                    // remove mutability
+                    let graph = graph;
+                    let lc_guard = lc.read().await;
+                    let lc = match lc_guard.as_ref() {
+                        Some(x) => x,
+                        None => {
+                            return Err(anyhow!("unable to get source language config"));
+                        }
+                    };
+
+                    let graph = add_dir_to_graph(
+                        &file,
+                        &lc.dependnecy_type_node_info,
+                        &lc.language_config,
+                        graph,
+                        max_file_bytes,
+                        &cancellation_flag,
+                    )?;
+                    drop(lc_guard);
+                    let mut db: SQLiteWriter = open_writer(&db_path)?;
+                    for (file_path, tag) in graph.file_to_tag.clone() {
+                        let file_str = file_path.to_string_lossy();
+                        let file_handle = graph
+                            .stack_graph
+                            .get_file(&file_str)
+                            .ok_or(anyhow!("unable to get file"))?;
+                        let mut partials = PartialPaths::new();
+                        let mut paths: Vec<PartialPath> = vec![];
+                        let stats =
+                            ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                                &graph.stack_graph,
+                                &mut partials,
+                                file_handle,
+                                StitcherConfig::default().with_collect_stats(true),
+                                &cancellation_flag,
+                                |_, _, p| paths.push(p.clone()),
+                            )?;
+                        with_busy_retry(|| {
                             db.store_result_for_file(
                                 &graph.stack_graph,
                                 file_handle,
                                 &tag,
                                 &mut partials,
                                 &paths,
-                            )?;
-                            trace!("stats for stitiching: {:?} - paths: {}", stats, paths.len(),);
-                        }
-                        debug!(
-                            "stats for dependency: {:?}, files indexed {:?}",
-                            dep_name, graph.files_loaded,
-                        );
-                        Ok((graph, dep_name))
-                    });
-                }
+                            )
+                        })?;
+                        trace!("stats for stitiching: {:?} - paths: {}", stats, paths.len(),);
+                    }
+                    debug!(
+                        "stats for dependency: {:?}, files indexed {:?}",
+                        dep_name, graph.files_loaded,
+                    );
+                    Ok((graph, dep_name))
+                });
             }
         }
+        let mut files_skipped = 0;
+        let mut files_unreadable = 0;
         for res in set.join_all().await {
             let (init_graph, dep_name) = match res {
                 Ok((i, dep_name)) => (i, dep_name),
@@ -363,15 +718,27 @@ impl Project {
                 "loaded {} files for dep: {:?} into database",
                 init_graph.files_loaded, dep_name
             );
+            files_skipped += init_graph.files_skipped;
+            files_unreadable += init_graph.files_unreadable;
+            self.add_diagnostics(init_graph.diagnostics);
+        }
+        if files_skipped > 0 {
+            warn!(
+                "skipped {} decompiled files larger than {} bytes",
+                files_skipped,
+                self.max_decompiled_file_bytes.unwrap_or_default()
+            );
+        }
+        if files_unreadable > 0 {
+            warn!(
+                "skipped {} decompiled files that could not be read",
+                files_unreadable
+            );
         }
 
-        let mut graph_guard = self
-            .graph
-            .lock()
-            .expect("project may not have been initialized");
         info!("adding all dependency and source to graph");
-        let mut db_reader = SQLiteReader::open(&self.db_path)?;
-        db_reader.load_graphs_for_file_or_directory(&self.location, &NoCancellation)?;
+        let mut db_reader = self.open_reader(&self.db_path)?;
+        db_reader.load_graphs_for_file_or_directory(&self.location, cancellation_flag)?;
         // Once you read the data back from the DB, you will not get the source information
         // This is not currently stored in the database
         // There may be a way to re-attach this but for now we will relay code-snipper.
@@ -379,15 +746,180 @@ impl Project {
         let read_graph = read_graph.to_serializable();
         let mut new_graph = StackGraph::new();
         read_graph.load_into(&mut new_graph)?;
+        self.merge_extra_databases(&mut new_graph, cancellation_flag)?;
         debug!(
             "new graph: {:?}",
             databse.to_serializable(&new_graph, partials)
         );
-        let _ = graph_guard.insert(new_graph);
+        self.set_graph(new_graph);
 
         Ok(())
     }
 
+    /// The assemblies the project's `web.config` files reference that paket did not install,
+    /// legacy ASP.NET applications bind to assemblies from `bin/` or the GAC that
+    /// `convert-from-nuget` never sees.
+    fn web_config_dependencies(
+        &self,
+        paket_deps: &[Dependencies],
+        gac_dirs: &[PathBuf],
+    ) -> Vec<Dependencies> {
+        let mut seen: HashSet<String> = paket_deps.iter().map(|d| d.name.clone()).collect();
+        let mut deps = vec![];
+        for web_config in web_config_files(&self.location) {
+            let references = match std::fs::read_to_string(&web_config)
+                .map_err(Error::new)
+                .and_then(|contents| parse_web_config(&contents))
+            {
+                Ok(references) => references,
+                Err(e) => {
+                    warn!("unable to read assemblies from {:?}: {}", web_config, e);
+                    continue;
+                }
+            };
+            for reference in references {
+                if seen.contains(&reference.name) {
+                    continue;
+                }
+                let Some(dll) = locate_assembly(&reference, &web_config, &self.location, gac_dirs)
+                else {
+                    debug!(
+                        "unable to find assembly {} from {:?}",
+                        reference.name, web_config
+                    );
+                    continue;
+                };
+                // Already decompiled as part of the package paket installed it with, or one of
+                // the framework's reference assemblies, which ilspy only resolves against.
+                if paket_deps.iter().any(|d| dll.starts_with(&d.location))
+                    || dll.components().any(|c| {
+                        c.as_os_str()
+                            .to_string_lossy()
+                            .starts_with(REFERNCE_ASSEMBLIES_NAME)
+                    })
+                {
+                    continue;
+                }
+                info!(
+                    "found assembly {} from {:?} at {:?}",
+                    reference.name, web_config, dll
+                );
+                seen.insert(reference.name.clone());
+                deps.push(Dependencies::from_assembly(&self.location, reference, dll));
+            }
+        }
+        deps
+    }
+
+    /// Each of the framework's reference assemblies in `reference_assembly_path` that is not
+    /// already a dependency, decompiled under the project's packages like the assemblies a
+    /// `web.config` references.
+    fn reference_assembly_dependencies(
+        &self,
+        reference_assembly_path: &Path,
+        deps: &[Dependencies],
+    ) -> Vec<Dependencies> {
+        let entries = match std::fs::read_dir(reference_assembly_path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(
+                    "unable to read reference assemblies from {:?}: {}",
+                    reference_assembly_path, e
+                );
+                return vec![];
+            }
+        };
+        let mut dlls: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "dll"))
+            .collect();
+        dlls.sort();
+        let seen: HashSet<&str> = deps.iter().map(|d| d.name.as_str()).collect();
+        let reference_deps: Vec<Dependencies> = dlls
+            .into_iter()
+            .filter_map(|dll| {
+                let name = dll.file_stem()?.to_string_lossy().into_owned();
+                if seen.contains(name.as_str()) {
+                    return None;
+                }
+                let reference = AssemblyReference {
+                    name,
+                    version: None,
+                };
+                Some(Dependencies::from_assembly(&self.location, reference, dll))
+            })
+            .collect();
+        info!(
+            "indexing {} reference assemblies from {:?}",
+            reference_deps.len(),
+            reference_assembly_path
+        );
+        reference_deps
+    }
+
+    /// The packages paket.dependencies asks for, each with the tree of packages it pulled in
+    /// according to paket.lock.
+    pub async fn dependency_dag(&self) -> Result<Vec<DependencyDagItem>, Error> {
+        let direct = paket_direct_dependencies(&self.read_paket_file("paket.dependencies").await?);
+        let groups = parse_paket_lock(&self.read_paket_file("paket.lock").await?);
+        let edges: usize = groups
+            .values()
+            .flat_map(|packages| packages.values())
+            .map(|package| package.dependencies.len())
+            .sum();
+        debug!(
+            "paket.lock has {} edges between {} packages",
+            edges,
+            groups.values().map(HashMap::len).sum::<usize>()
+        );
+        let no_packages = HashMap::new();
+        Ok(direct
+            .iter()
+            .map(|(group, name)| {
+                let packages = groups.get(group).unwrap_or(&no_packages);
+                dependency_dag_item(packages, name, false, &mut vec![])
+            })
+            .collect())
+    }
+
+    /// A file a previous resolution left in the project, such as paket.lock.
+    async fn read_paket_file(&self, file: &str) -> Result<String, Error> {
+        let path = self.location.join(file);
+        fs::read_to_string(&path).await.map_err(|e| {
+            anyhow!(
+                "unable to read {:?}, dependencies may not have been resolved: {}",
+                path,
+                e
+            )
+        })
+    }
+
+    /// Returns the resolved dependencies as JSON. Uses the dependencies from the last
+    /// resolution if there was one, otherwise reads the packages paket.dependencies asks for
+    /// at the versions paket.lock resolved them to, without running paket.
+    pub async fn dependencies_json(&self) -> Result<String, Error> {
+        let resolved = self.dependencies.lock().await;
+        if let Some(deps) = resolved.as_ref() {
+            return Ok(serde_json::to_string_pretty(deps)?);
+        }
+        drop(resolved);
+
+        let direct = paket_direct_dependencies(&self.read_paket_file("paket.dependencies").await?);
+        let groups = parse_paket_lock(&self.read_paket_file("paket.lock").await?);
+        let deps: Vec<Dependencies> = direct
+            .iter()
+            .map(|(group, name)| {
+                let locked = groups.get(group).and_then(|packages| packages.get(name));
+                if locked.is_none() {
+                    warn!("{} of group {} is not in paket.lock", name, group);
+                }
+                let version = locked.map_or("", |locked| locked.version.as_str());
+                Dependencies::package(&self.location, name, version)
+            })
+            .collect();
+        Ok(serde_json::to_string_pretty(&deps)?)
+    }
+
     async fn read_packet_dependency_file(
         &self,
         paket_deps_file: &Path,
@@ -399,61 +931,49 @@ impl Project {
         }
         let reader = BufReader::new(file.ok().unwrap());
         let mut lines = reader.lines();
-        let mut smallest_framework = "zzzzzzzzzzzzzzz".to_string();
+        let mut smallest_framework: Option<String> = None;
         let mut deps: Vec<Dependencies> = vec![];
         while let Some(line) = lines.next_line().await? {
-            if !line.contains("restriction") {
-                continue;
-            }
-            let parts: Vec<&str> = line.split("restriction:").collect();
-            if parts.len() != 2 {
+            let Some((dep, framework)) = Dependencies::from_paket_line(&self.location, &line)
+            else {
                 continue;
-            }
-            if let Some(dep_part) = parts.first() {
-                let white_space_split: Vec<&str> = dep_part.split_whitespace().collect();
-                if white_space_split.len() < 4 {
-                    continue;
-                }
-                let mut dep_path = self.location.clone();
-                dep_path.push("packages");
-                let name = match white_space_split.get(1) {
-                    Some(n) => n,
-                    None => {
-                        continue;
-                    }
-                };
-                dep_path.push(name);
-                let version = match white_space_split.get(2) {
-                    Some(v) => v,
-                    None => {
-                        continue;
-                    }
-                };
-                let dep = Dependencies {
-                    location: dep_path,
-                    name: name.to_string(),
-                    version: version.to_string(),
-                    decompiled_location: Arc::new(Mutex::new(HashSet::new())),
-                    decompiled_size: Mutex::new(None),
-                };
-                deps.push(dep);
-            }
-
-            if let Some(ref_name) = parts.get(1) {
-                let n = ref_name.to_string();
-                if let Some(framework) = n.split_whitespace().last() {
-                    let framework_string = framework.to_string();
-                    if framework_string < smallest_framework {
-                        smallest_framework = framework_string;
-                    }
-                }
-            }
+            };
+            deps.push(dep);
+            smallest_framework = Restriction::lowest(smallest_framework, framework);
         }
         drop(lines);
+        // Without a restriction to a .NET Framework there is nothing to tell which reference
+        // assemblies the project builds against.
+        let smallest_framework = match (smallest_framework, &self.default_framework) {
+            (Some(framework), _) => framework,
+            // Nothing is decompiled, the reference assemblies are not needed. The assemblies a
+            // `web.config` references are decompiled against them like paket's.
+            (None, None)
+                if deps.is_empty()
+                    && !self.index_reference_assemblies
+                    && self.web_config_dependencies(&deps, &gac_dirs()).is_empty() =>
+            {
+                return Ok((PathBuf::new(), String::new(), deps));
+            }
+            (None, Some(default)) => Restriction::lower_bound(default).ok_or_else(|| {
+                anyhow!(
+                    "default_framework {} is not a .NET Framework version such as net472",
+                    default
+                )
+            })?,
+            (None, None) => {
+                return Err(anyhow!(
+                    "no dependency in {:?} is restricted to a .NET Framework version, set \
+                     default_framework, such as net472, to the framework the project targets",
+                    paket_deps_file
+                ))
+            }
+        };
 
         // Now we we have the framework, we need to get the reference_assmblies
         let base_name = format!("{}.{}", REFERNCE_ASSEMBLIES_NAME, smallest_framework);
         let paket_reference_output = Command::new(&self.tools.paket_cmd)
+            .envs(&self.tools.env)
             .args(["add", base_name.as_str()])
             .current_dir(&self.location)
             .output()?;
@@ -493,3 +1013,789 @@ impl Project {
         Err(anyhow!("unable to get reference assembly"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prost_types::{value::Kind, Struct, Value};
+
+    use crate::c_sharp_graph::find_node::FindNode;
+    use crate::c_sharp_graph::query::QueryOptions;
+    use crate::provider::{AnalysisMode, ProviderConfig};
+    use crate::test_support::TestDir;
+
+    use super::*;
+
+    fn fixture_project() -> Project {
+        let location = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/paket");
+        Project::new(
+            location.clone(),
+            location.join("test.db"),
+            AnalysisMode::Full,
+            Tools {
+                ilspy_cmd: PathBuf::from("ilspycmd"),
+                paket_cmd: PathBuf::from("paket"),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn dependencies_json_matches_paket_dependencies() {
+        let project = fixture_project();
+        let dump: serde_json::Value =
+            serde_json::from_str(&project.dependencies_json().await.unwrap()).unwrap();
+
+        let packages = project.location.join("packages");
+        let expected = serde_json::json!([
+            {"name": "Antlr", "version": "3.4.1.9004", "location": packages.join("Antlr")},
+            {"name": "EntityFramework", "version": "6.1.3", "location": packages.join("EntityFramework")},
+            {"name": "Microsoft.AspNet.Mvc", "version": "4.0.30506", "location": packages.join("Microsoft.AspNet.Mvc")},
+        ]);
+        assert_eq!(dump, expected);
+    }
+
+    /// The tree as one line per package, indented by its depth.
+    fn dag_lines(items: &[DependencyDagItem], depth: usize) -> Vec<String> {
+        items
+            .iter()
+            .flat_map(|item| {
+                let key = item.key.as_ref().unwrap();
+                let line = format!(
+                    "{}{} {}{}",
+                    "  ".repeat(depth),
+                    key.name,
+                    key.version,
+                    if key.indirect { " (indirect)" } else { "" }
+                );
+                std::iter::once(line).chain(dag_lines(&item.added_deps, depth + 1))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn dependency_dag_follows_paket_lock() {
+        let project = fixture_project();
+        let dag = project.dependency_dag().await.unwrap();
+        assert_eq!(
+            dag_lines(&dag, 0),
+            vec![
+                "Antlr 3.4.1.9004",
+                "EntityFramework 6.1.3",
+                "Microsoft.AspNet.Mvc 4.0.30506",
+                "  Microsoft.AspNet.Razor 2.0.30506 (indirect)",
+                "  Microsoft.AspNet.WebPages 2.0.30506 (indirect)",
+                "    Microsoft.AspNet.Razor 2.0.30506 (indirect)",
+                "    Microsoft.Web.Infrastructure 1.0.0 (indirect)",
+            ]
+        );
+    }
+
+    #[test]
+    fn dependency_dag_stops_at_cycles_and_keeps_groups_apart() {
+        let lock = "NUGET
+  remote: https://www.nuget.org/api/v2
+    A (1.0)
+      B (>= 1.0)
+    B (1.0)
+      A (>= 1.0)
+    B (9.9)
+      C (>= 1.0)
+GROUP Build
+NUGET
+  remote: https://www.nuget.org/api/v2
+    B (2.0)
+GITHUB
+  remote: owner/repo
+    src/File.fs (abc123)
+";
+        let groups = parse_paket_lock(lock);
+        assert_eq!(groups["Main"]["B"].version, "1.0");
+        assert_eq!(groups["Main"]["B"].dependencies, vec!["A"]);
+        assert_eq!(groups["Build"].len(), 1);
+        let direct = paket_direct_dependencies("nuget A\ngroup Build\nnuget B 2.0\n");
+        assert_eq!(
+            direct,
+            vec![
+                ("Main".to_string(), "A".to_string()),
+                ("Build".to_string(), "B".to_string())
+            ]
+        );
+        let dag: Vec<DependencyDagItem> = direct
+            .iter()
+            .map(|(group, name)| dependency_dag_item(&groups[group], name, false, &mut vec![]))
+            .collect();
+        assert_eq!(
+            dag_lines(&dag, 0),
+            vec!["A 1.0", "  B 1.0 (indirect)", "B 2.0"]
+        );
+    }
+
+    #[tokio::test]
+    async fn dependencies_json_prefers_resolved_dependencies() {
+        let project = fixture_project();
+        let (dep, framework) = Dependencies::from_paket_line(
+            &project.location,
+            "nuget Antlr 3.4.1.9004 import_targets: false, restriction: >= net40",
+        )
+        .unwrap();
+        assert_eq!(framework.as_deref(), Some("net40"));
+        *project.dependencies.lock().await = Some(vec![dep]);
+
+        let dump: serde_json::Value =
+            serde_json::from_str(&project.dependencies_json().await.unwrap()).unwrap();
+        assert_eq!(dump.as_array().unwrap().len(), 1);
+        assert_eq!(dump[0]["name"], "Antlr");
+    }
+
+    #[test]
+    fn restrictions_pick_the_lowest_framework_they_allow() {
+        let framework = |restriction: &str| {
+            let line = format!(
+                "nuget Lib 1.0.0 import_targets: false, restriction: {}",
+                restriction
+            );
+            Dependencies::from_paket_line(Path::new("/project"), &line)
+                .unwrap()
+                .1
+        };
+        assert_eq!(framework(">= net45").as_deref(), Some("net45"));
+        assert_eq!(
+            framework("&& (>= net45) (< net48)").as_deref(),
+            Some("net45")
+        );
+        assert_eq!(framework(">= net45 && < net48").as_deref(), Some("net45"));
+        assert_eq!(
+            framework("&& (>= net40) (>= net461)").as_deref(),
+            Some("net461")
+        );
+        assert_eq!(
+            framework("|| (>= net461) (>= net40)").as_deref(),
+            Some("net40")
+        );
+        assert_eq!(framework(">= net472 || >= net45").as_deref(), Some("net45"));
+        assert_eq!(
+            framework("|| (>= netstandard2.0) (>= net45)").as_deref(),
+            Some("net45")
+        );
+        assert_eq!(
+            framework("|| (&& (>= net45) (< net47)) (>= net40)").as_deref(),
+            Some("net40")
+        );
+        assert_eq!(
+            framework(">= NET46 && < net48 || >= net472").as_deref(),
+            Some("net46")
+        );
+        assert_eq!(
+            framework(">= net45, specific_version: true").as_deref(),
+            Some("net45")
+        );
+        assert_eq!(framework(">= netstandard2.0"), None);
+        assert_eq!(framework("< net48"), None);
+    }
+
+    #[test]
+    fn decompiled_locations_merge_and_overlapping_dirs_are_indexed_once() {
+        let location = PathBuf::from("/project");
+        let dep = |line: &str| Dependencies::from_paket_line(&location, line).unwrap().0;
+        let antlr = dep("nuget Antlr 3.4.1.9004 import_targets: false, restriction: >= net40");
+        let ef = dep("nuget EntityFramework 6.1.3 import_targets: false, restriction: >= net45");
+        let shared = PathBuf::from("/project/packages/shared-decompiled");
+        let antlr_only = PathBuf::from("/project/packages/Antlr/lib/Antlr-decompiled");
+        let antlr_later = PathBuf::from("/project/packages/Antlr/lib/net40/Antlr-decompiled");
+        let ef_only = PathBuf::from("/project/packages/EntityFramework/lib/EF-decompiled");
+
+        antlr.add_decompiled_locations(HashSet::from([shared.clone(), antlr_only.clone()]));
+        ef.add_decompiled_locations(HashSet::from([shared.clone(), ef_only.clone()]));
+        // A second decompile of the same dependency adds to what it already recorded.
+        antlr.add_decompiled_locations(HashSet::from([antlr_later.clone()]));
+
+        assert_eq!(
+            *antlr.decompiled_location.lock().unwrap(),
+            HashSet::from([shared.clone(), antlr_only.clone(), antlr_later.clone()])
+        );
+        assert_eq!(
+            *ef.decompiled_location.lock().unwrap(),
+            HashSet::from([shared.clone(), ef_only.clone()])
+        );
+
+        let deps = vec![antlr, ef];
+        let by_dependency: Vec<(PathBuf, String)> = decompiled_locations_by_dependency(&deps)
+            .into_iter()
+            .map(|(location, d)| (location, d.name.clone()))
+            .collect();
+        assert_eq!(
+            by_dependency,
+            vec![
+                (antlr_only, "Antlr".to_string()),
+                (antlr_later, "Antlr".to_string()),
+                (shared, "Antlr".to_string()),
+                (ef_only, "EntityFramework".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn web_config_assemblies_paket_did_not_install_are_resolved() {
+        let location = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/web-config");
+        let project = Project::new(
+            location.clone(),
+            location.join("test.db"),
+            AnalysisMode::Full,
+            Tools {
+                ilspy_cmd: PathBuf::from("ilspycmd"),
+                paket_cmd: PathBuf::from("paket"),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        );
+        let (antlr, _) = Dependencies::from_paket_line(
+            &location,
+            "nuget Antlr 3.4.1.9004 import_targets: false, restriction: >= net40",
+        )
+        .unwrap();
+        let gac = location.join("gac/GAC_MSIL");
+
+        let deps = project.web_config_dependencies(&[antlr], std::slice::from_ref(&gac));
+
+        // Antlr3.Runtime comes with the Antlr package, System.Data.Entity is only in the
+        // reference assemblies and Missing.Assembly is nowhere to be found.
+        let resolved: Vec<(&str, &str, &Path)> = deps
+            .iter()
+            .map(|d| (d.name.as_str(), d.version.as_str(), d.location.as_path()))
+            .collect();
+        let contoso =
+            gac.join("Contoso.Logging/v4.0_1.0.0.0__31bf3856ad364e35/Contoso.Logging.dll");
+        let reporting = location.join("bin/Legacy.Reporting.dll");
+        assert_eq!(
+            resolved,
+            vec![
+                ("Contoso.Logging", "1.0.0.0", contoso.as_path()),
+                ("Legacy.Reporting", "2.1.0.0", reporting.as_path()),
+            ]
+        );
+        assert_eq!(
+            deps[1].decompile_to,
+            Some(location.join("packages/Legacy.Reporting/Legacy.Reporting-decompiled"))
+        );
+    }
+
+    #[tokio::test]
+    async fn load_to_database_skips_oversized_decompiled_files() {
+        let dir = TestDir::new("max-decompiled");
+        dir.write("Program.cs", "namespace Demo { class Program { } }");
+        let mut project = Project::new(
+            dir.to_path_buf(),
+            dir.join("test.db"),
+            AnalysisMode::Full,
+            Tools {
+                ilspy_cmd: PathBuf::from("ilspycmd"),
+                paket_cmd: PathBuf::from("paket"),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        );
+        project.max_decompiled_file_bytes = Some(1024);
+        let project = Arc::new(project);
+        project.validate_language_configuration().await.unwrap();
+        project
+            .get_project_graph(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+
+        // Decompiling happens after the source is indexed.
+        let decompiled = dir.join("packages/Huge/lib/Huge-decompiled");
+        std::fs::create_dir_all(&decompiled).unwrap();
+        std::fs::write(
+            decompiled.join("Small.cs"),
+            "namespace Huge { public class Small { } }",
+        )
+        .unwrap();
+        let members: String = (0..200)
+            .map(|i| format!("public void M{}() {{ }}\n", i))
+            .collect();
+        std::fs::write(
+            decompiled.join("Generated.cs"),
+            format!(
+                "namespace Huge {{ public class Generated {{\n{}}} }}",
+                members
+            ),
+        )
+        .unwrap();
+        let (dep, _) = Dependencies::from_paket_line(
+            &dir,
+            "nuget Huge 1.0.0 import_targets: false, restriction: >= net40",
+        )
+        .unwrap();
+        dep.add_decompiled_locations(HashSet::from([decompiled.clone()]));
+        *project.dependencies.lock().await = Some(vec![dep]);
+
+        project
+            .load_to_database(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+
+        let graph_guard = project.graph.lock().unwrap();
+        let graph = graph_guard.as_ref().unwrap();
+        let files: Vec<String> = graph
+            .iter_files()
+            .map(|f| graph[f].name().to_string())
+            .collect();
+        assert!(
+            files.contains(&decompiled.join("Small.cs").to_string_lossy().to_string()),
+            "{:?}",
+            files
+        );
+        assert!(
+            !files.iter().any(|f| f.ends_with("Generated.cs")),
+            "{:?}",
+            files
+        );
+        drop(graph_guard);
+    }
+
+    #[tokio::test]
+    async fn load_to_database_skips_decompiled_files_that_are_not_utf8() {
+        let dir = TestDir::new("invalid-utf8");
+        dir.write("Program.cs", "namespace Demo { class Program { } }");
+        let project = Arc::new(Project::new(
+            dir.to_path_buf(),
+            dir.join("test.db"),
+            AnalysisMode::Full,
+            Tools {
+                ilspy_cmd: PathBuf::from("ilspycmd"),
+                paket_cmd: PathBuf::from("paket"),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        ));
+        project.validate_language_configuration().await.unwrap();
+        project
+            .get_project_graph(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+
+        let decompiled = dir.join("packages/Odd/lib/Odd-decompiled");
+        std::fs::create_dir_all(&decompiled).unwrap();
+        std::fs::write(
+            decompiled.join("Valid.cs"),
+            "namespace Odd { public class Valid { } }",
+        )
+        .unwrap();
+        // A Latin-1 string literal, 0xE9 on its own is not UTF-8.
+        std::fs::write(
+            decompiled.join("Invalid.cs"),
+            b"namespace Odd { public class Invalid { string s = \"caf\xe9\"; } }",
+        )
+        .unwrap();
+        let (dep, _) = Dependencies::from_paket_line(
+            &dir,
+            "nuget Odd 1.0.0 import_targets: false, restriction: >= net40",
+        )
+        .unwrap();
+        dep.add_decompiled_locations(HashSet::from([decompiled.clone()]));
+        *project.dependencies.lock().await = Some(vec![dep]);
+
+        project
+            .load_to_database(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+
+        let graph_guard = project.graph.lock().unwrap();
+        let graph = graph_guard.as_ref().unwrap();
+        let files: Vec<String> = graph
+            .iter_files()
+            .map(|f| graph[f].name().to_string())
+            .collect();
+        assert!(
+            files.contains(&decompiled.join("Valid.cs").to_string_lossy().to_string()),
+            "{:?}",
+            files
+        );
+        assert!(
+            files.contains(&dir.join("Program.cs").to_string_lossy().to_string()),
+            "{:?}",
+            files
+        );
+        assert!(
+            !files.iter().any(|f| f.ends_with("Invalid.cs")),
+            "{:?}",
+            files
+        );
+        drop(graph_guard);
+    }
+
+    #[tokio::test]
+    async fn analysis_mode_selects_the_files_that_are_searched() {
+        use crate::c_sharp_graph::find_node::FindNode;
+
+        async fn matched_files(mode: AnalysisMode, name: &str) -> Vec<String> {
+            let dir = TestDir::new(name);
+            dir.write(
+                "User.cs",
+                "using Demo.Lib;\nnamespace Demo.App { class User { void Run() { Api.Call(); } } }",
+            );
+            let project = Arc::new(Project::new(
+                dir.to_path_buf(),
+                dir.join("test.db"),
+                mode,
+                Tools {
+                    ilspy_cmd: PathBuf::from("ilspycmd"),
+                    paket_cmd: PathBuf::from("paket"),
+                    env: BTreeMap::new(),
+                    ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+                },
+            ));
+            project.validate_language_configuration().await.unwrap();
+            project
+                .get_project_graph(&AtomicCancellationFlag::new())
+                .await
+                .unwrap();
+
+            let decompiled = dir.join("packages/Lib/lib/Lib-decompiled");
+            std::fs::create_dir_all(&decompiled).unwrap();
+            std::fs::write(
+                decompiled.join("Api.cs"),
+                "namespace Demo.Lib { public class Api { public static void Call() { } } }",
+            )
+            .unwrap();
+            std::fs::write(
+                decompiled.join("Helper.cs"),
+                "using Demo.Lib;\nnamespace Demo.Extra { class Helper { void Run() { Api.Call(); } } }",
+            )
+            .unwrap();
+            let (dep, _) = Dependencies::from_paket_line(
+                &dir,
+                "nuget Lib 1.0.0 import_targets: false, restriction: >= net40",
+            )
+            .unwrap();
+            dep.add_decompiled_locations(HashSet::from([decompiled]));
+            *project.dependencies.lock().await = Some(vec![dep]);
+            project
+                .load_to_database(&AtomicCancellationFlag::new())
+                .await
+                .unwrap();
+
+            let results = FindNode {
+                regex: "Demo.Lib.*".to_string(),
+                options: Default::default(),
+            }
+            .run(&project)
+            .await
+            .unwrap();
+            let mut files: Vec<String> = results
+                .into_iter()
+                .map(|r| r.file_uri.rsplit('/').next().unwrap().to_string())
+                .collect();
+            files.sort();
+            files.dedup();
+            files
+        }
+
+        assert_eq!(
+            matched_files(AnalysisMode::SourceOnly, "mode-source-only").await,
+            vec!["User.cs"]
+        );
+        assert_eq!(
+            matched_files(AnalysisMode::DependenciesOnly, "mode-dependencies-only").await,
+            vec!["Api.cs", "Helper.cs"]
+        );
+        assert_eq!(
+            matched_files(AnalysisMode::Full, "mode-full").await,
+            vec!["Api.cs", "Helper.cs", "User.cs"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn packages_with_source_are_not_decompiled() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("source-packages");
+        fs_extra::dir::copy(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/source-packages"),
+            &dir,
+            &fs_extra::dir::CopyOptions::new(),
+        )
+        .unwrap();
+        let location = dir.join("source-packages");
+        // Stands in for paket, installing an empty reference assembly package, and for ilspy,
+        // recording what it was asked to decompile.
+        let tool = dir.join("mock-tool");
+        let log = dir.join("decompiled.log");
+        std::fs::write(
+            &tool,
+            format!(
+                "#!/bin/sh\ncase \"$1\" in\n  add) mkdir -p \"packages/$2\" && echo 'D: /build/.NETFramework/v4.0' > \"packages/$2/paket-installmodel.cache\" ;;\n  -o) mkdir -p \"$2\" && echo \"$2\" >> {:?} ;;\nesac\n",
+                log
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut project = Project::new(
+            location.clone(),
+            dir.join("test.db"),
+            AnalysisMode::Full,
+            Tools {
+                ilspy_cmd: tool.clone(),
+                paket_cmd: tool.clone(),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        );
+        project.prefer_source_packages = true;
+
+        project
+            .resolve(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+
+        let packages = location.join("packages");
+        let guard = project.dependencies.lock().await;
+        let locations: BTreeMap<&str, HashSet<PathBuf>> = guard
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|d| {
+                let locations = d.decompiled_location.lock().unwrap().clone();
+                (d.name.as_str(), locations)
+            })
+            .collect();
+        let binary_decompiled = packages.join("Contoso.Binary/lib/net45/Contoso.Binary-decompiled");
+        assert_eq!(
+            locations,
+            BTreeMap::from([
+                ("Contoso.Binary", HashSet::from([binary_decompiled.clone()])),
+                (
+                    "Contoso.Text",
+                    HashSet::from([packages.join("Contoso.Text/src")])
+                ),
+            ])
+        );
+        // Only the package without source went through ilspy.
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            format!("{}\n", binary_decompiled.display())
+        );
+        drop(guard);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reference_assemblies_are_indexed_when_asked() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("reference-assemblies");
+        let location = dir.join("project");
+        std::fs::create_dir_all(&location).unwrap();
+        std::fs::write(
+            location.join("paket.dependencies"),
+            "source https://www.nuget.org/api/v2\n\nnuget Contoso.Missing 1.0.0 import_targets: false, restriction: >= net45\n",
+        )
+        .unwrap();
+        std::fs::write(
+            location.join("Page.cs"),
+            "using System.Web;\nnamespace Demo { class Page { HttpContext context; } }",
+        )
+        .unwrap();
+        // Stands in for paket, installing a reference assembly package with a single assembly,
+        // and for ilspy, decompiling it to the one type.
+        let tool = dir.join("mock-tool");
+        std::fs::write(
+            &tool,
+            "#!/bin/sh\ncase \"$1\" in\n  add) mkdir -p \"packages/$2/build/.NETFramework/v4.5\" && touch \"packages/$2/build/.NETFramework/v4.5/System.Web.dll\" && echo 'D: /build/.NETFramework/v4.5' > \"packages/$2/paket-installmodel.cache\" ;;\n  -o) mkdir -p \"$2\" && echo 'namespace System.Web { public sealed class HttpContext { } }' > \"$2/HttpContext.cs\" ;;\nesac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut project = Project::new(
+            location.clone(),
+            dir.join("test.db"),
+            AnalysisMode::Full,
+            Tools {
+                ilspy_cmd: tool.clone(),
+                paket_cmd: tool.clone(),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        );
+        project.index_reference_assemblies = true;
+        let project = Arc::new(project);
+        project.validate_language_configuration().await.unwrap();
+        project
+            .get_project_graph(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+        project
+            .resolve(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+        project
+            .load_to_database(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+
+        let results = FindNode {
+            regex: "System.Web.*".to_string(),
+            options: QueryOptions::default(),
+        }
+        .run(&project)
+        .await
+        .unwrap();
+        let declarations: Vec<_> = results
+            .iter()
+            .map(|r| (r.fqdn.as_deref(), r.file_uri.as_str()))
+            .collect();
+        assert_eq!(declarations.len(), 1, "{:?}", results);
+        assert_eq!(declarations[0].0, Some("System.Web.HttpContext"));
+        assert!(
+            declarations[0]
+                .1
+                .ends_with("packages/System.Web/System.Web-decompiled/HttpContext.cs"),
+            "{:?}",
+            declarations
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn dependencies_without_a_framework_need_the_default_framework() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("no-framework");
+        dir.write(
+            "paket.dependencies",
+            "source https://api.nuget.org/v3/index.json\n\
+             nuget Newtonsoft.Json 13.0.1 import_targets: false, restriction: >= netstandard2.0\n",
+        );
+        // Stands in for paket, records what it is asked to add and fails.
+        let tool = dir.join("mock-paket");
+        std::fs::write(&tool, "#!/bin/sh\necho \"$@\" >> paket-args.out\nexit 1\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut project = Project::new(
+            dir.to_path_buf(),
+            dir.join("test.db"),
+            AnalysisMode::Full,
+            Tools {
+                ilspy_cmd: tool.clone(),
+                paket_cmd: tool.clone(),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        );
+
+        let err = project
+            .resolve(&AtomicCancellationFlag::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("default_framework"), "{}", err);
+        assert!(!dir.join("paket-args.out").exists());
+
+        project.default_framework = Some("NET472".to_string());
+        assert!(project
+            .resolve(&AtomicCancellationFlag::new())
+            .await
+            .is_err());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("paket-args.out")).unwrap(),
+            "add Microsoft.NETFramework.ReferenceAssemblies.net472\n"
+        );
+
+        project.default_framework = Some("netstandard2.0".to_string());
+        let err = project
+            .resolve(&AtomicCancellationFlag::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("netstandard2.0"), "{}", err);
+
+        // Without any package, the assemblies a web.config references still need the
+        // framework they are decompiled against.
+        dir.write(
+            "paket.dependencies",
+            "source https://api.nuget.org/v3/index.json\n",
+        );
+        let _ = std::fs::remove_file(dir.join("paket-args.out"));
+        let web_config = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/web-config");
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        std::fs::copy(web_config.join("Web.config"), dir.join("Web.config")).unwrap();
+        std::fs::copy(
+            web_config.join("bin/Legacy.Reporting.dll"),
+            dir.join("bin/Legacy.Reporting.dll"),
+        )
+        .unwrap();
+        project.default_framework = None;
+        let err = project
+            .resolve(&AtomicCancellationFlag::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("default_framework"), "{}", err);
+        assert!(!dir.join("paket-args.out").exists());
+
+        project.default_framework = Some("net472".to_string());
+        assert!(project
+            .resolve(&AtomicCancellationFlag::new())
+            .await
+            .is_err());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("paket-args.out")).unwrap(),
+            "add Microsoft.NETFramework.ReferenceAssemblies.net472\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn tool_env_reaches_external_tools() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("tool-env");
+        // Stands in for paket, records what it was given and fails so resolve stops there.
+        let tool = dir.join("mock-tool");
+        std::fs::write(
+            &tool,
+            "#!/bin/sh\necho \"$NUGET_PACKAGES\" > tool-env.out\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let string = |s: &str| Value {
+            kind: Some(Kind::StringValue(s.to_string())),
+        };
+        let config = Struct {
+            fields: BTreeMap::from([
+                ("ilspy_cmd".to_string(), string(tool.to_str().unwrap())),
+                ("paket_cmd".to_string(), string(tool.to_str().unwrap())),
+                (
+                    "tool_env".to_string(),
+                    Value {
+                        kind: Some(Kind::StructValue(Struct {
+                            fields: BTreeMap::from([(
+                                "NUGET_PACKAGES".to_string(),
+                                string("/cache/nuget"),
+                            )]),
+                        })),
+                    },
+                ),
+            ]),
+        };
+        let tools = ProviderConfig::from_struct(&Some(config))
+            .unwrap()
+            .tools()
+            .unwrap();
+        let project = Project::new(
+            dir.to_path_buf(),
+            dir.join("test.db"),
+            AnalysisMode::Full,
+            tools,
+        );
+
+        assert!(project
+            .resolve(&AtomicCancellationFlag::new())
+            .await
+            .is_err());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("tool-env.out")).unwrap(),
+            "/cache/nuget\n"
+        );
+    }
+}
@@ -1,7 +1,16 @@
+mod cancellation;
+mod config;
 mod csharp;
 mod dependency_resolution;
 mod project;
+mod self_test;
+mod web_config;
 
-pub use csharp::CSharpProvider;
+pub use cancellation::AtomicCancellationFlag;
+pub use config::ProviderConfig;
+pub use csharp::{capabilities_manifest, CSharpProvider};
 pub use project::AnalysisMode;
 pub use project::Project;
+pub use project::QueryMode;
+pub use project::Tools;
+pub use self_test::run_self_test;
@@ -1,38 +1,117 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fmt::Debug,
-    path::PathBuf,
-    str::FromStr,
+    fs::File,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
 use anyhow::{anyhow, Error};
-use prost_types::{Struct, Value};
+use serde::Deserialize;
 use stack_graphs::{
-    graph::StackGraph, serde::StackGraph as serialize_stack_graph, stitching::ForwardCandidates,
-    storage::SQLiteReader, NoCancellation,
+    arena::Handle,
+    graph::{File as GraphFile, Node, StackGraph},
+    partial::{PartialPath, PartialPaths},
+    serde::{Filter, StackGraph as serialize_stack_graph},
+    stitching::ForwardCandidates,
+    storage::{FileStatus, SQLiteReader, StorageError},
 };
-use tokio::sync::{Mutex as TokioMutex, RwLock};
-use tracing::debug;
-use which::which;
+use tokio::sync::{Mutex as TokioMutex, OnceCell, RwLock};
+use tracing::{debug, warn};
+use tree_sitter_stack_graphs::NoCancellation;
 
+use crate::c_sharp_graph::database::{
+    index_stamp, open_reader, open_writer, remove_database, with_busy_retry, ReadPragmas,
+    INDEX_FORMAT_VERSION,
+};
 use crate::c_sharp_graph::language_config::SourceNodeLanguageConfiguration;
-use crate::c_sharp_graph::loader::{init_stack_graph, SourceType};
+use crate::c_sharp_graph::loader::{
+    declares_included_namespace, init_stack_graph, is_indexed_source, load_graph_for_file,
+    FileDiagnostic, IndexOptions, SourceType,
+};
+use crate::c_sharp_graph::query::{NamespaceFiles, NamespaceIndex};
 use crate::provider::dependency_resolution::Dependencies;
+use crate::provider::AtomicCancellationFlag;
 
 pub struct Project {
     pub location: PathBuf,
     pub db_path: PathBuf,
+    /// Databases indexed ahead of time, for example per sub-solution, whose graphs are merged
+    /// into the project graph. They are only read, indexing writes to `db_path`.
+    pub extra_db_paths: Vec<PathBuf>,
     pub dependencies: Arc<TokioMutex<Option<Vec<Dependencies>>>>,
     pub graph: Arc<Mutex<Option<StackGraph>>>,
-    pub source_language_config: Arc<RwLock<Option<SourceNodeLanguageConfiguration>>>,
+    /// Built from `graph` by the first query after it is set.
+    pub namespace_index: Arc<Mutex<Option<NamespaceIndex>>>,
+    /// Kept in place of `graph` in `QueryMode::DbBacked`, to read back only the files a query
+    /// searches.
+    pub namespace_files: Arc<Mutex<Option<NamespaceFiles>>>,
+    pub source_language_config: Arc<RwLock<Option<Arc<SourceNodeLanguageConfiguration>>>>,
     pub analysis_mode: AnalysisMode,
     pub tools: Tools,
+    /// Which of the files under `location` are indexed.
+    pub index_options: IndexOptions,
+    /// The .NET Framework whose reference assemblies are used when no dependency is restricted
+    /// to one, `net472`. Resolving such a project fails when not set.
+    pub default_framework: Option<String>,
+    /// Decompiled files larger than this are not indexed, a single decompiled assembly can be
+    /// megabytes of code that dominates indexing. Unlimited when not set.
+    pub max_decompiled_file_bytes: Option<u64>,
+    /// Index the source a package ships with, when it has any, instead of decompiling it. The
+    /// source is what the package was built from, decompiled code only approximates it.
+    pub prefer_source_packages: bool,
+    /// Decompile and index the framework's reference assemblies as well, so the definitions of
+    /// BCL types such as `System.Web.HttpContext` can be matched. Off by default, the reference
+    /// assemblies are a few hundred DLLs that take far longer to index than the project.
+    pub index_reference_assemblies: bool,
+    /// Build the graph on the first query rather than during init, for analyzers that start
+    /// many providers and should not wait on each one to index.
+    pub lazy_index: bool,
+    /// Set once the graph is built and the dependencies are loaded, so that it happens once.
+    pub indexed: OnceCell<()>,
+    /// Once indexed, read the databases through and build the namespace index so the first
+    /// query is not slowed down by a cold start.
+    pub warm_cache: bool,
+    /// The databases were indexed ahead of time with the project and its dependencies, the
+    /// graph is only read back from them. Dependencies are not resolved or decompiled, so
+    /// `tools` are never run and do not have to be installed.
+    pub use_existing_db: bool,
+    /// Whether `graph` is kept between queries or every query reads it from the databases.
+    pub query_mode: QueryMode,
+    /// Bytes of the databases SQLite caches while reading them, on the heap. Scaled to the
+    /// size of the database when not set, see `ReadPragmas::for_database`.
+    pub cache_size: Option<u64>,
+    /// Bytes of the databases mapped into memory while reading them. Mapped pages count
+    /// towards the memory of the process as they are read, but they are the operating
+    /// system's file cache and are given back under memory pressure. Scaled to the size of
+    /// the database when not set, `0` turns mapping off.
+    pub mmap_size: Option<u64>,
+    /// Files of the source or the dependencies that could not be parsed or built into the
+    /// graph when they were indexed, and are not searched.
+    pub diagnostics: Arc<Mutex<Vec<FileDiagnostic>>>,
 }
 
 #[derive(Eq, PartialEq, Debug)]
 pub enum AnalysisMode {
     Full,
     SourceOnly,
+    /// Only the decompiled dependencies are searched, for auditing the API surface of the
+    /// libraries an application uses rather than the application itself.
+    DependenciesOnly,
+}
+
+/// Where queries read the graph from.
+#[derive(Deserialize, Eq, PartialEq, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMode {
+    /// The graph is kept in memory once built, queries do not touch the databases.
+    #[default]
+    InMemory,
+    /// The graph is dropped once it is written to the databases, only the namespaces each
+    /// file imports or declares are kept. A query reads back the files that import or declare
+    /// a namespace it searches, so only those are held and only while it runs. Queries are
+    /// slower for it, but a large project is never held in memory as a whole.
+    DbBacked,
 }
 
 impl From<&str> for AnalysisMode {
@@ -40,6 +119,7 @@ impl From<&str> for AnalysisMode {
         match value {
             "full" => AnalysisMode::Full,
             "source-only" => AnalysisMode::SourceOnly,
+            "dependencies-only" => AnalysisMode::DependenciesOnly,
             _ => AnalysisMode::Full,
         }
     }
@@ -49,6 +129,7 @@ impl From<&String> for AnalysisMode {
         match value.as_str() {
             "full" => AnalysisMode::Full,
             "source-only" => AnalysisMode::SourceOnly,
+            "dependencies-only" => AnalysisMode::DependenciesOnly,
             _ => AnalysisMode::Full,
         }
     }
@@ -58,6 +139,7 @@ impl From<String> for AnalysisMode {
         match value.as_str() {
             "full" => AnalysisMode::Full,
             "source-only" => AnalysisMode::SourceOnly,
+            "dependencies-only" => AnalysisMode::DependenciesOnly,
             _ => AnalysisMode::Full,
         }
     }
@@ -74,17 +156,50 @@ impl Debug for Project {
     }
 }
 
+/// The files `Project::reindex_files` changed in the index.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FileChanges {
+    pub added: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
 #[derive(Clone)]
 pub struct Tools {
     pub ilspy_cmd: PathBuf,
     pub paket_cmd: PathBuf,
+    /// Set on every external command on top of the server's own environment, for things like
+    /// `HTTP_PROXY` or `NUGET_PACKAGES`.
+    pub env: BTreeMap<String, String>,
+    /// The C# version ilspycmd writes decompiled code in, one of `ILSPY_LANGUAGE_VERSIONS`.
+    pub ilspy_language_version: &'static str,
+}
+
+impl Tools {
+    pub const DEFAULT_ILSPY_LANGUAGE_VERSION: &str = "CSharp7_3";
+    /// The values ilspycmd's `-lv` accepts.
+    pub const ILSPY_LANGUAGE_VERSIONS: &[&str] = &[
+        "CSharp1",
+        "CSharp2",
+        "CSharp3",
+        "CSharp4",
+        "CSharp5",
+        "CSharp6",
+        "CSharp7",
+        "CSharp7_1",
+        "CSharp7_2",
+        "CSharp7_3",
+        "CSharp8_0",
+        "CSharp9_0",
+        "CSharp10_0",
+        "CSharp11_0",
+        "CSharp12_0",
+        "Preview",
+        "Latest",
+    ];
 }
 
 impl Project {
-    const ILSPY_CMD_LOC_KEY: &str = "ilspy_cmd";
-    const PAKET_CMD_LOC_KEY: &str = "paket_cmd";
-    const ILSPY_CMD: &str = "ilspy";
-    const PAKET_CMD: &str = "paket";
     pub fn new(
         location: PathBuf,
         db_path: PathBuf,
@@ -94,74 +209,63 @@ impl Project {
         Project {
             location,
             db_path,
+            extra_db_paths: vec![],
             dependencies: Arc::new(TokioMutex::new(None)),
             graph: Arc::new(Mutex::new(None)),
+            namespace_index: Arc::new(Mutex::new(None)),
+            namespace_files: Arc::new(Mutex::new(None)),
             source_language_config: Arc::new(RwLock::new(None)),
             analysis_mode,
             tools,
-        }
-    }
-
-    pub fn get_tools(specific_provider_config: &Option<Struct>) -> Result<Tools, Error> {
-        match specific_provider_config {
-            Some(specific_provider_config) => {
-                let ilspy_cmd = match specific_provider_config.fields.get(Self::ILSPY_CMD_LOC_KEY) {
-                    Some(Value {
-                        kind: Some(prost_types::value::Kind::StringValue(s)),
-                    }) => {
-                        let p = PathBuf::from_str(s)?;
-                        if p.exists() {
-                            p
-                        } else {
-                            return Err(anyhow!("not valid ilspycmd"));
-                        }
-                    }
-                    None => which(Self::ILSPY_CMD)?,
-                    _ => {
-                        return Err(anyhow!("not valid ilspycmd"));
-                    }
-                };
-                let paket_cmd = match specific_provider_config.fields.get(Self::PAKET_CMD_LOC_KEY) {
-                    Some(Value {
-                        kind: Some(prost_types::value::Kind::StringValue(s)),
-                    }) => {
-                        let p = PathBuf::from_str(s)?;
-                        if p.exists() {
-                            p
-                        } else {
-                            return Err(anyhow!("not valid ilspycmd"));
-                        }
-                    }
-                    None => which::which(Self::PAKET_CMD)?,
-                    _ => {
-                        return Err(anyhow!("not valid paket_cmd"));
-                    }
-                };
-                Ok(Tools {
-                    ilspy_cmd,
-                    paket_cmd,
-                })
-            }
-            None => Ok(Tools {
-                ilspy_cmd: which(Self::ILSPY_CMD)?,
-                paket_cmd: which(Self::PAKET_CMD)?,
-            }),
+            index_options: IndexOptions::default(),
+            default_framework: None,
+            max_decompiled_file_bytes: None,
+            prefer_source_packages: false,
+            index_reference_assemblies: false,
+            lazy_index: false,
+            indexed: OnceCell::new(),
+            warm_cache: false,
+            use_existing_db: false,
+            query_mode: QueryMode::InMemory,
+            cache_size: None,
+            mmap_size: None,
+            diagnostics: Arc::new(Mutex::new(vec![])),
         }
     }
 
     pub async fn validate_language_configuration(self: &Arc<Self>) -> Result<(), Error> {
         let clone = self.clone();
-        let lc = SourceNodeLanguageConfiguration::new(&tree_sitter_stack_graphs::NoCancellation)?;
+        let lc = SourceNodeLanguageConfiguration::shared(&NoCancellation)?;
         let mut lc_guard = clone.source_language_config.write().await;
         lc_guard.replace(lc);
         Ok(())
     }
 
-    pub async fn get_project_graph(self: &Arc<Self>) -> Result<usize, Error> {
+    pub async fn get_project_graph(
+        self: &Arc<Self>,
+        cancellation_flag: &AtomicCancellationFlag,
+    ) -> Result<usize, Error> {
+        if self.db_path.exists() {
+            // A database indexed with other conventions reads back wrong, and one whose files
+            // were picked with other filters has other files, index it again.
+            let filters = self.index_options.filters();
+            match index_stamp(&self.db_path) {
+                Ok(Some(stamp))
+                    if stamp.format_version == INDEX_FORMAT_VERSION && stamp.filters == filters => {
+                }
+                stamp => {
+                    warn!(
+                        "rebuilding {:?}, it was indexed as {:?} but format {} with {} is needed",
+                        self.db_path, stamp, INDEX_FORMAT_VERSION, filters
+                    );
+                    remove_database(&self.db_path)?;
+                }
+            }
+        }
         if self.db_path.exists() {
             debug!("trying to load from existing db: {:?}", &self.db_path);
             // Load the stack_graph.
-            let mut db_reader = match SQLiteReader::open(&self.db_path) {
+            let mut db_reader = match self.open_reader(&self.db_path) {
                 Ok(db_reader) => db_reader,
                 Err(e) => {
                     return Err(anyhow!(e));
@@ -169,7 +273,7 @@ impl Project {
             };
 
             if let Err(e) =
-                db_reader.load_graphs_for_file_or_directory(&self.location, &NoCancellation)
+                db_reader.load_graphs_for_file_or_directory(&self.location, cancellation_flag)
             {
                 return Err(anyhow!(e));
             }
@@ -190,13 +294,10 @@ impl Project {
             if graph.iter_symbols().count() == 0 {
                 debug!("unable to load graph");
             } else {
-                debug!("trying to get guard");
-                if let Ok(mut graph_guard) = self.graph.lock() {
-                    graph_guard.replace(graph);
-                    drop(graph_guard);
-                    debug!("setting graph on project");
-                    return Ok(stack_graph.iter_files().count());
-                }
+                let merged = self.merge_extra_databases(&mut graph, cancellation_flag)?;
+                debug!("setting graph on project");
+                self.set_graph(graph);
+                return Ok(stack_graph.iter_files().count() + merged);
             }
             drop(graph);
         }
@@ -204,20 +305,340 @@ impl Project {
         let lc_guard = self.source_language_config.read().await;
         // If the databse is present we should consider use that and load into the graph
         let lc = lc_guard.as_ref().expect("unable to get read lock");
-        let initialized_results = match init_stack_graph(
+        let mut initialized_results = match init_stack_graph(
             &self.location,
             &self.db_path,
             &lc.source_type_node_info,
             &lc.language_config,
+            &self.index_options,
+            cancellation_flag,
+            None,
         ) {
             Ok(i) => i,
             Err(e) => return Err(anyhow!(e)),
         };
+        self.add_diagnostics(std::mem::take(&mut initialized_results.diagnostics));
+
+        let merged =
+            self.merge_extra_databases(&mut initialized_results.stack_graph, cancellation_flag)?;
+
+        self.set_graph(initialized_results.stack_graph);
+        Ok(initialized_results.files_loaded + merged)
+    }
+
+    /// Sets the graph the next queries run against. In `QueryMode::DbBacked` only the
+    /// namespaces of its files are kept, queries load the files they need from the databases
+    /// it was written to.
+    pub fn set_graph(&self, graph: StackGraph) {
+        match self.query_mode {
+            QueryMode::InMemory => {
+                if let Ok(mut graph_guard) = self.graph.lock() {
+                    graph_guard.replace(graph);
+                }
+            }
+            QueryMode::DbBacked => {
+                if let Ok(mut namespace_files) = self.namespace_files.lock() {
+                    namespace_files.replace(NamespaceFiles::new(&graph));
+                }
+            }
+        }
+        self.invalidate_namespace_index();
+    }
+
+    /// Indexes the files again after they changed on disk, a file that is gone is removed from
+    /// the index. The database is updated first and then the files are replaced in the graph,
+    /// all while the graph is locked so no query sees it half updated. Nothing is done before
+    /// the project is first indexed, that reads the files as they are then.
+    pub async fn reindex_files(
+        self: &Arc<Self>,
+        paths: &[PathBuf],
+        cancellation_flag: &AtomicCancellationFlag,
+    ) -> Result<FileChanges, Error> {
+        if !self.indexed.initialized() {
+            debug!("project not indexed yet, ignoring changes to {:?}", paths);
+            return Ok(FileChanges::default());
+        }
+        let lc = self
+            .source_language_config
+            .read()
+            .await
+            .clone()
+            .ok_or_else(|| anyhow!("unable to get source language config"))?;
+        let project = self.clone();
+        let paths = paths.to_vec();
+        let cancellation_flag = cancellation_flag.clone();
+        // Parsing and writing the files blocks, as does waiting on the graph's lock.
+        tokio::task::spawn_blocking(move || {
+            project.reindex_files_blocking(&paths, &lc, &cancellation_flag)
+        })
+        .await?
+    }
+
+    fn reindex_files_blocking(
+        &self,
+        paths: &[PathBuf],
+        lc: &SourceNodeLanguageConfiguration,
+        cancellation_flag: &AtomicCancellationFlag,
+    ) -> Result<FileChanges, Error> {
+        let mut changes = FileChanges::default();
+        let mut graph_guard = self
+            .graph
+            .lock()
+            .map_err(|_| anyhow!("unable to get project graph"))?;
+        let mut db = open_writer(&self.db_path)?;
+        // What was wrong with the files before is found again.
+        self.diagnostics
+            .lock()
+            .expect("unable to get diagnostics")
+            .retain(|diagnostic| !paths.contains(&diagnostic.path));
+        let mut diagnostics = vec![];
+        // What the files declare now, for the queries of `QueryMode::DbBacked` to find them.
+        let mut reindexed = NamespaceFiles::default();
+        let mut reindexed_paths = vec![];
+        // The files as they are now, to replace them with in `QueryMode::InMemory`.
+        let mut changed = StackGraph::new();
+        SourceType::load_symbols_into_graph(&mut changed);
+        let mut failed = None;
+        for path in paths {
+            if !path.starts_with(&self.location)
+                || !is_indexed_source(&self.location, path, self.index_options.include_generated)
+                || self
+                    .index_options
+                    .file_list
+                    .as_ref()
+                    .is_some_and(|file_list| !file_list.contains(path))
+            {
+                debug!("not part of the project, ignoring change to {:?}", path);
+                continue;
+            }
+            // Whatever was indexed for the file goes, it is only added back if it still builds.
+            let indexed = with_busy_retry(|| db.clean_file(path))? > 0;
+            reindexed_paths.push(path.to_string_lossy().to_string());
+            if !path.exists()
+                || !declares_included_namespace(path, &self.index_options.include_namespaces)
+            {
+                if indexed {
+                    changes.deleted.push(path.clone());
+                }
+                continue;
+            }
+            let mut graph = StackGraph::new();
+            // The source type symbols have to come first, as they do in the builtins.
+            SourceType::load_symbols_into_graph(&mut graph);
+            match load_graph_for_file(
+                path.clone(),
+                &self.location,
+                &mut graph,
+                &lc.language_config,
+                &lc.source_type_node_info,
+                cancellation_flag,
+            ) {
+                Ok(Some((file, tag))) => {
+                    let mut partials = PartialPaths::new();
+                    let paths: Vec<PartialPath> = vec![];
+                    with_busy_retry(|| {
+                        db.store_result_for_file(&graph, file, &tag, &mut partials, &paths)
+                    })?;
+                    reindexed.extend(NamespaceFiles::new(&graph));
+                    changed.add_from_graph(&graph).map_err(|file| {
+                        anyhow!("{} is already in the graph", changed[file].name())
+                    })?;
+                    match indexed {
+                        true => changes.modified.push(path.clone()),
+                        false => changes.added.push(path.clone()),
+                    }
+                }
+                Ok(None) => debug!("not indexed, ignoring change to {:?}", path),
+                Err(e) => match e.downcast::<FileDiagnostic>() {
+                    // The file no longer builds, it is left out as it would be when indexing.
+                    Ok(diagnostic) => {
+                        if indexed {
+                            changes.deleted.push(path.clone());
+                        }
+                        diagnostics.push(diagnostic);
+                    }
+                    // The files written so far still go into the graph, so that it agrees
+                    // with the database.
+                    Err(e) => {
+                        failed = Some(anyhow!("unable to load file: {:?} - {}", path, e));
+                        break;
+                    }
+                },
+            }
+        }
+        self.add_diagnostics(diagnostics);
+        match self.query_mode {
+            QueryMode::InMemory => {
+                if let Some(graph) = graph_guard.as_ref() {
+                    let replaced = replace_files(graph, &reindexed_paths, &changed)?;
+                    graph_guard.replace(replaced);
+                }
+            }
+            QueryMode::DbBacked => {
+                let mut namespace_files = self
+                    .namespace_files
+                    .lock()
+                    .map_err(|_| anyhow!("unable to get namespace files"))?;
+                if let Some(namespace_files) = namespace_files.as_mut() {
+                    for path in reindexed_paths.iter() {
+                        namespace_files.remove_file(path);
+                    }
+                    namespace_files.extend(reindexed);
+                }
+            }
+        }
+        self.invalidate_namespace_index();
+        drop(graph_guard);
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(changes),
+        }
+    }
+
+    /// Reads the project's files and those of the extra databases back into a new graph.
+    pub fn load_graph_from_databases(
+        &self,
+        cancellation_flag: &AtomicCancellationFlag,
+    ) -> Result<StackGraph, Error> {
+        let mut db_reader = self.open_reader(&self.db_path)?;
+        db_reader.load_graphs_for_file_or_directory(&self.location, cancellation_flag)?;
+        // Loaded graphs are read only, copy it into one the extra databases can be added to.
+        let (read_graph, _, _) = db_reader.get();
+        let mut graph = StackGraph::new();
+        read_graph.to_serializable().load_into(&mut graph)?;
+        self.merge_extra_databases(&mut graph, cancellation_flag)?;
+        Ok(graph)
+    }
+
+    /// Runs `f` on a graph of only `files`, read back from the project's database or, for the
+    /// files it does not have, the first extra database that does.
+    pub fn with_graph_of_files<T>(
+        &self,
+        files: &BTreeSet<String>,
+        cancellation_flag: &AtomicCancellationFlag,
+        f: impl FnOnce(&StackGraph) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut db_reader = self.open_reader(&self.db_path)?;
+        let mut missing = vec![];
+        for file in files {
+            if cancellation_flag.is_cancelled() {
+                return Err(anyhow!("loading files cancelled"));
+            }
+            match has_graph_for_file(&db_reader, file)? {
+                true => {
+                    db_reader.load_graph_for_file(file)?;
+                }
+                false => missing.push(file),
+            }
+        }
+        let (graph, _, _) = db_reader.get();
+        for db_path in self.extra_db_paths.iter() {
+            if missing.is_empty() {
+                break;
+            }
+            let mut extra_reader = self.open_reader(db_path)?;
+            let mut found = false;
+            for file in missing.iter() {
+                if has_graph_for_file(&extra_reader, file)? {
+                    extra_reader.load_graph_for_file(file)?;
+                    found = true;
+                }
+            }
+            missing.retain(|file| extra_reader.get().0.get_file(file).is_none());
+            if found {
+                graph
+                    .add_from_graph(extra_reader.get().0)
+                    .map_err(|file| anyhow!("{} is already in the graph", graph[file].name()))?;
+            }
+        }
+        f(graph)
+    }
+
+    /// Opens a database for reading with the project's `cache_size` and `mmap_size`.
+    pub fn open_reader(&self, db_path: &Path) -> Result<SQLiteReader, StorageError> {
+        open_reader(
+            db_path,
+            &ReadPragmas::for_database(db_path, self.cache_size, self.mmap_size),
+        )
+    }
+
+    pub fn add_diagnostics(&self, diagnostics: Vec<FileDiagnostic>) {
+        for diagnostic in diagnostics.iter() {
+            warn!("not indexed: {}", diagnostic);
+        }
+        self.diagnostics
+            .lock()
+            .expect("unable to get diagnostics")
+            .extend(diagnostics);
+    }
+
+    /// Adds the files of every extra database to `graph`, returning how many were added.
+    /// Files the graph already has are skipped, symbols are looked up again by name as each
+    /// database numbers them differently.
+    pub fn merge_extra_databases(
+        &self,
+        graph: &mut StackGraph,
+        cancellation_flag: &AtomicCancellationFlag,
+    ) -> Result<usize, Error> {
+        let mut merged = 0;
+        for db_path in self.extra_db_paths.iter() {
+            debug!("merging graphs from extra db: {:?}", db_path);
+            let mut db_reader = self.open_reader(db_path)?;
+            let mut files: Vec<String> = vec![];
+            for entry in db_reader.list_all()?.try_iter()? {
+                let file = entry?.path.to_string_lossy().to_string();
+                if graph.get_file(&file).is_none() {
+                    files.push(file);
+                }
+            }
+            for file in files.iter() {
+                if cancellation_flag.is_cancelled() {
+                    return Err(anyhow!("merging extra databases cancelled"));
+                }
+                db_reader.load_graph_for_file(file)?;
+            }
+            let (extra_graph, _, _) = db_reader.get();
+            let added = graph.add_from_graph(extra_graph).map_err(|file| {
+                anyhow!(
+                    "{} from {:?} is already in the graph",
+                    graph[file].name(),
+                    db_path
+                )
+            })?;
+            merged += added.len();
+        }
+        Ok(merged)
+    }
+
+    /// Reads every database through once, pulling its pages into the OS page cache, and builds
+    /// the namespace index ahead of the first query. Returns how many bytes were read.
+    pub fn warm_up(&self) -> Result<u64, Error> {
+        let mut read = 0;
+        for db_path in std::iter::once(&self.db_path).chain(self.extra_db_paths.iter()) {
+            if db_path.exists() {
+                read += std::io::copy(&mut File::open(db_path)?, &mut std::io::sink())?;
+            }
+        }
+        let graph_guard = self
+            .graph
+            .lock()
+            .map_err(|_| anyhow!("unable to get project graph"))?;
+        if let Some(graph) = graph_guard.as_ref() {
+            let mut index_guard = self
+                .namespace_index
+                .lock()
+                .map_err(|_| anyhow!("unable to get namespace index"))?;
+            index_guard.get_or_insert_with(|| NamespaceIndex::new(graph));
+        }
+        Ok(read)
+    }
 
-        if let Ok(mut graph_guard) = self.graph.lock() {
-            graph_guard.replace(initialized_results.stack_graph);
+    /// Drops the namespace index, must be called whenever `graph` is replaced so the next
+    /// query does not start from nodes of the old graph.
+    pub fn invalidate_namespace_index(&self) {
+        if let Ok(mut index_guard) = self.namespace_index.lock() {
+            index_guard.take();
         }
-        Ok(initialized_results.files_loaded)
     }
 
     pub async fn get_source_type(self: &Arc<Self>) -> Option<Arc<SourceType>> {
@@ -227,9 +648,578 @@ impl Project {
         match lc_guard.as_ref() {
             Some(x) => match self.analysis_mode {
                 AnalysisMode::SourceOnly => Some(x.source_type_node_info.clone()),
-                AnalysisMode::Full => Some(x.dependnecy_type_node_info.clone()),
+                AnalysisMode::Full | AnalysisMode::DependenciesOnly => {
+                    Some(x.dependnecy_type_node_info.clone())
+                }
             },
             None => None,
         }
     }
 }
+
+/// A copy of `graph` with `files` replaced by the files of `changed`. A stack graph cannot
+/// remove a file, so the files that did not change are copied into a new one instead, which
+/// is still far quicker than reading every file back from the databases.
+fn replace_files(
+    graph: &StackGraph,
+    files: &[String],
+    changed: &StackGraph,
+) -> Result<StackGraph, Error> {
+    let mut replaced = StackGraph::new();
+    // The source type symbols have to come first, as they do in the builtins.
+    SourceType::load_symbols_into_graph(&mut replaced);
+    graph
+        .to_serializable_filter(&ExceptFiles(files))
+        .load_into(&mut replaced)?;
+    replaced
+        .add_from_graph(changed)
+        .map_err(|file| anyhow!("{} is already in the graph", replaced[file].name()))?;
+    Ok(replaced)
+}
+
+/// Leaves `files` out of a serialized graph, along with their nodes and the edges to them.
+struct ExceptFiles<'a>(&'a [String]);
+
+impl Filter for ExceptFiles<'_> {
+    fn include_file(&self, graph: &StackGraph, file: &Handle<GraphFile>) -> bool {
+        !self
+            .0
+            .iter()
+            .any(|excluded| excluded == graph[*file].name())
+    }
+
+    fn include_node(&self, graph: &StackGraph, node: &Handle<Node>) -> bool {
+        graph[*node]
+            .file()
+            .is_none_or(|file| self.include_file(graph, &file))
+    }
+
+    fn include_edge(&self, graph: &StackGraph, source: &Handle<Node>, sink: &Handle<Node>) -> bool {
+        self.include_node(graph, source) && self.include_node(graph, sink)
+    }
+
+    fn include_partial_path(
+        &self,
+        _graph: &StackGraph,
+        _paths: &PartialPaths,
+        _path: &PartialPath,
+    ) -> bool {
+        true
+    }
+}
+
+/// Whether the database has a graph for `file`. The reader's own `status_for_file` only works
+/// when given the tag the file was stored with.
+fn has_graph_for_file(db_reader: &SQLiteReader, file: &str) -> Result<bool, Error> {
+    let mut entries = db_reader.list_file_or_directory(Path::new(file))?;
+    for entry in entries.try_iter()? {
+        let entry = entry?;
+        if entry.path == Path::new(file) && matches!(entry.status, FileStatus::Indexed) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::c_sharp_graph::{find_node::FindNode, results::ResultNode};
+    use crate::test_support::TestDir;
+
+    #[tokio::test]
+    async fn get_project_graph_aborts_when_cancelled() {
+        let dir = TestDir::new("cancel-project");
+        for i in 0..20 {
+            fs::write(
+                dir.join(format!("File{}.cs", i)),
+                format!("namespace Demo {{ class C{} {{ void M() {{ }} }} }}", i),
+            )
+            .unwrap();
+        }
+        let project = Arc::new(Project::new(
+            dir.to_path_buf(),
+            dir.join("test.db"),
+            AnalysisMode::SourceOnly,
+            Tools {
+                ilspy_cmd: PathBuf::from("ilspycmd"),
+                paket_cmd: PathBuf::from("paket"),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        ));
+        project.validate_language_configuration().await.unwrap();
+
+        let cancellation_flag = AtomicCancellationFlag::new();
+        cancellation_flag.cancel();
+        let res = project.get_project_graph(&cancellation_flag).await;
+        assert!(res.is_err(), "indexing should stop once cancelled");
+        assert!(project.graph.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn extra_databases_are_queried_together() {
+        let dir = TestDir::new("extra-dbs");
+        let (library, app) = (dir.join("library"), dir.join("app"));
+        fs::create_dir_all(&library).unwrap();
+        fs::create_dir_all(&app).unwrap();
+        fs::write(
+            library.join("Api.cs"),
+            "namespace Demo.Union { public class Api { public static void Call() { } } }",
+        )
+        .unwrap();
+        fs::write(
+            app.join("User.cs"),
+            "using Demo.Union;\nnamespace Demo.App { class User { void M() { Api.Call(); } } }",
+        )
+        .unwrap();
+        let project = |location: &PathBuf, db_path: PathBuf, extra_db_paths: Vec<PathBuf>| {
+            let mut project = Project::new(
+                location.clone(),
+                db_path,
+                AnalysisMode::SourceOnly,
+                Tools {
+                    ilspy_cmd: PathBuf::from("ilspycmd"),
+                    paket_cmd: PathBuf::from("paket"),
+                    env: BTreeMap::new(),
+                    ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+                },
+            );
+            project.extra_db_paths = extra_db_paths;
+            Arc::new(project)
+        };
+        let search = || FindNode {
+            regex: "Demo.Union.*".to_string(),
+            options: Default::default(),
+        };
+        let files = |results: Vec<ResultNode>| {
+            let mut files: Vec<String> = results
+                .into_iter()
+                .map(|r| r.file_uri.rsplit('/').next().unwrap().to_string())
+                .collect();
+            files.sort();
+            files.dedup();
+            files
+        };
+
+        // Each part is indexed into its own database.
+        for (location, db) in [(&library, "library.db"), (&app, "app.db")] {
+            let part = project(location, dir.join(db), vec![]);
+            part.validate_language_configuration().await.unwrap();
+            assert_eq!(
+                part.get_project_graph(&AtomicCancellationFlag::new())
+                    .await
+                    .unwrap(),
+                1
+            );
+        }
+        // The application alone does not know where the namespace is declared.
+        let alone = project(&app, dir.join("app.db"), vec![]);
+        alone.validate_language_configuration().await.unwrap();
+        alone
+            .get_project_graph(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+        assert!(search().run(&alone).await.unwrap().is_empty());
+
+        let union = project(&app, dir.join("app.db"), vec![dir.join("library.db")]);
+        union.validate_language_configuration().await.unwrap();
+        assert_eq!(
+            union
+                .get_project_graph(&AtomicCancellationFlag::new())
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            files(search().run(&union).await.unwrap()),
+            vec!["Api.cs", "User.cs"]
+        );
+    }
+
+    #[tokio::test]
+    async fn db_backed_queries_return_the_in_memory_results() {
+        let dir = TestDir::new("query-mode");
+        let location = dir.join("src");
+        fs::create_dir_all(&location).unwrap();
+        fs::write(
+            location.join("Api.cs"),
+            "namespace Demo.Modes { public class Api { public int Count; public static void Call() { } } }",
+        )
+        .unwrap();
+        fs::write(
+            location.join("User.cs"),
+            "using Demo.Modes;\nnamespace Demo.App { class User { Api api; void M() { Api.Call(); var c = api.Count; } } }",
+        )
+        .unwrap();
+        fs::write(
+            location.join("Other.cs"),
+            "namespace Demo.Other { class Other { } }",
+        )
+        .unwrap();
+        let mut results = vec![];
+        // The graph is read in place from the project's database, with an extra database it is
+        // merged into a copy.
+        for (query_mode, db, extra_db) in [
+            (QueryMode::InMemory, "in-memory.db", None),
+            (QueryMode::DbBacked, "db-backed.db", None),
+            (
+                QueryMode::DbBacked,
+                "db-backed-extra.db",
+                Some("in-memory.db"),
+            ),
+        ] {
+            let mut project = Project::new(
+                location.clone(),
+                dir.join(db),
+                AnalysisMode::SourceOnly,
+                Tools {
+                    ilspy_cmd: PathBuf::from("ilspycmd"),
+                    paket_cmd: PathBuf::from("paket"),
+                    env: BTreeMap::new(),
+                    ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+                },
+            );
+            project.query_mode = query_mode;
+            project.extra_db_paths = extra_db.iter().map(|db| dir.join(db)).collect();
+            let project = Arc::new(project);
+            project.validate_language_configuration().await.unwrap();
+            assert_eq!(
+                project
+                    .get_project_graph(&AtomicCancellationFlag::new())
+                    .await
+                    .unwrap(),
+                3
+            );
+            assert_eq!(
+                project.graph.lock().unwrap().is_some(),
+                query_mode == QueryMode::InMemory
+            );
+            // Only the files that can reference the namespace are read back.
+            if let Some(namespace_files) = project.namespace_files.lock().unwrap().as_ref() {
+                let files = namespace_files
+                    .files("Demo.Modes.*", &Default::default())
+                    .unwrap();
+                assert_eq!(
+                    files,
+                    BTreeSet::from(
+                        ["Api.cs", "User.cs"]
+                            .map(|file| location.join(file).to_string_lossy().to_string())
+                    )
+                );
+            }
+            let mut found: Vec<String> = FindNode {
+                regex: "Demo.Modes.*".to_string(),
+                options: Default::default(),
+            }
+            .run(&project)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| format!("{:?}", r))
+            .collect();
+            found.sort();
+            results.push(found);
+        }
+        assert!(!results[0].is_empty());
+        assert_eq!(results[0], results[1]);
+        assert_eq!(results[0], results[2]);
+    }
+
+    #[tokio::test]
+    async fn reindex_files_picks_up_added_modified_and_deleted_files() {
+        for query_mode in [QueryMode::InMemory, QueryMode::DbBacked] {
+            reindex_files_in(query_mode).await;
+        }
+    }
+
+    async fn reindex_files_in(query_mode: QueryMode) {
+        let dir = TestDir::new(&format!("reindex-{:?}", query_mode));
+        let location = dir.join("src");
+        fs::create_dir_all(&location).unwrap();
+        fs::write(
+            location.join("Api.cs"),
+            "namespace Demo.Changes { public class Api { public static void Call() { } } }",
+        )
+        .unwrap();
+        let using = |class: &str, calls: usize| {
+            format!(
+                "using Demo.Changes;\nnamespace Demo.App {{ class {} {{ void M() {{\n{}}} }} }}",
+                class,
+                "Api.Call();\n".repeat(calls)
+            )
+        };
+        fs::write(location.join("User.cs"), using("User", 1)).unwrap();
+        fs::write(location.join("Old.cs"), using("Old", 1)).unwrap();
+        let mut project = Project::new(
+            location.clone(),
+            dir.join("test.db"),
+            AnalysisMode::SourceOnly,
+            Tools {
+                ilspy_cmd: PathBuf::from("ilspycmd"),
+                paket_cmd: PathBuf::from("paket"),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        );
+        project.query_mode = query_mode;
+        let project = Arc::new(project);
+        project.validate_language_configuration().await.unwrap();
+        project
+            .get_project_graph(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+        project.indexed.set(()).unwrap();
+        let incidents = || async {
+            let mut found: Vec<(String, usize)> = FindNode {
+                regex: "Demo.Changes.*".to_string(),
+                options: Default::default(),
+            }
+            .run(&project)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|r| !r.file_uri.ends_with("Api.cs"))
+            .map(|r| {
+                let file = r.file_uri.rsplit('/').next().unwrap().to_string();
+                (file, r.line_number)
+            })
+            .collect();
+            found.dedup();
+            found
+        };
+        let before = incidents().await;
+        assert!(
+            before.iter().any(|(file, _)| file == "Old.cs"),
+            "{:?}",
+            before
+        );
+
+        fs::write(location.join("User.cs"), using("User", 2)).unwrap();
+        fs::write(location.join("New.cs"), using("New", 1)).unwrap();
+        fs::remove_file(location.join("Old.cs")).unwrap();
+        let changed = ["User.cs", "New.cs", "Old.cs"].map(|file| location.join(file));
+        let changes = project
+            .reindex_files(&changed, &AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            changes,
+            FileChanges {
+                added: vec![location.join("New.cs")],
+                modified: vec![location.join("User.cs")],
+                deleted: vec![location.join("Old.cs")],
+            }
+        );
+        let after = incidents().await;
+        assert!(
+            after.iter().all(|(file, _)| file != "Old.cs"),
+            "{:?}",
+            after
+        );
+        assert!(
+            after.iter().any(|(file, _)| file == "New.cs"),
+            "{:?}",
+            after
+        );
+        assert!(after.contains(&("User.cs".to_string(), 3)), "{:?}", after);
+        if query_mode == QueryMode::InMemory {
+            // Only the changed files are replaced, the others are kept as they were.
+            let graph_guard = project.graph.lock().unwrap();
+            let graph = graph_guard.as_ref().unwrap();
+            let mut files: Vec<&str> = graph
+                .iter_files()
+                .map(|file| graph[file].name().rsplit('/').next().unwrap())
+                .collect();
+            files.sort();
+            assert_eq!(files, vec!["<builtins>", "Api.cs", "New.cs", "User.cs"]);
+        }
+        assert!(
+            !before.contains(&("User.cs".to_string(), 3)),
+            "{:?}",
+            before
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_reindex_leaves_the_graph_as_the_database_has_it() {
+        let dir = TestDir::new("reindex-cancelled");
+        let location = dir.join("src");
+        dir.write(
+            "src/Api.cs",
+            "namespace Demo.Changes { public class Api { } }",
+        );
+        dir.write("src/User.cs", "namespace Demo.App { class User { } }");
+        let project = Arc::new(Project::new(
+            location.clone(),
+            dir.join("test.db"),
+            AnalysisMode::SourceOnly,
+            Tools {
+                ilspy_cmd: PathBuf::from("ilspycmd"),
+                paket_cmd: PathBuf::from("paket"),
+                env: BTreeMap::new(),
+                ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+            },
+        ));
+        project.validate_language_configuration().await.unwrap();
+        project
+            .get_project_graph(&AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+        project.indexed.set(()).unwrap();
+        let graph_files = || {
+            let graph_guard = project.graph.lock().unwrap();
+            let graph = graph_guard.as_ref().unwrap();
+            let mut files: Vec<String> = graph
+                .iter_files()
+                .map(|file| graph[file].name().rsplit('/').next().unwrap().to_string())
+                .collect();
+            files.sort();
+            files
+        };
+
+        let changed = [location.join("User.cs")];
+        let cancelled = AtomicCancellationFlag::new();
+        cancelled.cancel();
+        assert!(project.reindex_files(&changed, &cancelled).await.is_err());
+        // The file was taken out of the database before it was cancelled.
+        assert_eq!(graph_files(), vec!["<builtins>", "Api.cs"]);
+
+        let changes = project
+            .reindex_files(&changed, &AtomicCancellationFlag::new())
+            .await
+            .unwrap();
+        assert_eq!(changes.added, changed.to_vec());
+        assert_eq!(graph_files(), vec!["<builtins>", "Api.cs", "User.cs"]);
+    }
+
+    #[tokio::test]
+    async fn databases_indexed_with_other_filters_are_rebuilt() {
+        let dir = TestDir::new("index-filters");
+        let location = dir.join("src");
+        fs::create_dir_all(&location).unwrap();
+        fs::write(
+            location.join("Api.cs"),
+            "namespace Demo.Keep { public class Api { } }",
+        )
+        .unwrap();
+        fs::write(
+            location.join("Other.cs"),
+            "namespace Demo.Drop { public class Other { } }",
+        )
+        .unwrap();
+        fs::write(location.join(".gitignore"), "Other.cs\n").unwrap();
+        let db_path = dir.join("test.db");
+        let files_loaded =
+            |include_namespaces: &[&str], respect_gitignore: bool, file_list: Option<&[&str]>| {
+                let mut project = Project::new(
+                    location.clone(),
+                    db_path.clone(),
+                    AnalysisMode::SourceOnly,
+                    Tools {
+                        ilspy_cmd: PathBuf::from("ilspycmd"),
+                        paket_cmd: PathBuf::from("paket"),
+                        env: BTreeMap::new(),
+                        ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+                    },
+                );
+                project.index_options.include_namespaces =
+                    include_namespaces.iter().map(|n| n.to_string()).collect();
+                project.index_options.respect_gitignore = respect_gitignore;
+                project.index_options.file_list =
+                    file_list.map(|files| files.iter().map(|file| location.join(file)).collect());
+                async move {
+                    let project = Arc::new(project);
+                    project.validate_language_configuration().await.unwrap();
+                    project
+                        .get_project_graph(&AtomicCancellationFlag::new())
+                        .await
+                        .unwrap()
+                }
+            };
+        assert_eq!(files_loaded(&["Demo.Keep"], false, None).await, 1);
+        assert_eq!(files_loaded(&["Demo.Keep"], false, None).await, 1);
+        // Reopening the database with other filters indexes the files they pick.
+        assert_eq!(files_loaded(&[], false, None).await, 2);
+        assert_eq!(files_loaded(&[], false, Some(&["Other.cs"])).await, 1);
+        assert_eq!(
+            files_loaded(&[], false, Some(&["Api.cs", "Other.cs"])).await,
+            2
+        );
+        assert_eq!(files_loaded(&[], true, None).await, 1);
+        assert_eq!(
+            index_stamp(&db_path).unwrap().unwrap().filters,
+            IndexOptions {
+                respect_gitignore: true,
+                ..Default::default()
+            }
+            .filters()
+        );
+    }
+
+    #[tokio::test]
+    async fn databases_stamped_with_another_index_format_are_rebuilt() {
+        let dir = TestDir::new("index-format");
+        let location = dir.join("src");
+        fs::create_dir_all(&location).unwrap();
+        fs::write(
+            location.join("Api.cs"),
+            "namespace Demo.Format { public class Api { } }",
+        )
+        .unwrap();
+        let db_path = dir.join("test.db");
+        let project = || {
+            Arc::new(Project::new(
+                location.clone(),
+                db_path.clone(),
+                AnalysisMode::SourceOnly,
+                Tools {
+                    ilspy_cmd: PathBuf::from("ilspycmd"),
+                    paket_cmd: PathBuf::from("paket"),
+                    env: BTreeMap::new(),
+                    ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+                },
+            ))
+        };
+        let files_loaded = || async {
+            let project = project();
+            project.validate_language_configuration().await.unwrap();
+            project
+                .get_project_graph(&AtomicCancellationFlag::new())
+                .await
+                .unwrap()
+        };
+        assert_eq!(files_loaded().await, 1);
+        let stamp = index_stamp(&db_path).unwrap().unwrap();
+        assert_eq!(stamp.format_version, INDEX_FORMAT_VERSION);
+
+        // A file added since is only seen when the database is not read back.
+        fs::write(
+            location.join("User.cs"),
+            "using Demo.Format;\nnamespace Demo.App { class User { Api api; } }",
+        )
+        .unwrap();
+        assert_eq!(files_loaded().await, 1);
+        rusqlite::Connection::open(&db_path)
+            .unwrap()
+            .execute(
+                "UPDATE index_metadata SET format_version = ?1",
+                [INDEX_FORMAT_VERSION - 1],
+            )
+            .unwrap();
+        assert_eq!(files_loaded().await, 2);
+        let stamp = index_stamp(&db_path).unwrap().unwrap();
+        assert_eq!(stamp.format_version, INDEX_FORMAT_VERSION);
+
+        // Databases from before the stamp are rebuilt as well.
+        rusqlite::Connection::open(&db_path)
+            .unwrap()
+            .execute("DROP TABLE index_metadata", [])
+            .unwrap();
+        assert_eq!(index_stamp(&db_path).unwrap(), None);
+        assert_eq!(files_loaded().await, 2);
+        assert!(index_stamp(&db_path).unwrap().is_some());
+    }
+}
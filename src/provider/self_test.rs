@@ -0,0 +1,221 @@
+use std::{fmt, fs, path::Path, process, sync::Arc};
+
+use anyhow::{anyhow, Error};
+
+use crate::c_sharp_graph::{find_node::FindNode, query::QueryOptions};
+use crate::provider::{AnalysisMode, AtomicCancellationFlag, Project, ProviderConfig, Tools};
+
+const SAMPLE_LIBRARY: &str = r#"namespace SelfTest.Library
+{
+    public class Greeter
+    {
+        public string Greet(string name)
+        {
+            return "hello " + name;
+        }
+    }
+}
+"#;
+
+const SAMPLE_PROGRAM: &str = r#"using SelfTest.Library;
+
+namespace SelfTest.App
+{
+    public class Program
+    {
+        public static void Main()
+        {
+            Greeter greeter = new Greeter();
+            greeter.Greet("world");
+        }
+    }
+}
+"#;
+
+const SAMPLE_FILES: [(&str, &str); 2] = [
+    ("Library.cs", SAMPLE_LIBRARY),
+    ("Program.cs", SAMPLE_PROGRAM),
+];
+
+pub enum Outcome {
+    Pass,
+    /// Not needed for the checks that follow but will limit a real analysis.
+    Skip,
+    Fail,
+}
+
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+#[derive(Default)]
+pub struct SelfTestReport {
+    pub checks: Vec<Check>,
+}
+
+impl SelfTestReport {
+    pub fn is_success(&self) -> bool {
+        !self
+            .checks
+            .iter()
+            .any(|c| matches!(c.outcome, Outcome::Fail))
+    }
+
+    fn record(&mut self, name: &'static str, result: Result<String, Error>) -> bool {
+        let (outcome, detail) = match result {
+            Ok(detail) => (Outcome::Pass, detail),
+            Err(e) => (Outcome::Fail, e.to_string()),
+        };
+        let passed = matches!(outcome, Outcome::Pass);
+        self.checks.push(Check {
+            name,
+            outcome,
+            detail,
+        });
+        passed
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in self.checks.iter() {
+            let outcome = match check.outcome {
+                Outcome::Pass => "PASS",
+                Outcome::Skip => "SKIP",
+                Outcome::Fail => "FAIL",
+            };
+            writeln!(f, "{} {}: {}", outcome, check.name, check.detail)?;
+        }
+        if self.is_success() {
+            writeln!(f, "self-test passed")
+        } else {
+            writeln!(f, "self-test failed")
+        }
+    }
+}
+
+/// Index a small bundled sample into a fresh database, read it back and run a few queries
+/// with known answers. Stops at the first failing step as the later ones depend on it.
+pub async fn run_self_test() -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    for (name, cmd) in [
+        ("ilspy", ProviderConfig::ILSPY_CMD),
+        ("paket", ProviderConfig::PAKET_CMD),
+    ] {
+        // Looked up as init looks it up when the config does not give its path.
+        let (outcome, detail) = match ProviderConfig::default().find_tool(cmd) {
+            Ok(path) => (Outcome::Pass, format!("found {}", path.display())),
+            Err(e) => (
+                Outcome::Skip,
+                format!("{}, only source-only analysis will work", e),
+            ),
+        };
+        report.checks.push(Check {
+            name,
+            outcome,
+            detail,
+        });
+    }
+
+    let dir = std::env::temp_dir().join(format!("c_sharp_provider_self_test_{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let location = dir.join("sample");
+    let db_path = dir.join("self_test.db");
+    if report.record("sample", write_sample(&location)) {
+        run_checks(&mut report, &location, &db_path).await;
+    }
+    let _ = fs::remove_dir_all(&dir);
+    report
+}
+
+async fn run_checks(report: &mut SelfTestReport, location: &Path, db_path: &Path) {
+    let cancellation_flag = AtomicCancellationFlag::new();
+    let project = new_project(location, db_path);
+    let configured = project
+        .validate_language_configuration()
+        .await
+        .map(|_| "loaded the C# grammar and stack graph rules".to_string());
+    if !report.record("language configuration", configured) {
+        return;
+    }
+    let indexed = project
+        .get_project_graph(&cancellation_flag)
+        .await
+        .and_then(|files| expect_files(files, "indexed"));
+    if !report.record("index", indexed) {
+        return;
+    }
+
+    // A second project on the same database only reads back what the first one wrote.
+    let reloaded = new_project(location, db_path);
+    let database = match reloaded.validate_language_configuration().await {
+        Ok(()) => reloaded
+            .get_project_graph(&cancellation_flag)
+            .await
+            .and_then(|files| expect_files(files, "read back")),
+        Err(e) => Err(e),
+    };
+    if !report.record("database", database) {
+        return;
+    }
+
+    for (pattern, expected) in [("SelfTest.Library.*", 4), ("SelfTest.Missing.*", 0)] {
+        let found = FindNode {
+            regex: pattern.to_string(),
+            options: QueryOptions::default(),
+        }
+        .run(&reloaded)
+        .await
+        .and_then(|results| match results.len() {
+            n if n == expected => Ok(format!("{} matched {} incidents", pattern, n)),
+            n => Err(anyhow!(
+                "{} matched {} incidents, expected {}",
+                pattern,
+                n,
+                expected
+            )),
+        });
+        report.record("query", found);
+    }
+}
+
+fn new_project(location: &Path, db_path: &Path) -> Arc<Project> {
+    Arc::new(Project::new(
+        location.to_path_buf(),
+        db_path.to_path_buf(),
+        AnalysisMode::SourceOnly,
+        Tools {
+            ilspy_cmd: Default::default(),
+            paket_cmd: Default::default(),
+            env: Default::default(),
+            ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+        },
+    ))
+}
+
+fn write_sample(location: &Path) -> Result<String, Error> {
+    fs::create_dir_all(location)?;
+    for (name, contents) in SAMPLE_FILES {
+        fs::write(location.join(name), contents)?;
+    }
+    Ok(format!(
+        "wrote {} files to {}",
+        SAMPLE_FILES.len(),
+        location.display()
+    ))
+}
+
+fn expect_files(files: usize, action: &str) -> Result<String, Error> {
+    if files == SAMPLE_FILES.len() {
+        Ok(format!("{} {} files", action, files))
+    } else {
+        Err(anyhow!(
+            "{} {} files, expected {}",
+            action,
+            files,
+            SAMPLE_FILES.len()
+        ))
+    }
+}
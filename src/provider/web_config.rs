@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+use walkdir::WalkDir;
+
+/// An assembly a `web.config` references, by name and the version the application binds to
+/// when one is given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyReference {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// The assemblies referenced by `<compilation><assemblies>` and `<assemblyBinding>` entries.
+/// A binding redirect's `newVersion` is what gets loaded, so it wins over the version in the
+/// compilation entry.
+pub fn parse_web_config(contents: &str) -> Result<Vec<AssemblyReference>, Error> {
+    let doc = roxmltree::Document::parse(contents)?;
+    let mut references: BTreeMap<String, Option<String>> = BTreeMap::new();
+    for node in doc.descendants().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "add"
+                if node
+                    .parent_element()
+                    .is_some_and(|p| p.tag_name().name() == "assemblies") =>
+            {
+                let Some(assembly) = node.attribute("assembly") else {
+                    continue;
+                };
+                // "System.Data.Entity, Version=4.0.0.0, Culture=neutral, PublicKeyToken=..."
+                let mut parts = assembly.split(',').map(str::trim);
+                let Some(name) = parts.next().filter(|n| !n.is_empty() && *n != "*") else {
+                    continue;
+                };
+                let version = parts
+                    .find_map(|p| p.strip_prefix("Version="))
+                    .map(str::to_string);
+                let entry = references.entry(name.to_string()).or_default();
+                if entry.is_none() {
+                    *entry = version;
+                }
+            }
+            "dependentAssembly" => {
+                let Some(name) = node
+                    .children()
+                    .find(|c| c.tag_name().name() == "assemblyIdentity")
+                    .and_then(|c| c.attribute("name"))
+                else {
+                    continue;
+                };
+                let version = node
+                    .children()
+                    .find(|c| c.tag_name().name() == "bindingRedirect")
+                    .and_then(|c| c.attribute("newVersion"))
+                    .map(str::to_string);
+                let entry = references.entry(name.to_string()).or_default();
+                if version.is_some() {
+                    *entry = version;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(references
+        .into_iter()
+        .map(|(name, version)| AssemblyReference { name, version })
+        .collect())
+}
+
+/// Every `web.config` of the project, ASP.NET nests them per directory, as `Views/Web.config`.
+/// Restored packages are skipped, they ship sample configs that are not the application's.
+pub fn web_config_files(location: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(location)
+        .into_iter()
+        .filter_entry(|e| !(e.file_type().is_dir() && e.file_name() == "packages"))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && e.file_name().eq_ignore_ascii_case("web.config"))
+        .map(|e| e.into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Where an assembly is installed: the `bin` directory next to the `web.config` is what the
+/// application loads, then the restored packages, then the global assembly cache.
+pub fn locate_assembly(
+    reference: &AssemblyReference,
+    web_config: &Path,
+    location: &Path,
+    gac_dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    let file_name = format!("{}.dll", reference.name);
+    let bin = web_config.parent().map(|dir| dir.join("bin"));
+    bin.into_iter()
+        .chain([location.join("packages")])
+        .find_map(|dir| find_dll(&dir, &file_name))
+        .or_else(|| {
+            gac_dirs
+                .iter()
+                .find_map(|gac| find_in_gac(gac, reference, &file_name))
+        })
+}
+
+/// The last match in path order, for packages that carry a build per framework that is the
+/// one for the newest framework.
+fn find_dll(dir: &Path, file_name: &str) -> Option<PathBuf> {
+    WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && e.file_name().eq_ignore_ascii_case(file_name))
+        .map(|e| e.into_path())
+        .last()
+}
+
+/// The GAC keeps an assembly under `<name>/<version>__<token>/`, prefixed with the runtime
+/// as `v4.0_` in the .NET 4 cache. Without a version the newest is used.
+fn find_in_gac(gac: &Path, reference: &AssemblyReference, file_name: &str) -> Option<PathBuf> {
+    let mut versions: Vec<PathBuf> = std::fs::read_dir(gac.join(&reference.name))
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.join(file_name).is_file())
+        .collect();
+    versions.sort();
+    let dir = match &reference.version {
+        Some(version) => versions.into_iter().find(|p| {
+            p.file_name().is_some_and(|n| {
+                let n = n.to_string_lossy();
+                n.starts_with(&format!("{}__", version)) || n.contains(&format!("_{}__", version))
+            })
+        }),
+        None => versions.pop(),
+    }?;
+    Some(dir.join(file_name))
+}
+
+/// The global assembly caches of the machine, only Windows has them.
+pub fn gac_dirs() -> Vec<PathBuf> {
+    let Some(windir) = std::env::var_os("WINDIR") else {
+        return vec![];
+    };
+    let windir = PathBuf::from(windir);
+    ["GAC_MSIL", "GAC_64", "GAC_32"]
+        .iter()
+        .flat_map(|gac| {
+            [
+                windir.join("Microsoft.NET").join("assembly").join(gac),
+                windir.join("assembly").join(gac),
+            ]
+        })
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_redirects_override_compilation_versions() {
+        let web_config =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/web-config/Web.config");
+        let references = parse_web_config(&std::fs::read_to_string(web_config).unwrap()).unwrap();
+        let reference = |name: &str, version: &str| AssemblyReference {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+        };
+        assert_eq!(
+            references,
+            vec![
+                reference("Antlr3.Runtime", "3.4.1.9004"),
+                reference("Contoso.Logging", "1.0.0.0"),
+                reference("Legacy.Reporting", "2.1.0.0"),
+                reference("Missing.Assembly", "1.0.0.0"),
+                reference("System.Data.Entity", "4.0.0.0"),
+            ]
+        );
+    }
+}
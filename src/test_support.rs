@@ -0,0 +1,74 @@
+//! Fixtures shared by the unit tests.
+
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tempfile::TempDir;
+use tree_sitter_stack_graphs::NoCancellation;
+
+use crate::c_sharp_graph::{
+    language_config::SourceNodeLanguageConfiguration,
+    loader::{init_stack_graph, IndexOptions, InitializedGraph},
+};
+
+/// A directory of test files, removed when dropped whether or not the test passed.
+pub struct TestDir(TempDir);
+
+impl TestDir {
+    /// An empty directory, its name starts with `name` to tell the tests' directories apart.
+    pub fn new(name: &str) -> Self {
+        TestDir(
+            tempfile::Builder::new()
+                .prefix(&format!("{}-", name))
+                .tempdir()
+                .unwrap(),
+        )
+    }
+
+    /// Writes `contents` to `file`, relative to the directory, creating its parents.
+    pub fn write(&self, file: &str, contents: &str) -> PathBuf {
+        let path = self.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Indexes the directory into `test.db` in it.
+    pub fn index(&self, options: &IndexOptions) -> InitializedGraph {
+        let lc = language_config();
+        init_stack_graph(
+            self,
+            &self.join("test.db"),
+            &lc.source_type_node_info,
+            &lc.language_config,
+            options,
+            &NoCancellation,
+            None,
+        )
+        .unwrap()
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.0.path()
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        self.0.path()
+    }
+}
+
+/// The language configuration the projects share, building it takes longer than indexing the
+/// few files a test has.
+pub fn language_config() -> Arc<SourceNodeLanguageConfiguration> {
+    SourceNodeLanguageConfiguration::shared(&NoCancellation).unwrap()
+}
@@ -0,0 +1,14 @@
+using Konveyor.Demos.Access;
+
+namespace Konveyor.Demos.Profile
+{
+    public class Preferences
+    {
+        public string Apply()
+        {
+            Settings.Load().Theme = "dark";
+            Settings.Load().Save();
+            return Settings.Load().Theme;
+        }
+    }
+}
@@ -0,0 +1,16 @@
+namespace Konveyor.Demos.Access
+{
+    public class Settings
+    {
+        public string Theme { get; set; }
+
+        public static Settings Load()
+        {
+            return new Settings();
+        }
+
+        public void Save()
+        {
+        }
+    }
+}
@@ -0,0 +1,9 @@
+namespace Konveyor.Demos.Auditing
+{
+    public static class AuditLog
+    {
+        public static void Write(string entry)
+        {
+        }
+    }
+}
@@ -0,0 +1,17 @@
+using Konveyor.Demos.Auditing;
+
+namespace Konveyor.Demos.Dinners
+{
+    public class DinnerEditor
+    {
+        public void Edit(int id)
+        {
+            AuditLog.Write("edit");
+        }
+
+        public void Delete(int id)
+        {
+            AuditLog.Write("delete");
+        }
+    }
+}
@@ -0,0 +1,15 @@
+namespace Konveyor.Demos.Text
+{
+    public class Encoding
+    {
+        public static Encoding GetEncoding(string name)
+        {
+            return new Encoding();
+        }
+
+        public static Encoding GetEncoding(int codePage)
+        {
+            return new Encoding();
+        }
+    }
+}
@@ -0,0 +1,19 @@
+using Konveyor.Demos.Text;
+
+namespace Konveyor.Demos.Export
+{
+    public class Exporter
+    {
+        public void Export()
+        {
+            var ascii = Encoding.GetEncoding("ascii");
+            var unicode = Encoding.GetEncoding("utf-8");
+            var codePage = Encoding.GetEncoding(20127);
+            Write(Encoding.GetEncoding(@"ascii"));
+        }
+
+        private void Write(Encoding encoding)
+        {
+        }
+    }
+}
@@ -0,0 +1,4 @@
+using Konveyor.Demos.AssemblyAttributes;
+
+[assembly: Product("Orders")]
+[module: Verified]
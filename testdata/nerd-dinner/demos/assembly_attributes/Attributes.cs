@@ -0,0 +1,13 @@
+namespace Konveyor.Demos.AssemblyAttributes
+{
+    public class ProductAttribute : System.Attribute
+    {
+        public ProductAttribute(string name)
+        {
+        }
+    }
+
+    public class VerifiedAttribute : System.Attribute
+    {
+    }
+}
@@ -0,0 +1,23 @@
+using Konveyor.Demos.Services;
+
+namespace Konveyor.Demos.Dinners
+{
+    public class DinnerService
+    {
+        [WebMethod]
+        public string Find(int id)
+        {
+            return "dinner";
+        }
+
+        [WebMethodAttribute, ScriptMethod]
+        public void Cancel(int id)
+        {
+        }
+
+        [ScriptMethod]
+        public void Ping()
+        {
+        }
+    }
+}
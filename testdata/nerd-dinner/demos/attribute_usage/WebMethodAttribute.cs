@@ -0,0 +1,10 @@
+namespace Konveyor.Demos.Services
+{
+    public class WebMethodAttribute : System.Attribute
+    {
+    }
+
+    public class ScriptMethodAttribute : System.Attribute
+    {
+    }
+}
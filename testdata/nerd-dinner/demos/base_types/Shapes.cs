@@ -0,0 +1,25 @@
+namespace Konveyor.Demos.Inheritance
+{
+    public interface IFillable
+    {
+    }
+
+    public class Element
+    {
+    }
+
+    public class Shape : Element
+    {
+        public void Draw()
+        {
+        }
+    }
+
+    public class Circle : Shape, IFillable
+    {
+        public void Fill()
+        {
+            Draw();
+        }
+    }
+}
@@ -0,0 +1,12 @@
+using Konveyor.Demos.Chained;
+
+namespace Konveyor.Demos.Consumer
+{
+    public class Reader
+    {
+        public string Read()
+        {
+            return Settings.GetConfig().GetSection("x").Value;
+        }
+    }
+}
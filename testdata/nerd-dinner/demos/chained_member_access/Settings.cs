@@ -0,0 +1,23 @@
+namespace Konveyor.Demos.Chained
+{
+    public class Settings
+    {
+        public static Config GetConfig()
+        {
+            return new Config();
+        }
+    }
+
+    public class Config
+    {
+        public Section GetSection(string name)
+        {
+            return new Section();
+        }
+    }
+
+    public class Section
+    {
+        public string Value;
+    }
+}
@@ -0,0 +1,9 @@
+namespace Konveyor.Demos.Comments
+{
+    public class Gateway
+    {
+        public static void Send()
+        {
+        }
+    }
+}
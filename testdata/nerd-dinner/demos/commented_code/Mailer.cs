@@ -0,0 +1,17 @@
+using Konveyor.Demos.Comments;
+
+namespace Konveyor.Demos.Mail
+{
+    public class Mailer
+    {
+        /// <example>
+        /// <code>Gateway.Send();</code>
+        /// </example>
+        public string Notify()
+        {
+            // Gateway.Send();
+            Gateway.Send();
+            return "Gateway.Send";
+        }
+    }
+}
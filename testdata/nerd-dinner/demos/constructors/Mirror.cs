@@ -0,0 +1,15 @@
+using Konveyor.Demos.Transfer;
+
+namespace Konveyor.Demos.Mirroring
+{
+    public class Mirror
+    {
+        public string Fetch(string path)
+        {
+            var local = new WebClient();
+            var remote = new WebClient("https://mirror.example.com");
+            var slow = new WebClient("https://archive.example.com", 300);
+            return local.Download(path) + remote.Download(path) + slow.Download(path);
+        }
+    }
+}
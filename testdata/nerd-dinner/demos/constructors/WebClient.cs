@@ -0,0 +1,29 @@
+namespace Konveyor.Demos.Transfer
+{
+    public class WebClient
+    {
+        private readonly string baseAddress;
+        private readonly int timeout;
+
+        public WebClient()
+            : this("http://localhost")
+        {
+        }
+
+        public WebClient(string baseAddress)
+            : this(baseAddress, 100)
+        {
+        }
+
+        public WebClient(string baseAddress, int timeout)
+        {
+            this.baseAddress = baseAddress;
+            this.timeout = timeout;
+        }
+
+        public string Download(string path)
+        {
+            return baseAddress + path;
+        }
+    }
+}
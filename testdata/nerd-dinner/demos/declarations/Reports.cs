@@ -0,0 +1,12 @@
+using Konveyor.Demos.Inventory;
+
+namespace Konveyor.Demos.InventoryReports
+{
+    public class Report
+    {
+        public Item Top()
+        {
+            return new Item("bolt", Unit.Piece);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+namespace Konveyor.Demos.Inventory
+{
+    public class Warehouse
+    {
+        public enum Zone
+        {
+            Cold,
+            Dry
+        }
+
+        public class Shelf
+        {
+            public int Capacity { get; set; }
+        }
+    }
+
+    public interface IStock
+    {
+        int Count();
+    }
+
+    public enum Unit
+    {
+        Piece,
+        Crate
+    }
+
+    public record Item(string Name, Unit Unit);
+}
+
+namespace Konveyor.Demos.Inventory.Audit
+{
+    public class Auditor
+    {
+        public void Check()
+        {
+        }
+    }
+}
@@ -0,0 +1,10 @@
+namespace Konveyor.Demos.Legacy
+{
+    public class LegacyFormatter
+    {
+        public static string Format(decimal amount)
+        {
+            return amount.ToString();
+        }
+    }
+}
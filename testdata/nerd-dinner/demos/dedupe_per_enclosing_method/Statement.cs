@@ -0,0 +1,15 @@
+using Konveyor.Demos.Legacy;
+
+namespace Konveyor.Demos.Statements
+{
+    public class Statement
+    {
+        public string Render(decimal opening, decimal change, decimal closing)
+        {
+            var header = LegacyFormatter.Format(opening);
+            var body = LegacyFormatter.Format(change);
+            var footer = LegacyFormatter.Format(closing);
+            return header + body + footer;
+        }
+    }
+}
@@ -0,0 +1,9 @@
+namespace Konveyor.Demos.Tooling
+{
+    public class Tool
+    {
+        public static void Use()
+        {
+        }
+    }
+}
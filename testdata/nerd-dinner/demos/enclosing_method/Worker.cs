@@ -0,0 +1,23 @@
+using Konveyor.Demos.Tooling;
+
+namespace Konveyor.Demos.Workers
+{
+    public class Worker
+    {
+        public void Run(bool ready)
+        {
+            if (ready)
+            {
+                Tool.Use();
+            }
+        }
+
+        public class Helper
+        {
+            public void Assist()
+            {
+                Tool.Use();
+            }
+        }
+    }
+}
@@ -0,0 +1,11 @@
+using System;
+
+namespace Konveyor.Demos.Errors
+{
+    public class LegacyConfigurationException : Exception
+    {
+        public LegacyConfigurationException(string message) : base(message)
+        {
+        }
+    }
+}
@@ -0,0 +1,28 @@
+using Konveyor.Demos.Errors;
+
+namespace Konveyor.Demos.Handling
+{
+    public class Loader
+    {
+        public string Load(string path)
+        {
+            try
+            {
+                return Read(path);
+            }
+            catch (LegacyConfigurationException)
+            {
+                return null;
+            }
+        }
+
+        public string Read(string path)
+        {
+            if (path == "")
+            {
+                throw new LegacyConfigurationException("empty path");
+            }
+            return path ?? throw new Konveyor.Demos.Errors.LegacyConfigurationException("no path");
+        }
+    }
+}
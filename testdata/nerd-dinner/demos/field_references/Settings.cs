@@ -0,0 +1,11 @@
+namespace Konveyor.Demos.Configuration
+{
+    public static class Settings
+    {
+        public static string ConnectionName = "Default";
+
+        public static int TimeoutSeconds { get; set; } = 30;
+
+        public static string Environment => "production";
+    }
+}
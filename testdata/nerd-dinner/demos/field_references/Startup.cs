@@ -0,0 +1,13 @@
+using Konveyor.Demos.Configuration;
+
+namespace Konveyor.Demos.Startup
+{
+    public class Startup
+    {
+        public string Connect()
+        {
+            Settings.TimeoutSeconds = 60;
+            return Settings.ConnectionName;
+        }
+    }
+}
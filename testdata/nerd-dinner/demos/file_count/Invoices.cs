@@ -0,0 +1,12 @@
+using Konveyor.Demos.Mailing;
+
+namespace Konveyor.Demos.Billing
+{
+    public class Invoices
+    {
+        public void Notify(string email)
+        {
+            new Mailer().Send(email, "Your invoice");
+        }
+    }
+}
@@ -0,0 +1,9 @@
+namespace Konveyor.Demos.Mailing
+{
+    public class Mailer
+    {
+        public void Send(string to, string body)
+        {
+        }
+    }
+}
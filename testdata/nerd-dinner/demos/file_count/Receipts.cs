@@ -0,0 +1,10 @@
+namespace Konveyor.Demos.Billing
+{
+    public class Receipts
+    {
+        public string Format(decimal amount)
+        {
+            return amount.ToString("C");
+        }
+    }
+}
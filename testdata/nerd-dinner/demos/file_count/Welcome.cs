@@ -0,0 +1,14 @@
+using Konveyor.Demos.Mailing;
+
+namespace Konveyor.Demos.Signup
+{
+    public class Welcome
+    {
+        public void Greet(string email)
+        {
+            var mailer = new Mailer();
+            mailer.Send(email, "Welcome");
+            mailer.Send(email, "Getting started");
+        }
+    }
+}
@@ -0,0 +1,43 @@
+namespace Konveyor.Demos.FileScoped;
+
+public class Greeter : IGreeter
+{
+    public string Greet(string name)
+    {
+        return "Hello " + name;
+    }
+}
+
+public class Caller
+{
+    public string Run()
+    {
+        var greeter = new Greeter();
+        return greeter.Greet("world");
+    }
+}
+
+public interface IGreeter
+{
+    string Greet(string name);
+}
+
+public record Greeting(string Text);
+
+public struct Point
+{
+    public int X;
+}
+
+public enum Tone
+{
+    Formal,
+    Casual
+}
+
+public class Styled
+{
+    public Greeting Greeting;
+    public Point Position;
+    public Tone Tone;
+}
@@ -0,0 +1,10 @@
+namespace Konveyor.Demos.Constraints
+{
+    public interface IEntity
+    {
+    }
+
+    public class AuditedEntity
+    {
+    }
+}
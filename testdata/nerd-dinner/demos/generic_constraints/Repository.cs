@@ -0,0 +1,11 @@
+using Konveyor.Demos.Constraints;
+
+namespace Konveyor.Demos.Storage
+{
+    public class Repository<T> where T : IEntity, new()
+    {
+        public void Copy<TOther>(TOther other) where TOther : AuditedEntity
+        {
+        }
+    }
+}
@@ -0,0 +1,22 @@
+using System.Collections.Generic;
+
+namespace Konveyor.Demos.Indexers
+{
+    public class Configuration
+    {
+        private readonly Dictionary<string, string> values = new Dictionary<string, string>();
+
+        public string Environment { get; set; }
+
+        public static Configuration Load()
+        {
+            return new Configuration();
+        }
+
+        public string this[string key]
+        {
+            get { return values[key]; }
+            set { values[key] = value; }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+using Konveyor.Demos.Indexers;
+
+namespace Konveyor.Demos.Hosting
+{
+    public class Startup
+    {
+        public string Configure()
+        {
+            Configuration configuration = new Configuration();
+            configuration["connection"] = "Server=db";
+            Configuration.Load().Environment = "Production";
+            return configuration["connection"];
+        }
+
+        public string Environment()
+        {
+            var configuration = new Configuration();
+            return configuration["environment"] + Configuration.Load().Environment;
+        }
+    }
+}
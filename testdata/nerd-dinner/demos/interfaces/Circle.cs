@@ -0,0 +1,31 @@
+using System;
+using Konveyor.Demos.Shapes;
+
+namespace Konveyor.Demos.Drawing
+{
+    public class Circle : IShape, IDisposable
+    {
+        private readonly double radius;
+
+        public Circle(double radius)
+        {
+            this.radius = radius;
+        }
+
+        public double Area()
+        {
+            return Math.PI * radius * radius;
+        }
+
+        public void Dispose()
+        {
+        }
+    }
+
+    public class Canvas
+    {
+        public void Draw(Circle circle)
+        {
+        }
+    }
+}
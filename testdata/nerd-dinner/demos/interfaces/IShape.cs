@@ -0,0 +1,7 @@
+namespace Konveyor.Demos.Shapes
+{
+    public interface IShape
+    {
+        double Area();
+    }
+}
@@ -0,0 +1,10 @@
+namespace Konveyor.Demos.MethodAttributes
+{
+    public class AuditedAttribute : System.Attribute
+    {
+    }
+
+    public class NotNullAttribute : System.Attribute
+    {
+    }
+}
@@ -0,0 +1,18 @@
+using Konveyor.Demos.MethodAttributes;
+
+namespace Konveyor.Demos.Orders
+{
+    public class OrderService
+    {
+        [Audited]
+        public void Place([NotNull] string id)
+        {
+        }
+
+        [return: NotNull]
+        public string Describe()
+        {
+            return "order";
+        }
+    }
+}
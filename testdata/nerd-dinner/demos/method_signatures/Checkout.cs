@@ -0,0 +1,14 @@
+using Konveyor.Demos.Receipts;
+
+namespace Konveyor.Demos.Shop
+{
+    public class Checkout : ReceiptFormatter
+    {
+        public string Summary(decimal total)
+        {
+            var plain = Format(total);
+            var priced = Format(total, "EUR");
+            return plain + "\n" + priced;
+        }
+    }
+}
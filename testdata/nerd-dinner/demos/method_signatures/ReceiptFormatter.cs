@@ -0,0 +1,15 @@
+namespace Konveyor.Demos.Receipts
+{
+    public class ReceiptFormatter
+    {
+        public string Format(decimal amount)
+        {
+            return amount.ToString("0.00");
+        }
+
+        public string Format(decimal amount, string currency)
+        {
+            return Format(amount) + " " + currency;
+        }
+    }
+}
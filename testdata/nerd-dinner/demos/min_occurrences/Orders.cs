@@ -0,0 +1,15 @@
+using Konveyor.Demos.Telemetry;
+
+namespace Konveyor.Demos.Orders
+{
+    public class OrderService
+    {
+        public void Place()
+        {
+            Tracker.Track("placed");
+            Tracker.Track("paid");
+            Tracker.Track("shipped");
+            Auditor.Record("order");
+        }
+    }
+}
@@ -0,0 +1,16 @@
+namespace Konveyor.Demos.Telemetry
+{
+    public class Tracker
+    {
+        public static void Track(string name)
+        {
+        }
+    }
+
+    public class Auditor
+    {
+        public static void Record(string name)
+        {
+        }
+    }
+}
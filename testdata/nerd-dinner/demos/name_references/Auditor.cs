@@ -0,0 +1,18 @@
+using System;
+using Konveyor.Demos.Data;
+
+namespace Konveyor.Demos.Audit
+{
+    public class Auditor
+    {
+        public void Audit()
+        {
+            var operation = nameof(Repository.Save);
+            var type = nameof(Repository);
+            var save = typeof(Repository).GetMethod("Save");
+            var repository = Type.GetType("Konveyor.Demos.Data.Repository");
+            var missing = typeof(Repository).GetMethod("Delete");
+            var local = nameof(operation);
+        }
+    }
+}
@@ -0,0 +1,9 @@
+namespace Konveyor.Demos.Data
+{
+    public class Repository
+    {
+        public void Save()
+        {
+        }
+    }
+}
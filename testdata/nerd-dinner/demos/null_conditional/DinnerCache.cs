@@ -0,0 +1,21 @@
+namespace Konveyor.Demos.Caching
+{
+    public class DinnerCache
+    {
+        public int Count { get; set; }
+
+        public static DinnerCache Open()
+        {
+            return null;
+        }
+
+        public DinnerCache Parent()
+        {
+            return null;
+        }
+
+        public void Flush()
+        {
+        }
+    }
+}
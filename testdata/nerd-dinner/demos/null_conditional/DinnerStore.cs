@@ -0,0 +1,15 @@
+using Konveyor.Demos.Caching;
+
+namespace Konveyor.Demos.Dinners
+{
+    public class DinnerStore
+    {
+        public void Store()
+        {
+            DinnerCache.Open()?.Flush();
+            DinnerCache.Open()?.Parent()?.Flush();
+            var cache = DinnerCache.Open() ?? new DinnerCache();
+            var count = DinnerCache.Open()?.Count ?? 0;
+        }
+    }
+}
@@ -0,0 +1,14 @@
+using System.Collections.Generic;
+using Konveyor.Demos.Init;
+
+namespace Konveyor.Demos.Startup
+{
+    public class Bootstrap
+    {
+        public List<Options> Configure()
+        {
+            var primary = new Options { Retries = 3, Name = "primary" };
+            return new List<Options> { primary, new Options { Name = "fallback" } };
+        }
+    }
+}
@@ -0,0 +1,9 @@
+namespace Konveyor.Demos.Init
+{
+    public class Options
+    {
+        public int Retries { get; set; }
+
+        public string Name { get; set; }
+    }
+}
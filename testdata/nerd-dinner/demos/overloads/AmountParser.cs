@@ -0,0 +1,22 @@
+using System;
+
+namespace Konveyor.Demos.Amounts
+{
+    public class AmountParser
+    {
+        public decimal Parse(string text)
+        {
+            return decimal.Parse(text);
+        }
+
+        public decimal Parse(string text, IFormatProvider provider)
+        {
+            return decimal.Parse(text, provider);
+        }
+
+        public decimal Parse(string text, int scale)
+        {
+            return Math.Round(decimal.Parse(text), scale);
+        }
+    }
+}
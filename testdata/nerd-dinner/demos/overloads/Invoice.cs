@@ -0,0 +1,22 @@
+using System;
+using System.Globalization;
+using Konveyor.Demos.Amounts;
+
+namespace Konveyor.Demos.Billing
+{
+    public class Invoice : AmountParser
+    {
+        public decimal Total(string subtotal, string tax)
+        {
+            // Parsed with the culture of the server.
+            var total = Parse(subtotal);
+            // Only these two pick the overload taking a format provider.
+            total += Parse("1.50", CultureInfo.InvariantCulture);
+            IFormatProvider provider = CultureInfo.CurrentCulture;
+            total += new AmountParser().Parse(tax, provider);
+            // Same number of arguments, but an int scale.
+            total += Parse("2.125", 2);
+            return total;
+        }
+    }
+}
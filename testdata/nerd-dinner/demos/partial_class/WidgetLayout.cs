@@ -0,0 +1,10 @@
+namespace Konveyor.Demos.Partial
+{
+    public partial class Widget
+    {
+        public int Measure()
+        {
+            return 42;
+        }
+    }
+}
@@ -0,0 +1,12 @@
+using Konveyor.Demos.Records;
+
+namespace Konveyor.Demos.Shop
+{
+    public class Checkout
+    {
+        public string Greet()
+        {
+            return new Customer("Ada", 36).Name;
+        }
+    }
+}
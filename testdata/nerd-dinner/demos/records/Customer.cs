@@ -0,0 +1,4 @@
+namespace Konveyor.Demos.Records
+{
+    public record Customer(string Name, int Age);
+}
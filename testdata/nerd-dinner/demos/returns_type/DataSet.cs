@@ -0,0 +1,6 @@
+namespace Konveyor.Demos.Ledger
+{
+    public class DataSet
+    {
+    }
+}
@@ -0,0 +1,27 @@
+using System.Collections.Generic;
+using Konveyor.Demos.Ledger;
+
+namespace Konveyor.Demos.LedgerAccess
+{
+    public class OrderRepository
+    {
+        public DataSet LoadOrders()
+        {
+            return new DataSet();
+        }
+
+        public DataSet? FindOrder(int id)
+        {
+            return null;
+        }
+
+        public List<DataSet> LoadPages()
+        {
+            return new List<DataSet>();
+        }
+
+        public void Save(DataSet orders)
+        {
+        }
+    }
+}
@@ -0,0 +1,14 @@
+using Konveyor.Demos.Routing;
+
+namespace Konveyor.Demos.Storefront
+{
+    [Route("api/[controller]")]
+    public class OrdersController
+    {
+        [Route("{id}", Name = "GetOrder", Order = 2)]
+        public string Get(int id)
+        {
+            return id.ToString();
+        }
+    }
+}
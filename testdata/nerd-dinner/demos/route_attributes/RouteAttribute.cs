@@ -0,0 +1,19 @@
+using System;
+
+namespace Konveyor.Demos.Routing
+{
+    [AttributeUsage(AttributeTargets.Class | AttributeTargets.Method)]
+    public class RouteAttribute : Attribute
+    {
+        public RouteAttribute(string template)
+        {
+            Template = template;
+        }
+
+        public string Template { get; }
+
+        public string Name { get; set; }
+
+        public int Order { get; set; }
+    }
+}
@@ -0,0 +1,40 @@
+namespace Konveyor.Demos.Security
+{
+    public class Policy
+    {
+        public void Enforce()
+        {
+        }
+    }
+}
+
+namespace Konveyor.Demos.Security.Crypto
+{
+    public class Hasher
+    {
+        public string Hash(string input)
+        {
+            return input;
+        }
+    }
+}
+
+namespace Konveyor.Demos.Security.Crypto.Keys
+{
+    public class KeyStore
+    {
+        public void Load()
+        {
+        }
+    }
+}
+
+namespace Konveyor.Demos.Security.Audit
+{
+    public class AuditLog
+    {
+        public void Record()
+        {
+        }
+    }
+}
@@ -0,0 +1,22 @@
+using Konveyor.Demos.Security;
+using Konveyor.Demos.Security.Audit;
+using Konveyor.Demos.Security.Crypto;
+using Konveyor.Demos.Security.Crypto.Keys;
+
+namespace Konveyor.Demos.Vault
+{
+    public class Vault
+    {
+        public void Open()
+        {
+            Policy policy = new Policy();
+            policy.Enforce();
+            Hasher hasher = new Hasher();
+            hasher.Hash("secret");
+            KeyStore store = new KeyStore();
+            store.Load();
+            AuditLog log = new AuditLog();
+            log.Record();
+        }
+    }
+}
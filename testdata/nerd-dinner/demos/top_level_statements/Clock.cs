@@ -0,0 +1,10 @@
+namespace Konveyor.Demos.TopLevel
+{
+    public static class Clock
+    {
+        public static string Now()
+        {
+            return "now";
+        }
+    }
+}
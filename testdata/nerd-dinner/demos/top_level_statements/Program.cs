@@ -0,0 +1,8 @@
+using Konveyor.Demos.TopLevel;
+
+var time = Clock.Now();
+Report(time);
+
+static void Report(string value)
+{
+}
@@ -0,0 +1,36 @@
+using Konveyor.Demos.Principals;
+
+namespace Konveyor.Demos.Authorization
+{
+    public class Authorizer
+    {
+        public bool IsLegacy(object user)
+        {
+            return user is LegacyPrincipal;
+        }
+
+        public string NameOf(object user)
+        {
+            if (user is LegacyPrincipal principal)
+            {
+                return principal.Name;
+            }
+            var identity = user as LegacyIdentity;
+            return null;
+        }
+
+        public int Describe(object user)
+        {
+            switch (user)
+            {
+                case LegacyIdentity identity:
+                    return 1;
+            }
+            return user switch
+            {
+                LegacyPrincipal => 2,
+                _ => 0,
+            };
+        }
+    }
+}
@@ -0,0 +1,11 @@
+namespace Konveyor.Demos.Principals
+{
+    public class LegacyPrincipal
+    {
+        public string Name { get; set; }
+    }
+
+    public class LegacyIdentity
+    {
+    }
+}
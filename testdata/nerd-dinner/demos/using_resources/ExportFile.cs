@@ -0,0 +1,22 @@
+using System;
+
+namespace Konveyor.Demos.Resources
+{
+    public class ExportFile : IDisposable
+    {
+        public ExportFile(string path)
+        {
+            Path = path;
+        }
+
+        public string Path { get; }
+
+        public void Write(string line)
+        {
+        }
+
+        public void Dispose()
+        {
+        }
+    }
+}
@@ -0,0 +1,20 @@
+using Konveyor.Demos.Resources;
+
+namespace Konveyor.Demos.Exports
+{
+    public class Exporter
+    {
+        public void Export(string path)
+        {
+            using (var file = new ExportFile(path))
+            {
+                file.Write("header");
+            }
+        }
+
+        public void ExportAll(string path)
+        {
+            using ExportFile file = new ExportFile(path);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+using Contoso.Text;
+
+namespace Contoso.App
+{
+    public class Program
+    {
+        public static void Main()
+        {
+            Slug.Create("Hello World");
+        }
+    }
+}
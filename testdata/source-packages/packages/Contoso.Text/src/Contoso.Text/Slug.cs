@@ -0,0 +1,10 @@
+namespace Contoso.Text
+{
+    public static class Slug
+    {
+        public static string Create(string title)
+        {
+            return title.ToLowerInvariant().Replace(' ', '-');
+        }
+    }
+}
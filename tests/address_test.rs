@@ -0,0 +1,59 @@
+use std::net::TcpListener;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+use tonic::transport::Channel;
+
+use c_sharp_analyzer_provider_cli::analyzer_service::provider_service_client::ProviderServiceClient;
+
+struct Provider(Child);
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[tokio::test]
+async fn address_binds_the_port_on_an_ipv4_address() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let _provider = Provider(
+        Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+            .args(["--port", &port.to_string(), "--address", "0.0.0.0"])
+            .spawn()
+            .unwrap(),
+    );
+    let endpoint = format!("http://127.0.0.1:{}", port);
+    for _ in 0..50 {
+        if let Ok(channel) = Channel::from_shared(endpoint.clone())
+            .unwrap()
+            .connect()
+            .await
+        {
+            let capabilities = ProviderServiceClient::new(channel)
+                .capabilities(())
+                .await
+                .unwrap();
+            assert_eq!(capabilities.get_ref().capabilities[0].name, "referenced");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("provider did not start listening on {}", endpoint);
+}
+
+#[test]
+fn address_that_is_not_an_ip_is_rejected() {
+    let output = Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+        .args(["--port", "0", "--address", "localhost:80"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is not an IP address"), "{}", stderr);
+}
@@ -0,0 +1,24 @@
+use std::process::Command;
+
+#[test]
+fn dump_capabilities_prints_the_capabilities_as_json() {
+    let output = Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+        .arg("--dump-capabilities")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let manifest: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let names: Vec<&str> = manifest["capabilities"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|c| c["name"].as_str())
+        .collect();
+    assert!(names.contains(&"referenced"), "{:?}", names);
+    assert!(
+        manifest["schema"]["components"]["schemas"]["ReferenceCondition"].is_object(),
+        "{}",
+        manifest["schema"]
+    );
+}
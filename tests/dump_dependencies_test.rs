@@ -0,0 +1,62 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn dump_dependencies_prints_the_versions_paket_lock_resolved() {
+    let temp = tempfile::Builder::new()
+        .prefix("dump-dependencies-")
+        .tempdir()
+        .unwrap();
+    let dir = temp.path();
+    // Neither an unpinned package nor one without a restriction is left out.
+    fs::write(
+        dir.join("paket.dependencies"),
+        "source https://www.nuget.org/api/v2
+
+nuget Antlr
+nuget EntityFramework >= 6.0 import_targets: false, restriction: >= net45
+
+group Build
+source https://www.nuget.org/api/v2
+nuget FAKE ~> 5.0
+",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("paket.lock"),
+        "NUGET
+  remote: https://www.nuget.org/api/v2
+    Antlr (3.4.1.9004)
+    EntityFramework (6.1.3)
+
+GROUP Build
+NUGET
+  remote: https://www.nuget.org/api/v2
+    FAKE (5.23.1)
+",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+        .arg("dump-dependencies")
+        .arg(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let dump: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let versions: Vec<(&str, &str)> = dump
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| (d["name"].as_str().unwrap(), d["version"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        versions,
+        vec![
+            ("Antlr", "3.4.1.9004"),
+            ("EntityFramework", "6.1.3"),
+            ("FAKE", "5.23.1")
+        ]
+    );
+}
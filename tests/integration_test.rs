@@ -1,16 +1,17 @@
-use std::path::absolute;
+use std::env;
+use std::path::{absolute, Path};
 use std::{fs::File, path::PathBuf, str::FromStr};
 
-use prost_types::value::Kind::StringValue;
-use prost_types::Value;
+use prost_types::value::Kind::StructValue;
 use serde::Deserialize;
+use tonic::transport::Channel;
 use walkdir::WalkDir;
 
 use c_sharp_analyzer_provider_cli::analyzer_service::IncidentContext;
 use c_sharp_analyzer_provider_cli::analyzer_service::{
     provider_service_client::ProviderServiceClient, EvaluateRequest,
 };
-use c_sharp_analyzer_provider_cli::c_sharp_graph::results::ResultNode;
+use c_sharp_analyzer_provider_cli::c_sharp_graph::results::{serde_json_to_prost, ResultNode};
 
 #[derive(Deserialize, Debug)]
 pub struct TestEvaluateRequest {
@@ -29,11 +30,33 @@ impl From<TestEvaluateRequest> for EvaluateRequest {
     }
 }
 
+/// Demos with both a request and an expected output, optionally narrowed down to the one
+/// named by `filter`, either by its directory name or its path under `demos/`.
+fn select_demos(demos_path: &Path, filter: Option<&str>) -> Vec<PathBuf> {
+    let mut demos = vec![];
+    for entry in WalkDir::new(demos_path).sort_by_file_name() {
+        let entry = entry.unwrap();
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        let demo = entry.into_path();
+        if !demo.join("request.yaml").exists() || !demo.join("demo-output.yaml").exists() {
+            continue;
+        }
+        if let Some(filter) = filter {
+            let name = demo.file_name().unwrap().to_string_lossy();
+            let relative = demo.strip_prefix(demos_path).unwrap();
+            if name != filter && relative != Path::new(filter) {
+                continue;
+            }
+        }
+        demos.push(demo);
+    }
+    demos
+}
+
 #[tokio::test]
 async fn integration_tests() {
-    let mut client = ProviderServiceClient::connect("http://localhost:9000")
-        .await
-        .unwrap();
     let current_file = file!();
     let file_path = absolute(PathBuf::from_str(current_file).unwrap()).unwrap();
     println!("{:?}", file_path);
@@ -42,68 +65,123 @@ async fn integration_tests() {
     let base = parent.parent().unwrap();
     let base: String = base.to_string_lossy().into_owned();
     let demos_path = parent.to_path_buf().join("demos");
-    println!("Walking dir: {:?}", demos_path);
-    for entry in WalkDir::new(&demos_path) {
-        let entry = entry.unwrap();
-        if !entry.file_type().is_dir() {
-            continue;
-        }
-        let request_file = entry.clone().into_path().join("request.yaml");
-        if !request_file.exists() {
-            continue;
-        }
-        let demo_ouput = entry.clone().into_path().join("demo-output.yaml");
-        if !demo_ouput.exists() {
-            continue;
+    let filter = env::var("DEMO_FILTER").ok();
+    let demos = select_demos(&demos_path, filter.as_deref());
+    println!("Running {} demos from: {:?}", demos.len(), demos_path);
+    assert!(!demos.is_empty(), "no demo matches {:?}", filter);
+
+    let mut client = ProviderServiceClient::connect("http://localhost:9000")
+        .await
+        .unwrap();
+
+    let mut outcomes: Vec<(PathBuf, Result<(), String>)> = vec![];
+    for demo in demos {
+        let outcome = run_demo(&mut client, &demo, &base).await;
+        outcomes.push((demo, outcome));
+    }
+
+    println!("demo summary:");
+    for (demo, outcome) in outcomes.iter() {
+        let name = demo.strip_prefix(&demos_path).unwrap().display();
+        match outcome {
+            Ok(()) => println!("  PASS {}", name),
+            Err(e) => println!("  FAIL {}: {}", name, e),
         }
+    }
+    let failed = outcomes.iter().filter(|(_, o)| o.is_err()).count();
+    assert_eq!(failed, 0, "{} of {} demos failed", failed, outcomes.len());
+}
+
+async fn run_demo(
+    client: &mut ProviderServiceClient<Channel>,
+    demo: &Path,
+    base: &str,
+) -> Result<(), String> {
+    let requst_file = File::open(demo.join("request.yaml")).unwrap();
+
+    let request: TestEvaluateRequest = serde_yml::from_reader(requst_file).unwrap();
+    let request: EvaluateRequest = request.into();
+
+    println!("here: {:?}", request);
 
-        let requst_file = File::open(&request_file).unwrap();
-
-        let request: TestEvaluateRequest = serde_yml::from_reader(requst_file).unwrap();
-        let request: EvaluateRequest = request.into();
-
-        println!("here: {:?}", request);
-
-        let result = client.evaluate(request).await.unwrap().into_inner();
-        println!("result -- {:?}", result);
-        assert!(result.successful);
-        let expected_file = File::open(&demo_ouput).unwrap();
-        let expected_output: Vec<ResultNode> = serde_json::from_reader(expected_file).unwrap();
-        let expected_output: Vec<IncidentContext> = expected_output
-            .iter()
-            .map(|rn| {
-                let mut x: IncidentContext = (*rn).clone().into();
-                if x.file_uri.contains("<REPLACE_ME>") {
-                    x.file_uri = x.file_uri.replace("<REPLACE_ME>", &base);
-                    let mut var = x.variables.clone().unwrap();
-                    if let Some(s) = var.fields.get("file") {
-                        if let Some(StringValue(y)) = &s.kind {
-                            var.fields.insert(
-                                "file".to_string(),
-                                Value {
-                                    kind: Some(StringValue(y.replace("<REPLACE_ME>", &base))),
-                                },
-                            );
-                        }
-                    }
-                    x.variables = Some(var);
-                }
-
-                x
-            })
-            .collect();
-        match result.response {
-            None => panic!(),
-            Some(x) => {
-                for (i, ic) in x.incident_contexts.iter().enumerate() {
-                    assert_eq!(
-                        ic,
-                        expected_output.get(i).unwrap(),
-                        "test case: {:?}",
-                        entry
-                    );
-                }
+    let result = client
+        .evaluate(request)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_inner();
+    println!("result -- {:?}", result);
+    if !result.successful {
+        return Err(format!("evaluate was not successful: {}", result.error));
+    }
+    // The files of the expected incidents, nested in their variables too, are relative to
+    // where the tests run.
+    let expected_output = std::fs::read_to_string(demo.join("demo-output.yaml"))
+        .unwrap()
+        .replace("<REPLACE_ME>", base);
+    let expected_output: Vec<ResultNode> = serde_json::from_str(&expected_output).unwrap();
+    let expected_output: Vec<IncidentContext> =
+        expected_output.into_iter().map(Into::into).collect();
+    let Some(x) = result.response else {
+        return Err("no response".to_string());
+    };
+    for (i, ic) in x.incident_contexts.iter().enumerate() {
+        // Every incident carries the text of the line it is on for display.
+        if !ic
+            .variables
+            .as_ref()
+            .is_some_and(|v| v.fields.contains_key("line"))
+        {
+            return Err(format!("incident {} has no line: {:?}", i, ic));
+        }
+        match expected_output.get(i) {
+            Some(expected) if expected == ic => (),
+            Some(expected) => {
+                return Err(format!(
+                    "incident {} differs\n  got:      {:?}\n  expected: {:?}",
+                    i, ic, expected
+                ))
             }
+            None => return Err(format!("unexpected incident {}: {:?}", i, ic)),
+        }
+    }
+    if x.incident_contexts.len() != expected_output.len() {
+        return Err(format!(
+            "got {} incidents, expected {}, missing: {:?}",
+            x.incident_contexts.len(),
+            expected_output.len(),
+            &expected_output[x.incident_contexts.len()..]
+        ));
+    }
+    // The template context of the response is only checked by the demos that expect one.
+    let context_file = demo.join("demo-context.yaml");
+    if context_file.exists() {
+        let expected: serde_json::Value =
+            serde_json::from_reader(File::open(context_file).unwrap()).unwrap();
+        let expected = match serde_json_to_prost(expected).kind {
+            Some(StructValue(context)) => Some(context),
+            _ => return Err("demo-context.yaml is not an object".to_string()),
+        };
+        if x.template_context != expected {
+            return Err(format!(
+                "template context differs\n  got:      {:?}\n  expected: {:?}",
+                x.template_context, expected
+            ));
         }
     }
+    Ok(())
+}
+
+#[test]
+fn demo_filter_selects_one_demo() {
+    let demos_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/demos");
+    let all = select_demos(&demos_path, None);
+    assert!(all.len() > 1, "{:?}", all);
+
+    for filter in ["subtree", "csharp_syntax/subtree"] {
+        assert_eq!(
+            select_demos(&demos_path, Some(filter)),
+            vec![demos_path.join("csharp_syntax/subtree")]
+        );
+    }
+    assert!(select_demos(&demos_path, Some("no_such_demo")).is_empty());
 }
@@ -0,0 +1,85 @@
+use std::net::TcpListener;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+use tonic::transport::Channel;
+
+use c_sharp_analyzer_provider_cli::analyzer_service::{
+    provider_service_client::ProviderServiceClient, ServiceRequest,
+};
+
+struct Provider(Child);
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn port_and_socket_are_served_together() {
+    let temp = tempfile::Builder::new()
+        .prefix("listeners-")
+        .tempdir()
+        .unwrap();
+    let dir = temp.path();
+    let socket = dir.join("provider.sock");
+    let port = TcpListener::bind("[::1]:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut provider = Provider(
+        Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+            .args(["--port", &port.to_string(), "--socket"])
+            .arg(&socket)
+            .arg("--db-path")
+            .arg(dir.join("test.db"))
+            .spawn()
+            .unwrap(),
+    );
+    let endpoint = format!("http://[::1]:{}", port);
+    let mut channel = None;
+    for _ in 0..50 {
+        if let Ok(connected) = Channel::from_shared(endpoint.clone())
+            .unwrap()
+            .connect()
+            .await
+        {
+            channel = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut client =
+        ProviderServiceClient::new(channel.expect("provider did not listen on --port"));
+    assert!(
+        tokio::net::UnixStream::connect(&socket).await.is_ok(),
+        "provider did not listen on {:?}",
+        socket
+    );
+
+    // Stopping through one listener stops both.
+    client.stop(ServiceRequest { id: 1 }).await.unwrap();
+    let mut status = None;
+    for _ in 0..100 {
+        status = provider.0.try_wait().unwrap();
+        if status.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(status.is_some_and(|s| s.success()), "{:?}", status);
+}
+
+#[test]
+fn neither_port_nor_socket_is_an_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--port, --socket or both"), "{}", stderr);
+}
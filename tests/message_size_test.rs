@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use prost::Message;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Server};
+
+use c_sharp_analyzer_provider_cli::analyzer_service::provider_service_client::ProviderServiceClient;
+use c_sharp_analyzer_provider_cli::analyzer_service::EvaluateRequest;
+use c_sharp_analyzer_provider_cli::provider::{
+    AnalysisMode, AtomicCancellationFlag, CSharpProvider, Project, Tools,
+};
+
+const TONIC_DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+const LIBRARY: &str = r#"namespace Konveyor.Large
+{
+    public class Target
+    {
+        public static void Run()
+        {
+        }
+    }
+}
+"#;
+
+/// A project whose incidents add up to more than tonic's default limit. Every incident
+/// carries its file path, so the path is made long rather than indexing a huge file.
+async fn large_project(dir: &std::path::Path) -> Arc<Project> {
+    let mut location = dir.to_path_buf();
+    for segment in ["a", "b", "c", "d"] {
+        location = location.join(segment.repeat(200));
+    }
+    std::fs::create_dir_all(&location).unwrap();
+    std::fs::write(location.join("Library.cs"), LIBRARY).unwrap();
+    let calls = "            Target.Run();\n".repeat(1500);
+    std::fs::write(
+        location.join("Program.cs"),
+        format!(
+            "using Konveyor.Large;\n\nnamespace Konveyor.App\n{{\n    public class Program\n    {{\n        public static void Main()\n        {{\n{}        }}\n    }}\n}}\n",
+            calls
+        ),
+    )
+    .unwrap();
+
+    let project = Arc::new(Project::new(
+        location,
+        dir.join("message_size.db"),
+        AnalysisMode::SourceOnly,
+        Tools {
+            ilspy_cmd: Default::default(),
+            paket_cmd: Default::default(),
+            env: Default::default(),
+            ilspy_language_version: Tools::DEFAULT_ILSPY_LANGUAGE_VERSION,
+        },
+    ));
+    project.validate_language_configuration().await.unwrap();
+    project
+        .get_project_graph(&AtomicCancellationFlag::new())
+        .await
+        .unwrap();
+    project
+}
+
+async fn serve(project: Arc<Project>, max_message_size: usize) -> Channel {
+    let provider = CSharpProvider::new(project.db_path.clone());
+    provider.project.lock().await.replace(project);
+    let listener = TcpListener::bind("[::1]:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(provider.into_service(max_message_size))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    Channel::from_shared(format!("http://{}", addr))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap()
+}
+
+fn request() -> EvaluateRequest {
+    EvaluateRequest {
+        id: 1,
+        cap: "referenced".to_string(),
+        condition_info: r#"{"referenced": {"pattern": "Konveyor.Large.*"}}"#.to_string(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn responses_larger_than_the_tonic_default_are_delivered() {
+    let temp = tempfile::Builder::new()
+        .prefix("c_sharp_provider_message_size-")
+        .tempdir()
+        .unwrap();
+    let dir = temp.path();
+    let project = large_project(dir).await;
+
+    let channel = serve(project.clone(), CSharpProvider::DEFAULT_MAX_MESSAGE_SIZE).await;
+    let response = ProviderServiceClient::new(channel)
+        .max_decoding_message_size(usize::MAX)
+        .evaluate(request())
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.successful, "{}", response.error);
+    assert!(
+        response.encoded_len() > TONIC_DEFAULT_MAX_MESSAGE_SIZE,
+        "response is only {} bytes",
+        response.encoded_len()
+    );
+    let incidents = response.response.unwrap().incident_contexts;
+    assert!(incidents.len() >= 1500, "{} incidents", incidents.len());
+
+    // The same response does not fit when the server keeps the default.
+    let channel = serve(project, TONIC_DEFAULT_MAX_MESSAGE_SIZE).await;
+    let status = ProviderServiceClient::new(channel)
+        .max_decoding_message_size(usize::MAX)
+        .evaluate(request())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::OutOfRange, "{}", status);
+}
@@ -0,0 +1,81 @@
+use std::net::TcpListener;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+use tonic::transport::Channel;
+use tonic_reflection::pb::v1alpha::{
+    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
+    ServerReflectionRequest,
+};
+
+use c_sharp_analyzer_provider_cli::analyzer_service::provider_service_client::ProviderServiceClient;
+
+struct Provider(Child);
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn start_provider(extra_args: &[&str]) -> (Provider, Channel) {
+    let port = TcpListener::bind("[::1]:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let provider = Provider(
+        Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+            .args(["--port", &port.to_string()])
+            .args(extra_args)
+            .spawn()
+            .unwrap(),
+    );
+    let endpoint = format!("http://[::1]:{}", port);
+    for _ in 0..50 {
+        if let Ok(channel) = Channel::from_shared(endpoint.clone())
+            .unwrap()
+            .connect()
+            .await
+        {
+            return (provider, channel);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("provider did not start listening on {}", endpoint);
+}
+
+async fn list_services(channel: Channel) -> Result<(), tonic::Status> {
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut responses = ServerReflectionClient::new(channel)
+        .server_reflection_info(tokio_stream::iter(vec![request]))
+        .await?
+        .into_inner();
+    responses.message().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_reflection_disables_only_reflection() {
+    let (_provider, channel) = start_provider(&["--no-reflection"]).await;
+
+    let capabilities = ProviderServiceClient::new(channel.clone())
+        .capabilities(())
+        .await
+        .unwrap();
+    assert_eq!(capabilities.get_ref().capabilities[0].name, "referenced");
+
+    let status = list_services(channel).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unimplemented);
+}
+
+#[tokio::test]
+async fn reflection_is_served_by_default() {
+    let (_provider, channel) = start_provider(&[]).await;
+
+    list_services(channel).await.unwrap();
+}
@@ -0,0 +1,13 @@
+use std::process::Command;
+
+#[test]
+fn self_test_passes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+        .arg("self-test")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "self-test failed:\n{}", stdout);
+    assert!(!stdout.contains("FAIL"), "{}", stdout);
+    assert!(stdout.contains("self-test passed"), "{}", stdout);
+}
@@ -0,0 +1,45 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+struct Provider(Child);
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn socket_mode_sets_the_socket_permissions() {
+    let temp = tempfile::Builder::new()
+        .prefix("socket-mode-")
+        .tempdir()
+        .unwrap();
+    let dir = temp.path();
+    let socket = dir.join("provider.sock");
+
+    let _provider = Provider(
+        Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+            .arg("--socket")
+            .arg(&socket)
+            .args(["--socket-mode", "660", "--db-path"])
+            .arg(dir.join("test.db"))
+            .spawn()
+            .unwrap(),
+    );
+    let mut mode = None;
+    for _ in 0..50 {
+        if let Ok(metadata) = std::fs::metadata(&socket) {
+            mode = Some(metadata.permissions().mode() & 0o7777);
+            if mode == Some(0o660) {
+                break;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(mode, Some(0o660), "{:?}", socket);
+}
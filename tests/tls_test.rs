@@ -0,0 +1,97 @@
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+
+use c_sharp_analyzer_provider_cli::analyzer_service::provider_service_client::ProviderServiceClient;
+
+struct Provider(Child);
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn tls_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata/tls")
+        .join(name)
+}
+
+fn read(name: &str) -> Vec<u8> {
+    std::fs::read(tls_file(name)).unwrap()
+}
+
+async fn connect(port: u16, tls: ClientTlsConfig) -> Result<Channel, tonic::transport::Error> {
+    Channel::from_shared(format!("https://localhost:{}", port))
+        .unwrap()
+        .tls_config(tls)
+        .unwrap()
+        .connect()
+        .await
+}
+
+#[tokio::test]
+async fn tls_requires_a_client_certificate_signed_by_the_ca() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let _provider = Provider(
+        Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+            .args(["--port", &port.to_string(), "--address", "127.0.0.1"])
+            .arg("--tls-cert")
+            .arg(tls_file("server.pem"))
+            .arg("--tls-key")
+            .arg(tls_file("server.key"))
+            .arg("--tls-ca")
+            .arg(tls_file("ca.pem"))
+            .spawn()
+            .unwrap(),
+    );
+    let trusted = || ClientTlsConfig::new().ca_certificate(Certificate::from_pem(read("ca.pem")));
+    let client_tls =
+        || trusted().identity(Identity::from_pem(read("client.pem"), read("client.key")));
+
+    let mut channel = None;
+    for _ in 0..50 {
+        if let Ok(connected) = connect(port, client_tls()).await {
+            channel = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let channel = channel.expect("provider did not accept the client certificate");
+    let capabilities = ProviderServiceClient::new(channel)
+        .capabilities(())
+        .await
+        .unwrap();
+    assert_eq!(capabilities.get_ref().capabilities[0].name, "referenced");
+
+    // Without a client certificate the handshake, or the first request, is refused.
+    let refused = match connect(port, trusted()).await {
+        Ok(channel) => ProviderServiceClient::new(channel)
+            .capabilities(())
+            .await
+            .is_err(),
+        Err(_) => true,
+    };
+    assert!(refused);
+}
+
+#[test]
+fn tls_cert_without_a_key_is_rejected() {
+    let output = Command::new(env!("CARGO_BIN_EXE_c-sharp-analyzer-provider-cli"))
+        .args(["--port", "0", "--tls-cert"])
+        .arg(tls_file("server.pem"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--tls-key"), "{}", stderr);
+}